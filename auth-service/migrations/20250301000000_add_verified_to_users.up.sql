@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Accounts created before email verification existed are trusted as they are
+UPDATE users SET verified = TRUE;
\ No newline at end of file
@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS totp_secrets;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS requires_2fa BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET requires_2fa = (two_fa_method <> 'none');
+
+ALTER TABLE users DROP COLUMN IF EXISTS two_fa_method;
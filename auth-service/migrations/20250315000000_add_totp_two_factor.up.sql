@@ -0,0 +1,13 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'none';
+
+UPDATE users SET two_fa_method = 'email' WHERE requires_2fa;
+
+ALTER TABLE users DROP COLUMN IF EXISTS requires_2fa;
+
+-- Secrets are encrypted by the application before they are stored
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   encrypted_secret BYTEA NOT NULL,
+   confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+   last_used_step BIGINT
+);
@@ -0,0 +1,18 @@
+-- Emails are deleted once delivered. Rows with status 'dead' ran out of attempts and are
+-- kept until they are replayed.
+CREATE TABLE IF NOT EXISTS email_outbox(
+   id UUID PRIMARY KEY,
+   -- Emails that are due at the same time go out in the order they were queued
+   position BIGSERIAL,
+   recipient TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   html_body TEXT NOT NULL,
+   text_body TEXT NOT NULL,
+   status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'dead')),
+   attempts INTEGER NOT NULL DEFAULT 0,
+   last_error TEXT,
+   created_at BIGINT NOT NULL,
+   next_attempt_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS email_outbox_due_idx ON email_outbox(next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS users_email_lower_idx;
+DROP TABLE IF EXISTS lowercased_email_local_parts;
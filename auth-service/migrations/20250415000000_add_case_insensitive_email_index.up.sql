@@ -0,0 +1,34 @@
+-- Rows from before addresses were canonicalized may differ from the canonical form in case.
+-- Lowercase them, along with the tables keyed by email, so lookups can match exactly.
+-- Fails if two accounts differ by case alone, which have to be merged by hand first.
+ALTER TABLE totp_secrets DROP CONSTRAINT IF EXISTS totp_secrets_email_fkey;
+ALTER TABLE recovery_codes DROP CONSTRAINT IF EXISTS recovery_codes_email_fkey;
+
+-- Authenticator secrets are encrypted with the address they were enrolled under, so ones
+-- whose address changes can't be read anymore. Those accounts fall back to email codes.
+UPDATE users SET two_fa_method = 'email'
+WHERE two_fa_method = 'totp' AND email <> lower(email);
+DELETE FROM totp_secrets WHERE email <> lower(email);
+
+-- Owners of addresses whose local part is lowercased here have to type it in lowercase from
+-- now on, which the case-sensitive local part policy won't do for them. The service refuses
+-- to start with that policy while any are listed; restore their addresses by hand first.
+CREATE TABLE IF NOT EXISTS lowercased_email_local_parts (
+    email TEXT PRIMARY KEY
+);
+INSERT INTO lowercased_email_local_parts (email)
+SELECT lower(email) FROM users
+WHERE split_part(email, '@', 1) <> lower(split_part(email, '@', 1))
+ON CONFLICT DO NOTHING;
+
+UPDATE users SET email = lower(email) WHERE email <> lower(email);
+UPDATE recovery_codes SET email = lower(email) WHERE email <> lower(email);
+
+ALTER TABLE totp_secrets ADD CONSTRAINT totp_secrets_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
+ALTER TABLE recovery_codes ADD CONSTRAINT recovery_codes_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
+
+-- With the case-sensitive local part policy addresses keep their case, but no two accounts
+-- may still differ by case alone
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email));
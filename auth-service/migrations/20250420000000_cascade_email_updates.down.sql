@@ -0,0 +1,7 @@
+ALTER TABLE recovery_codes DROP CONSTRAINT IF EXISTS recovery_codes_email_fkey;
+ALTER TABLE recovery_codes ADD CONSTRAINT recovery_codes_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
+
+ALTER TABLE totp_secrets DROP CONSTRAINT IF EXISTS totp_secrets_email_fkey;
+ALTER TABLE totp_secrets ADD CONSTRAINT totp_secrets_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE;
@@ -0,0 +1,8 @@
+-- Changing an account's email carries its authenticator and recovery codes along
+ALTER TABLE totp_secrets DROP CONSTRAINT IF EXISTS totp_secrets_email_fkey;
+ALTER TABLE totp_secrets ADD CONSTRAINT totp_secrets_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON UPDATE CASCADE ON DELETE CASCADE;
+
+ALTER TABLE recovery_codes DROP CONSTRAINT IF EXISTS recovery_codes_email_fkey;
+ALTER TABLE recovery_codes ADD CONSTRAINT recovery_codes_email_fkey
+   FOREIGN KEY (email) REFERENCES users(email) ON UPDATE CASCADE ON DELETE CASCADE;
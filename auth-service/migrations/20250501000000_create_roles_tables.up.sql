@@ -0,0 +1,41 @@
+-- Roles are granted to users and carried in their access tokens. Permissions spell out what
+-- each role allows, for services that want finer checks than a role name.
+CREATE TABLE IF NOT EXISTS roles(
+   name TEXT PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS permissions(
+   name TEXT PRIMARY KEY,
+   description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions(
+   role TEXT NOT NULL REFERENCES roles(name) ON UPDATE CASCADE ON DELETE CASCADE,
+   permission TEXT NOT NULL REFERENCES permissions(name) ON UPDATE CASCADE ON DELETE CASCADE,
+   PRIMARY KEY (role, permission)
+);
+
+CREATE TABLE IF NOT EXISTS user_roles(
+   email TEXT NOT NULL REFERENCES users(email) ON UPDATE CASCADE ON DELETE CASCADE,
+   role TEXT NOT NULL REFERENCES roles(name) ON UPDATE CASCADE ON DELETE CASCADE,
+   PRIMARY KEY (email, role)
+);
+
+CREATE INDEX IF NOT EXISTS user_roles_role_idx ON user_roles(role);
+
+-- The first admin has to be granted by hand:
+-- INSERT INTO user_roles (email, role) VALUES ('someone@example.com', 'admin');
+INSERT INTO roles (name, description) VALUES
+   ('admin', 'Manages users and their roles')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO permissions (name, description) VALUES
+   ('users:read', 'Look up users'),
+   ('users:write', 'Change users and their roles')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+   ('admin', 'users:read'),
+   ('admin', 'users:write')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,15 @@
+-- Disabled users can't log in until an admin enables them again
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Every action taken through the admin API. Emails are kept as they were at the time, so
+-- entries outlive the accounts they name.
+CREATE TABLE IF NOT EXISTS admin_audit_log(
+   id BIGSERIAL PRIMARY KEY,
+   actor TEXT NOT NULL,
+   action TEXT NOT NULL,
+   target TEXT,
+   details TEXT,
+   created_at BIGINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS admin_audit_log_target_idx ON admin_audit_log(target);
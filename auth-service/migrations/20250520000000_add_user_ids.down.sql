@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS users_id_idx;
+
+ALTER TABLE users DROP COLUMN IF EXISTS id;
@@ -0,0 +1,5 @@
+-- An id for each account that, unlike the email, never changes. TOTP secrets are bound to
+-- it, so they can still be decrypted after the account changes its email.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid();
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_id_idx ON users(id);
@@ -1,34 +1,116 @@
+use axum::http::HeaderName;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AdminAuditStore, BannedTokenStore, EmailChangeTokenStore, EmailClient, EmailOutboxStore,
+        EmailVerificationTokenStore, LoginAttemptStore, PasswordPolicy, PasswordResetTokenStore,
+        RecoveryCodeStore, RefreshTokenStore, SessionStore, TotpSecretStore, TwoFACodeStore,
+        UserStore,
+    },
+    services::email_outbox::EmailOutbox,
+    utils::auth::Keyring,
+};
 
 // Using a type alias to improve readability!
-pub type UserStoreType = Arc<RwLock<dyn UserStore>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore>>;
-pub type EmailClientType = Arc<RwLock<dyn EmailClient>>;
+pub type UserStoreType = Arc<dyn UserStore>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore>;
+pub type RefreshTokenStoreType = Arc<dyn RefreshTokenStore>;
+pub type PasswordResetTokenStoreType = Arc<dyn PasswordResetTokenStore>;
+pub type EmailVerificationTokenStoreType = Arc<dyn EmailVerificationTokenStore>;
+pub type EmailChangeTokenStoreType = Arc<dyn EmailChangeTokenStore>;
+pub type TotpSecretStoreType = Arc<dyn TotpSecretStore>;
+pub type RecoveryCodeStoreType = Arc<dyn RecoveryCodeStore>;
+pub type LoginAttemptStoreType = Arc<dyn LoginAttemptStore>;
+pub type SessionStoreType = Arc<dyn SessionStore>;
+pub type AdminAuditStoreType = Arc<dyn AdminAuditStore>;
+pub type EmailOutboxStoreType = Arc<dyn EmailOutboxStore>;
+pub type EmailClientType = Arc<dyn EmailClient>;
+pub type KeyringType = Arc<RwLock<Keyring>>;
+
+// Whether `login` lets users in before they have confirmed their email address
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmailVerificationPolicy {
+    Optional,
+    Required,
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub email_verification_token_store: EmailVerificationTokenStoreType,
+    pub email_change_token_store: EmailChangeTokenStoreType,
+    pub totp_secret_store: TotpSecretStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub login_attempt_store: LoginAttemptStoreType,
+    pub session_store: SessionStoreType,
+    pub admin_audit_store: AdminAuditStoreType,
+    pub email_outbox: EmailOutbox,
+    pub keyring: KeyringType,
+    pub email_verification_policy: EmailVerificationPolicy,
+    pub password_policy: Arc<PasswordPolicy>,
+    // Header the reverse proxy puts the client's address in. Without it the peer address of
+    // the connection is used.
+    pub trusted_proxy_header: Option<HeaderName>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
-        email_client: EmailClientType,
+        refresh_token_store: RefreshTokenStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        email_verification_token_store: EmailVerificationTokenStoreType,
+        email_change_token_store: EmailChangeTokenStoreType,
+        totp_secret_store: TotpSecretStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        login_attempt_store: LoginAttemptStoreType,
+        session_store: SessionStoreType,
+        admin_audit_store: AdminAuditStoreType,
+        email_outbox: EmailOutbox,
+        keyring: KeyringType,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
-            email_client,
+            refresh_token_store,
+            password_reset_token_store,
+            email_verification_token_store,
+            email_change_token_store,
+            totp_secret_store,
+            recovery_code_store,
+            login_attempt_store,
+            session_store,
+            admin_audit_store,
+            email_outbox,
+            keyring,
+            email_verification_policy: EmailVerificationPolicy::Optional,
+            password_policy: Arc::new(PasswordPolicy::default()),
+            trusted_proxy_header: None,
         }
     }
+
+    pub fn with_email_verification_policy(mut self, policy: EmailVerificationPolicy) -> Self {
+        self.email_verification_policy = policy;
+        self
+    }
+
+    pub fn with_password_policy(mut self, policy: PasswordPolicy) -> Self {
+        self.password_policy = Arc::new(policy);
+        self
+    }
+
+    pub fn with_trusted_proxy_header(mut self, header: Option<HeaderName>) -> Self {
+        self.trusted_proxy_header = header;
+        self
+    }
 }
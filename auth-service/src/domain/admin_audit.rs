@@ -0,0 +1,104 @@
+use chrono::Utc;
+
+use super::Email;
+
+// Something an admin did through the admin API. Looking users up counts too, since it shows
+// their personal data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdminAction {
+    ListUsers,
+    ViewUser,
+    DisableUser,
+    EnableUser,
+    ForcePasswordReset,
+    ResetTwoFA,
+    RevokeTokens,
+    GrantRole,
+    RevokeRole,
+}
+
+impl AdminAction {
+    pub fn parse(action: &str) -> Result<Self, String> {
+        match action {
+            "list_users" => Ok(Self::ListUsers),
+            "view_user" => Ok(Self::ViewUser),
+            "disable_user" => Ok(Self::DisableUser),
+            "enable_user" => Ok(Self::EnableUser),
+            "force_password_reset" => Ok(Self::ForcePasswordReset),
+            "reset_2fa" => Ok(Self::ResetTwoFA),
+            "revoke_tokens" => Ok(Self::RevokeTokens),
+            "grant_role" => Ok(Self::GrantRole),
+            "revoke_role" => Ok(Self::RevokeRole),
+            _ => Err(format!("Unknown admin action: {}", action)),
+        }
+    }
+}
+
+impl AsRef<str> for AdminAction {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::ListUsers => "list_users",
+            Self::ViewUser => "view_user",
+            Self::DisableUser => "disable_user",
+            Self::EnableUser => "enable_user",
+            Self::ForcePasswordReset => "force_password_reset",
+            Self::ResetTwoFA => "reset_2fa",
+            Self::RevokeTokens => "revoke_tokens",
+            Self::GrantRole => "grant_role",
+            Self::RevokeRole => "revoke_role",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminAuditEntry {
+    pub actor: Email,
+    pub action: AdminAction,
+    // The user acted on, if the action concerns one
+    pub target: Option<Email>,
+    // E.g. the role granted or the search that was run
+    pub details: Option<String>,
+    // Seconds since the epoch
+    pub created_at: i64,
+}
+
+impl AdminAuditEntry {
+    pub fn new(
+        actor: Email,
+        action: AdminAction,
+        target: Option<Email>,
+        details: Option<String>,
+    ) -> Self {
+        Self {
+            actor,
+            action,
+            target,
+            details,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_what_it_writes() {
+        for action in [
+            AdminAction::ListUsers,
+            AdminAction::ViewUser,
+            AdminAction::DisableUser,
+            AdminAction::EnableUser,
+            AdminAction::ForcePasswordReset,
+            AdminAction::ResetTwoFA,
+            AdminAction::RevokeTokens,
+            AdminAction::GrantRole,
+            AdminAction::RevokeRole,
+        ] {
+            assert_eq!(AdminAction::parse(action.as_ref()), Ok(action));
+        }
+
+        assert!(AdminAction::parse("drop_tables").is_err());
+    }
+}
@@ -1,14 +1,60 @@
-use rand::Rng;
+use rand::{distributions::Alphanumeric, Rng};
+use subtle::ConstantTimeEq;
 use uuid::Uuid;
 
-use super::{Email, Password, User};
+use super::{
+    AdminAuditEntry, Email, LoginAttemptKey, LoginFailures, OutboxEmail, Password, Role, Session,
+    TotpSecret, TwoFAMethod, User,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore: Sync + Send {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+    // Moves the account to an address the user has confirmed, so it counts as verified.
+    // Fails with `UserAlreadyExists` if another account has `new_email`.
+    async fn update_email(&self, email: &Email, new_email: Email) -> Result<(), UserStoreError>;
+    // Turning 2FA on asks for email codes, unless a method is already in use. Turning it off
+    // drops whichever method that was.
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError>;
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_disabled(&self, email: &Email, disabled: bool) -> Result<(), UserStoreError>;
+    // Users ordered by email. `search` matches any part of the email, ignoring case.
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<UserPage, UserStoreError>;
+    // Sorted by name
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError>;
+    // Assigning a role the user already has does nothing. Stores that keep a list of roles
+    // fail with `RoleNotFound` for any role that isn't on it.
+    async fn assign_role(&self, email: &Email, role: Role) -> Result<(), UserStoreError>;
+    async fn remove_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError>;
+}
+
+pub struct UserPage {
+    pub users: Vec<User>,
+    // Users matching the search across all pages
+    pub total: u64,
 }
 
 #[derive(Debug, PartialEq)]
@@ -16,13 +62,24 @@ pub enum UserStoreError {
     UserAlreadyExists,
     UserNotFound,
     InvalidCredentials,
+    RoleNotFound,
     UnexpectedError,
 }
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError>;
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Bans every token issued to `email` at or before `timestamp` (seconds since the epoch)
+    async fn revoke_user_tokens(
+        &self,
+        email: &Email,
+        timestamp: usize,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn get_user_revocation(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError>;
 }
 
 #[derive(Debug)]
@@ -30,22 +87,455 @@ pub enum BannedTokenStoreError {
     UnexpectedError,
 }
 
+// Refresh tokens are single use. Every rotation issues a new token in the same family, and
+// presenting a token that has already been consumed is treated as theft of the family.
+#[async_trait::async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn consume_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError>;
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenStoreError>;
+    async fn revoke_user_families(&self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    TokenReused(RefreshTokenFamily),
+    FamilyRevoked,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenFamily {
+    pub id: String,
+    pub email: Email,
+}
+
+impl RefreshTokenFamily {
+    pub fn new(email: Email) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            email,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if token.len() == REFRESH_TOKEN_LENGTH && token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(token))
+        } else {
+            Err("Invalid refresh token".to_owned())
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        Self(generate_random_token(REFRESH_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+// Every signed-in device, keyed by session id. Access tokens are only accepted while their
+// session is in here, so removing a session signs that device out straight away.
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError>;
+    async fn touch_session(&self, id: &str, last_seen: i64) -> Result<(), SessionStoreError>;
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    async fn remove_session(&self, id: &str) -> Result<(), SessionStoreError>;
+    async fn remove_user_sessions(&self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+// Emails waiting to be delivered, plus the dead letters that ran out of attempts. Claiming
+// an email hides it from other workers for `lease_seconds`, so a worker that dies mid-send
+// doesn't lose it.
+#[async_trait::async_trait]
+pub trait EmailOutboxStore: Send + Sync {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError>;
+    // Leases the next due email, leaving out the ones in `skip`. Emails are claimed one at a
+    // time so that the lease only has to cover a single send.
+    async fn claim_next(
+        &self,
+        now: i64,
+        lease_seconds: u64,
+        skip: &[Uuid],
+    ) -> Result<Option<OutboxEmail>, EmailOutboxStoreError>;
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxStoreError>;
+    // Counts a failed delivery. Without a `retry_at` the email becomes a dead letter.
+    async fn mark_failed(
+        &self,
+        id: Uuid,
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> Result<(), EmailOutboxStoreError>;
+    async fn get_dead_letters(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError>;
+    // Puts a dead letter back in the queue with a fresh set of attempts
+    async fn replay_dead_letter(&self, id: Uuid, now: i64) -> Result<(), EmailOutboxStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailOutboxStoreError {
+    EmailNotFound,
+    UnexpectedError,
+}
+
+// This trait represents the interface all concrete password reset token stores should implement
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    // Looks a token up without using it, e.g. to check a new password before it is set
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    // Reset tokens are single use, so looking one up also removes it
+    async fn consume_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordResetTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if is_random_token(&token, PASSWORD_RESET_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err("Invalid password reset token".to_owned())
+        }
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        Self(generate_random_token(PASSWORD_RESET_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const PASSWORD_RESET_TOKEN_LENGTH: usize = 64;
+
+// This trait represents the interface all concrete email verification token stores should implement
+#[async_trait::async_trait]
+pub trait EmailVerificationTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError>;
+    async fn consume_token(
+        &self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError>;
+    // Claims the right to send another link to `email`. Fails with `ResendTooSoon` if it was
+    // claimed less than EMAIL_VERIFICATION_RESEND_SECONDS ago, so the route can't be used to
+    // flood someone's inbox.
+    async fn claim_resend(&self, email: &Email) -> Result<(), EmailVerificationTokenStoreError>;
+}
+
+pub const EMAIL_VERIFICATION_RESEND_SECONDS: u64 = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum EmailVerificationTokenStoreError {
+    TokenNotFound,
+    ResendTooSoon,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailVerificationToken(String);
+
+impl EmailVerificationToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if is_random_token(&token, EMAIL_VERIFICATION_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err("Invalid email verification token".to_owned())
+        }
+    }
+}
+
+impl Default for EmailVerificationToken {
+    fn default() -> Self {
+        Self(generate_random_token(EMAIL_VERIFICATION_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for EmailVerificationToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const EMAIL_VERIFICATION_TOKEN_LENGTH: usize = 64;
+
+// A change of address waiting to be confirmed from the new address
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailChange {
+    pub email: Email,
+    pub new_email: Email,
+}
+
+#[async_trait::async_trait]
+pub trait EmailChangeTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        change: EmailChange,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError>;
+    async fn consume_token(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<EmailChange, EmailChangeTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EmailChangeTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailChangeToken(String);
+
+impl EmailChangeToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if is_random_token(&token, EMAIL_CHANGE_TOKEN_LENGTH) {
+            Ok(Self(token))
+        } else {
+            Err("Invalid email change token".to_owned())
+        }
+    }
+}
+
+impl Default for EmailChangeToken {
+    fn default() -> Self {
+        Self(generate_random_token(EMAIL_CHANGE_TOKEN_LENGTH))
+    }
+}
+
+impl AsRef<str> for EmailChangeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const EMAIL_CHANGE_TOKEN_LENGTH: usize = 64;
+
+// One-time codes that stand in for the second factor when the user has lost access to it.
+// Issuing a new batch invalidates every code from the previous one.
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore: Send + Sync {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    async fn consume_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    // Users type these in by hand, so case, spaces and dashes are ignored
+    pub fn parse(code: String) -> Result<Self, String> {
+        let code: String = code
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        if code.len() == RECOVERY_CODE_LENGTH && code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(code))
+        } else {
+            Err("Invalid recovery code".to_owned())
+        }
+    }
+
+    pub fn generate_batch() -> Vec<Self> {
+        (0..RECOVERY_CODE_BATCH_SIZE)
+            .map(|_| Self::default())
+            .collect()
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        Self(
+            (0..RECOVERY_CODE_LENGTH)
+                .map(|_| {
+                    RECOVERY_CODE_CHARSET[rng.gen_range(0..RECOVERY_CODE_CHARSET.len())] as char
+                })
+                .collect(),
+        )
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+const RECOVERY_CODE_LENGTH: usize = 10;
+const RECOVERY_CODE_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+pub const RECOVERY_CODE_BATCH_SIZE: usize = 10;
+
+fn generate_random_token(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+fn is_random_token(token: &str, length: usize) -> bool {
+    token.len() == length && token.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+// Authenticator app secrets. A new secret stays pending until the user proves they can
+// produce codes from it, and the last accepted time step is kept so a code can't be replayed.
+#[async_trait::async_trait]
+pub trait TotpSecretStore: Send + Sync {
+    // Replaces any existing secret for `email` with a new, unconfirmed one
+    async fn add_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError>;
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError>;
+    async fn confirm_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError>;
+    // Fails with `StepAlreadyUsed` unless `step` is later than every step accepted before
+    async fn use_step(&self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError>;
+    // Succeeds whether or not there was a secret to delete
+    async fn delete_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TotpSecretStoreError {
+    SecretNotFound,
+    StepAlreadyUsed,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TotpEnrollment {
+    pub secret: TotpSecret,
+    pub confirmed: bool,
+}
+
+// Failed logins per email address and per client IP. A key's count is forgotten once its
+// policy's `lockout_seconds` have passed since its last failure.
+#[async_trait::async_trait]
+pub trait LoginAttemptStore: Send + Sync {
+    // Counts an attempt before the password is checked, so that a burst of parallel guesses
+    // can't all get past the throttle before any of them is counted. Returns the failures as
+    // they were before this attempt.
+    async fn record_attempt(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<LoginFailures, LoginAttemptStoreError>;
+    // Takes back a counted attempt that turned out not to be a failure
+    async fn forget_attempt(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError>;
+    // Marks a counted attempt as failed, which starts its delay
+    async fn record_failure(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError>;
+    async fn get_failures(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<LoginFailures>, LoginAttemptStoreError>;
+    async fn clear_failures(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoginAttemptStoreError {
+    UnexpectedError,
+}
+
 // This trait represents the interface all concrete 2FA code stores should implement
 #[async_trait::async_trait]
 pub trait TwoFACodeStore: Send + Sync {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    // Fails with `LoginAttemptIdNotFound` if the code is already gone, so that of two
+    // requests racing to redeem the same code only one succeeds
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Counts an attempt at the pending login attempt's code. Called before the code is
+    // checked, so that a burst of parallel guesses can't all be checked before any is counted.
+    // Past MAX_TWO_FA_ATTEMPTS the code is removed and `LoginAttemptIdNotFound` returned, so
+    // the user has to log in again to get a new one.
+    async fn record_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError>;
 }
 
+pub const MAX_TWO_FA_ATTEMPTS: u32 = 5;
+
 #[derive(Debug, PartialEq)]
 pub enum TwoFACodeStoreError {
     LoginAttemptIdNotFound,
@@ -77,9 +567,16 @@ impl AsRef<str> for LoginAttemptId {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct TwoFACode(pub String);
 
+// Compared in constant time so response times don't reveal how much of a guess was right
+impl PartialEq for TwoFACode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
 impl TwoFACode {
     pub fn parse(code: String) -> Result<Self, String> {
         if code.len() == 6 {
@@ -105,3 +602,26 @@ impl AsRef<str> for TwoFACode {
         &self.0
     }
 }
+
+// The audit trail of the admin API. Entries are never changed or removed.
+#[async_trait::async_trait]
+pub trait AdminAuditStore: Send + Sync {
+    async fn record(&self, entry: AdminAuditEntry) -> Result<(), AdminAuditStoreError>;
+    // Newest first, optionally only the entries about `target`
+    async fn list_entries(
+        &self,
+        target: Option<&Email>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<AdminAuditPage, AdminAuditStoreError>;
+}
+
+pub struct AdminAuditPage {
+    pub entries: Vec<AdminAuditEntry>,
+    pub total: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AdminAuditStoreError {
+    UnexpectedError,
+}
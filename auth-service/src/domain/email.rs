@@ -0,0 +1,208 @@
+use std::str::FromStr;
+
+use crate::utils::constants::EMAIL_LOCAL_PART_POLICY;
+
+// RFC 5321 limits on the whole path, the local part and the domain, in octets
+const MAX_EMAIL_LENGTH: usize = 254;
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_DOMAIN_LENGTH: usize = 253;
+// Characters a dot-atom local part may use besides letters and digits (RFC 5322 `atext`)
+const LOCAL_PART_SPECIALS: &str = "!#$%&'*+/=?^_`{|}~-";
+
+// Whether the local part keeps its case. RFC 5321 leaves that up to the receiving server,
+// but almost every provider ignores it, so by default `Bob@…` and `bob@…` are one account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocalPartPolicy {
+    #[default]
+    CaseInsensitive,
+    // Addresses keep the case they were signed up with. Postgres still won't hold two
+    // accounts whose addresses differ only by case.
+    CaseSensitive,
+}
+
+impl FromStr for LocalPartPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "case-insensitive" => Ok(LocalPartPolicy::CaseInsensitive),
+            "case-sensitive" => Ok(LocalPartPolicy::CaseSensitive),
+            _ => Err(format!("Unknown local part policy: {}", value)),
+        }
+    }
+}
+
+// An address in canonical form: the domain in lowercase ASCII, with international domains
+// punycoded, and the local part folded according to the `LocalPartPolicy`. Two spellings
+// of the same address parse to equal values, so the canonical form is what gets stored
+// and used as a key.
+#[derive(Eq, Hash, PartialEq, Debug, Clone)]
+pub struct Email(String);
+
+impl Email {
+    // Uses the policy configured through `EMAIL_LOCAL_PART_POLICY`
+    pub fn parse(email: &str) -> Result<Self, String> {
+        Self::parse_with_policy(email, *EMAIL_LOCAL_PART_POLICY)
+    }
+
+    // Only dot-atom local parts are accepted. Quoted ones and IP address literals for the
+    // domain are valid RFC 5322 but have no place in a sign up form.
+    pub fn parse_with_policy(email: &str, policy: LocalPartPolicy) -> Result<Self, String> {
+        let invalid = || format!("Invalid Email Address: {}", email);
+
+        let (local_part, domain) = email.trim().rsplit_once('@').ok_or_else(invalid)?;
+
+        if !is_valid_local_part(local_part) {
+            return Err(invalid());
+        }
+
+        let domain = canonical_domain(domain).ok_or_else(invalid)?;
+        let local_part = match policy {
+            LocalPartPolicy::CaseInsensitive => local_part.to_lowercase(),
+            LocalPartPolicy::CaseSensitive => local_part.to_owned(),
+        };
+
+        let email = format!("{}@{}", local_part, domain);
+        if email.len() > MAX_EMAIL_LENGTH {
+            return Err(invalid());
+        }
+
+        Ok(Self(email))
+    }
+}
+
+impl AsRef<str> for Email {
+    fn as_ref(&self) -> &str {
+        &self.0[..]
+    }
+}
+
+// Letters beyond ASCII are allowed as RFC 6531 does for internationalized addresses
+fn is_valid_local_part(local_part: &str) -> bool {
+    !local_part.is_empty()
+        && local_part.len() <= MAX_LOCAL_PART_LENGTH
+        && local_part.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom.chars().all(|c| {
+                    c.is_ascii_alphanumeric()
+                        || LOCAL_PART_SPECIALS.contains(c)
+                        || (!c.is_ascii() && c.is_alphanumeric())
+                })
+        })
+}
+
+// Lowercases and punycodes the domain, then checks it could be a public host name: at least
+// two labels of letters, digits and hyphens, and a top level label that isn't a number
+fn canonical_domain(domain: &str) -> Option<String> {
+    let domain = idna::domain_to_ascii_strict(domain).ok()?;
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid = domain.len() <= MAX_DOMAIN_LENGTH
+        && labels.len() >= 2
+        && labels.iter().all(|label| !label.is_empty())
+        && labels
+            .last()
+            .is_some_and(|tld| !tld.chars().all(|c| c.is_ascii_digit()));
+
+    valid.then_some(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(email: &str) -> Result<String, String> {
+        Email::parse_with_policy(email, LocalPartPolicy::CaseInsensitive)
+            .map(|email| email.as_ref().to_owned())
+    }
+
+    #[test]
+    fn should_accept_valid_addresses() {
+        for email in [
+            "a@b.co",
+            "first.last@example.com",
+            "user+tag@sub.example.co.uk",
+            "o'brien@example.ie",
+            "x_y-z!#$%&*=?^`{|}~@example.com",
+        ] {
+            assert_eq!(parse(email), Ok(email.to_owned()));
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_addresses() {
+        for email in [
+            "",
+            "invalid_email",
+            "@example.com",
+            "user@",
+            "user@localhost",
+            ".user@example.com",
+            "user.@example.com",
+            "us..er@example.com",
+            "\"quoted\"@example.com",
+            "us er@example.com",
+            "user@exa mple.com",
+            "user@-example.com",
+            "user@example..com",
+            "user@[127.0.0.1]",
+            "user@127.0.0.1",
+            "a@b@example.com",
+        ] {
+            assert!(parse(email).is_err(), "Accepted {:?}", email);
+        }
+    }
+
+    #[test]
+    fn should_enforce_length_limits() {
+        let local_part = "a".repeat(MAX_LOCAL_PART_LENGTH);
+        assert!(parse(&format!("{}@example.com", local_part)).is_ok());
+        assert!(parse(&format!("a{}@example.com", local_part)).is_err());
+
+        let domain = format!("{}.com", vec!["a".repeat(60); 4].join("."));
+        assert!(parse(&format!("{}@{}", local_part, domain)).is_err());
+    }
+
+    #[test]
+    fn should_fold_domain_case() {
+        assert_eq!(
+            Email::parse_with_policy("Bob@Example.COM", LocalPartPolicy::CaseSensitive)
+                .unwrap()
+                .as_ref(),
+            "Bob@example.com"
+        );
+    }
+
+    #[test]
+    fn should_fold_local_part_case_only_if_case_insensitive() {
+        assert_eq!(parse("Bob@Example.com"), parse("bob@example.com"));
+        assert_ne!(
+            Email::parse_with_policy("Bob@example.com", LocalPartPolicy::CaseSensitive),
+            Email::parse_with_policy("bob@example.com", LocalPartPolicy::CaseSensitive)
+        );
+    }
+
+    #[test]
+    fn should_punycode_international_domains() {
+        assert_eq!(
+            parse("user@Bücher.de"),
+            Ok("user@xn--bcher-kva.de".to_owned())
+        );
+        assert_eq!(parse("user@xn--bcher-kva.de"), parse("user@bücher.de"));
+        assert_eq!(parse("josé@例え.jp"), Ok("josé@xn--r8jz45g.jp".to_owned()));
+    }
+
+    #[test]
+    fn should_trim_surrounding_whitespace() {
+        assert_eq!(
+            parse("  bob@example.com\n"),
+            Ok("bob@example.com".to_owned())
+        );
+    }
+
+    #[test]
+    fn should_parse_canonical_form_to_itself() {
+        let email = parse("José.Álvarez+News@Bücher.DE").unwrap();
+        assert_eq!(parse(&email), Ok(email));
+    }
+}
@@ -1,12 +1,15 @@
 use super::Email;
 
+// A rendered email with HTML and plain-text alternatives of the same content
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
 // This trait represents the interface all concrete email clients should implement
 #[async_trait::async_trait]
 pub trait EmailClient: Sync + Send {
-    async fn send_email(
-        &self,
-        recipient: &Email,
-        subject: &str,
-        content: &str,
-    ) -> Result<(), String>;
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String>;
 }
@@ -0,0 +1,89 @@
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{Email, EmailMessage};
+
+// An email waiting in the outbox. It stays there until it is delivered, or it is moved to
+// the dead letters once `EmailRetryPolicy::max_attempts` deliveries have failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    // Seconds since the epoch
+    pub created_at: i64,
+}
+
+impl OutboxEmail {
+    pub fn new(recipient: Email, message: EmailMessage) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            recipient,
+            message,
+            attempts: 0,
+            last_error: None,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+}
+
+// Failed deliveries are retried after `base_delay_seconds`, doubling with every further
+// failure up to `max_delay_seconds`. After `max_attempts` failures the email is dead-lettered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailRetryPolicy {
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+    pub max_attempts: u32,
+}
+
+pub const EMAIL_RETRY_POLICY: EmailRetryPolicy = EmailRetryPolicy {
+    base_delay_seconds: 5,
+    max_delay_seconds: 60 * 60,
+    max_attempts: 8,
+};
+
+impl EmailRetryPolicy {
+    // When to try again after the `attempts`th failed delivery, or `None` once the email
+    // should be given up on
+    pub fn next_attempt_at(&self, attempts: u32, now: i64) -> Option<i64> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+
+        let doublings = attempts.saturating_sub(1).min(31);
+        let delay = (self.base_delay_seconds << doublings).min(self.max_delay_seconds);
+
+        Some(now + delay as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_back_off_exponentially() {
+        let now = 1_000;
+
+        assert_eq!(EMAIL_RETRY_POLICY.next_attempt_at(1, now), Some(now + 5));
+        assert_eq!(EMAIL_RETRY_POLICY.next_attempt_at(2, now), Some(now + 10));
+        assert_eq!(EMAIL_RETRY_POLICY.next_attempt_at(4, now), Some(now + 40));
+    }
+
+    #[test]
+    fn should_cap_delay() {
+        let policy = EmailRetryPolicy {
+            max_attempts: 100,
+            ..EMAIL_RETRY_POLICY
+        };
+
+        assert_eq!(policy.next_attempt_at(40, 0), Some(60 * 60));
+    }
+
+    #[test]
+    fn should_give_up_after_max_attempts() {
+        assert_eq!(EMAIL_RETRY_POLICY.next_attempt_at(8, 0), None);
+    }
+}
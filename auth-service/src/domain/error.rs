@@ -1,3 +1,5 @@
+use super::PasswordPolicyViolation;
+
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
@@ -5,4 +7,17 @@ pub enum AuthAPIError {
     IncorrectCredentials,
     MissingToken,
     InvalidToken,
+    EmailNotVerified,
+    AccountDisabled,
+    TwoFAAlreadyEnabled,
+    SessionNotFound,
+    // The access token is valid but lacks a role the route needs
+    Forbidden,
+    UserNotFound,
+    UnknownRole,
+    // Seconds until the client may try again
+    TooManyLoginAttempts(u64),
+    TooManyRequests(u64),
+    // Every rule a new password breaks
+    WeakPassword(Vec<PasswordPolicyViolation>),
 }
@@ -0,0 +1,70 @@
+// Languages emails can be sent in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    pub fn parse(tag: &str) -> Option<Self> {
+        // Only the primary language matters, so `es-MX` gets the Spanish templates
+        let language = tag.split(['-', '_']).next()?.trim();
+
+        match language.to_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "es" => Some(Locale::Es),
+            _ => None,
+        }
+    }
+
+    // Picks the supported language the client prefers most, based on the q-values of an
+    // `Accept-Language` header. Falls back to English.
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut preferences: Vec<(f32, Locale)> = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let locale = Self::parse(parts.next()?)?;
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse().ok())?;
+                (quality > 0.0).then_some((quality, locale))
+            })
+            .collect();
+
+        // Stable, so equally weighted languages keep the client's order
+        preferences.sort_by(|a, b| b.0.total_cmp(&a.0));
+        preferences
+            .first()
+            .map(|(_, locale)| *locale)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_pick_most_preferred_supported_locale() {
+        assert_eq!(Locale::from_accept_language("es-MX,es;q=0.9"), Locale::Es);
+        assert_eq!(
+            Locale::from_accept_language("fr, es;q=0.8, en;q=0.5"),
+            Locale::Es
+        );
+        assert_eq!(
+            Locale::from_accept_language("en;q=0.4, es;q=0.6"),
+            Locale::Es
+        );
+        assert_eq!(Locale::from_accept_language("en-US,es"), Locale::En);
+    }
+
+    #[test]
+    fn should_fall_back_to_english() {
+        assert_eq!(Locale::from_accept_language(""), Locale::En);
+        assert_eq!(Locale::from_accept_language("fr-FR,de"), Locale::En);
+        assert_eq!(Locale::from_accept_language("es;q=0"), Locale::En);
+        assert_eq!(Locale::from_accept_language("*"), Locale::En);
+    }
+}
@@ -0,0 +1,144 @@
+use std::net::IpAddr;
+
+use super::Email;
+
+// Failed logins are counted separately per email address and per client IP. A shared IP
+// (e.g. an office behind NAT) gets more leeway than a single account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LoginAttemptKey {
+    Email(Email),
+    Ip(IpAddr),
+}
+
+impl LoginAttemptKey {
+    pub fn policy(&self) -> &'static LoginThrottlePolicy {
+        match self {
+            LoginAttemptKey::Email(_) => &EMAIL_LOGIN_THROTTLE,
+            LoginAttemptKey::Ip(_) => &IP_LOGIN_THROTTLE,
+        }
+    }
+}
+
+// The first `free_attempts` failures cost nothing. Every failure after that doubles the wait
+// before the next attempt, starting at `base_delay_seconds`, until `lockout_threshold` is
+// reached and logins are refused for `lockout_seconds`. Counts are forgotten once
+// `lockout_seconds` have passed since the last failure.
+#[derive(Debug, PartialEq)]
+pub struct LoginThrottlePolicy {
+    pub free_attempts: u32,
+    pub base_delay_seconds: u64,
+    pub lockout_threshold: u32,
+    pub lockout_seconds: u64,
+}
+
+pub const EMAIL_LOGIN_THROTTLE: LoginThrottlePolicy = LoginThrottlePolicy {
+    free_attempts: 3,
+    base_delay_seconds: 1,
+    lockout_threshold: 10,
+    lockout_seconds: 15 * 60,
+};
+
+pub const IP_LOGIN_THROTTLE: LoginThrottlePolicy = LoginThrottlePolicy {
+    free_attempts: 20,
+    base_delay_seconds: 1,
+    lockout_threshold: 100,
+    lockout_seconds: 15 * 60,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoginFailures {
+    pub count: u32,
+    // Seconds since the epoch
+    pub last_failure: i64,
+}
+
+impl LoginFailures {
+    fn delay_seconds(&self, policy: &LoginThrottlePolicy) -> u64 {
+        if self.count >= policy.lockout_threshold {
+            policy.lockout_seconds
+        } else if self.count >= policy.free_attempts {
+            let doublings = (self.count - policy.free_attempts).min(31);
+            (policy.base_delay_seconds << doublings).min(policy.lockout_seconds)
+        } else {
+            0
+        }
+    }
+
+    // Seconds until the next attempt is allowed, or `None` if it is allowed now
+    pub fn retry_after(&self, policy: &LoginThrottlePolicy, now: i64) -> Option<u64> {
+        let allowed_at = self.last_failure + self.delay_seconds(policy) as i64;
+
+        if allowed_at > now {
+            Some((allowed_at - now) as u64)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failures(count: u32) -> LoginFailures {
+        LoginFailures {
+            count,
+            last_failure: 1_000,
+        }
+    }
+
+    #[test]
+    fn test_free_attempts_are_not_delayed() {
+        for count in 0..EMAIL_LOGIN_THROTTLE.free_attempts {
+            assert_eq!(
+                failures(count).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_delay_doubles_after_free_attempts() {
+        assert_eq!(
+            failures(3).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+            Some(1)
+        );
+        assert_eq!(
+            failures(4).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+            Some(2)
+        );
+        assert_eq!(
+            failures(9).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+            Some(64)
+        );
+
+        // Time already waited counts towards the delay
+        assert_eq!(
+            failures(9).retry_after(&EMAIL_LOGIN_THROTTLE, 1_060),
+            Some(4)
+        );
+        assert_eq!(failures(9).retry_after(&EMAIL_LOGIN_THROTTLE, 1_064), None);
+    }
+
+    #[test]
+    fn test_lockout_after_threshold() {
+        assert_eq!(
+            failures(10).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+            Some(15 * 60)
+        );
+        assert_eq!(
+            failures(50).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000),
+            Some(15 * 60)
+        );
+        assert_eq!(
+            failures(50).retry_after(&EMAIL_LOGIN_THROTTLE, 1_000 + 15 * 60),
+            None
+        );
+    }
+
+    #[test]
+    fn test_ip_policy_is_more_lenient() {
+        let key = LoginAttemptKey::Ip("127.0.0.1".parse().unwrap());
+        assert_eq!(failures(10).retry_after(key.policy(), 1_000), None);
+    }
+}
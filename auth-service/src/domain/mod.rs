@@ -1,33 +1,33 @@
+pub mod admin_audit;
 pub mod data_stores;
+pub mod email;
 pub mod email_client;
+pub mod email_outbox;
 pub mod error;
+pub mod locale;
+pub mod login_throttle;
+pub mod password_policy;
+pub mod role;
+pub mod session;
+pub mod totp;
 pub mod user;
 
+pub use admin_audit::*;
 pub use data_stores::*;
+pub use email::*;
 pub use email_client::*;
+pub use email_outbox::*;
 pub use error::*;
+pub use locale::*;
+pub use login_throttle::*;
+pub use password_policy::*;
+pub use role::*;
+pub use session::*;
+pub use totp::*;
 pub use user::*;
 
 use core::convert::AsRef;
 
-#[derive(Eq, Hash, PartialEq, Debug, Clone)]
-pub struct Email(String);
-
-impl Email {
-    pub fn parse(email: &str) -> Result<Self, String> {
-        if !email.contains("@") || email.len() < 8 {
-            return Err(format!("Invalid Email Address: {}", email));
-        }
-        Ok(Self(email.to_owned()))
-    }
-}
-
-impl AsRef<str> for Email {
-    fn as_ref(&self) -> &str {
-        &self.0[..]
-    }
-}
-
 #[derive(Eq, Hash, PartialEq, Debug, Clone)]
 pub struct Password(String);
 
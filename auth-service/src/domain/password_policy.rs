@@ -0,0 +1,404 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use super::{Email, Password};
+
+// Scored by `estimate_entropy_bits`. 40 bits takes e.g. 9 random lowercase letters or 7
+// random characters drawn from letters, digits and symbols.
+pub const DEFAULT_PASSWORD_MIN_ENTROPY_BITS: f64 = 40.0;
+// Shorter local parts turn up inside unrelated passwords by chance
+const MIN_CHECKED_LOCAL_PART_LENGTH: usize = 4;
+const BUNDLED_BREACHED_PASSWORDS: &str = include_str!("../../data/breached_passwords.txt");
+// Pwned Passwords range queries split hashes after this many hex digits too
+const HASH_PREFIX_LENGTH: usize = 5;
+
+// Why a new password was turned down. `Password::parse` only checks the length, since it
+// also parses the passwords users log in with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordPolicyViolation {
+    TooWeak,
+    Breached,
+    ContainsEmail,
+}
+
+impl AsRef<str> for PasswordPolicyViolation {
+    fn as_ref(&self) -> &str {
+        match self {
+            PasswordPolicyViolation::TooWeak => "too_weak",
+            PasswordPolicyViolation::Breached => "breached",
+            PasswordPolicyViolation::ContainsEmail => "contains_email",
+        }
+    }
+}
+
+// What passwords chosen at signup or on a reset have to live up to
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    min_entropy_bits: f64,
+    breached_passwords: BreachedPasswords,
+}
+
+impl PasswordPolicy {
+    pub fn new(min_entropy_bits: f64, breached_passwords: BreachedPasswords) -> Self {
+        Self {
+            min_entropy_bits,
+            breached_passwords,
+        }
+    }
+
+    // Lists every rule the password breaks, so they can all be fixed in one go
+    pub fn check(
+        &self,
+        password: &Password,
+        email: &Email,
+    ) -> Result<(), Vec<PasswordPolicyViolation>> {
+        let mut violations = Vec::new();
+
+        if estimate_entropy_bits(password.as_ref()) < self.min_entropy_bits {
+            violations.push(PasswordPolicyViolation::TooWeak);
+        }
+
+        if self.breached_passwords.contains(password.as_ref()) {
+            violations.push(PasswordPolicyViolation::Breached);
+        }
+
+        if contains_local_part(password.as_ref(), email) {
+            violations.push(PasswordPolicyViolation::ContainsEmail);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_PASSWORD_MIN_ENTROPY_BITS,
+            BreachedPasswords::bundled(),
+        )
+    }
+}
+
+// SHA-1 hashes of known breached passwords. Everything is looked up locally, so no password
+// or hash prefix ever leaves the service.
+#[derive(Debug)]
+pub struct BreachedPasswords {
+    corpus: Corpus,
+}
+
+#[derive(Debug)]
+enum Corpus {
+    // Indexed the way Pwned Passwords range queries are: by the first five hex digits, with
+    // the rest of each hash kept under that prefix
+    InMemory(HashMap<String, HashSet<String>>),
+    // The full download runs to tens of gigabytes, so it stays on disk and is binary
+    // searched on every lookup
+    SortedFile(PathBuf),
+}
+
+impl BreachedPasswords {
+    // The few hundred most common passwords, compiled in
+    pub fn bundled() -> Self {
+        Self::from_reader(BUNDLED_BREACHED_PASSWORDS.as_bytes())
+            .expect("Bundled breached passwords are valid")
+    }
+
+    // Uses a Pwned Passwords download "ordered by hash" in place, one `HASH` or `HASH:COUNT`
+    // per line. Only the first line is checked here; lines out of order are missed by
+    // lookups rather than reported.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let mut file = BufReader::new(
+            File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?,
+        );
+
+        match read_line_hash(&mut file).map_err(|e| e.to_string())? {
+            Some(hash) if is_sha1_hex(&hash) => Ok(Self {
+                corpus: Corpus::SortedFile(path.to_owned()),
+            }),
+            _ => Err("Line 1 is not a SHA-1 hash".to_owned()),
+        }
+    }
+
+    // Reads a short list into memory. Lines may come in any order, and blank lines and ones
+    // starting with `#` are skipped.
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, String> {
+        let mut ranges: HashMap<String, HashSet<String>> = HashMap::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let hash = line.split(':').next().unwrap_or_default();
+            if !is_sha1_hex(hash) {
+                return Err(format!("Line {} is not a SHA-1 hash", number + 1));
+            }
+
+            let (prefix, suffix) = hash.split_at(HASH_PREFIX_LENGTH);
+            ranges
+                .entry(prefix.to_uppercase())
+                .or_default()
+                .insert(suffix.to_uppercase());
+        }
+
+        Ok(Self {
+            corpus: Corpus::InMemory(ranges),
+        })
+    }
+
+    // A corpus that can't be read counts as not containing the password, so signups don't
+    // fail along with the disk
+    pub fn contains(&self, password: &str) -> bool {
+        let hash = sha1_hex(password);
+
+        match &self.corpus {
+            Corpus::InMemory(ranges) => {
+                let (prefix, suffix) = hash.split_at(HASH_PREFIX_LENGTH);
+                ranges
+                    .get(prefix)
+                    .is_some_and(|suffixes| suffixes.contains(suffix))
+            }
+            Corpus::SortedFile(path) => search_sorted_file(path, &hash).unwrap_or_else(|e| {
+                eprintln!("Failed to search breached passwords: {}", e);
+                false
+            }),
+        }
+    }
+}
+
+// Finds the smallest offset whose next line holds a hash no lower than `hash`, then checks
+// whether that line is the hash itself. Each probe reads a single line.
+fn search_sorted_file(path: &Path, hash: &str) -> io::Result<bool> {
+    let mut file = BufReader::new(File::open(path)?);
+    let (mut low, mut high) = (0, file.get_ref().metadata()?.len());
+
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match line_hash_after(&mut file, middle)? {
+            Some(line_hash) if line_hash.as_str().cmp(hash) == Ordering::Less => low = middle + 1,
+            _ => high = middle,
+        }
+    }
+
+    Ok(line_hash_after(&mut file, low)?.is_some_and(|line_hash| line_hash == hash))
+}
+
+// The hash on the first line that starts at or after `offset`
+fn line_hash_after(file: &mut BufReader<File>, offset: u64) -> io::Result<Option<String>> {
+    if offset == 0 {
+        file.seek(SeekFrom::Start(0))?;
+    } else {
+        // Skips the rest of the line `offset` falls in, which is nothing when the byte
+        // before it ends a line
+        file.seek(SeekFrom::Start(offset - 1))?;
+        file.read_until(b'\n', &mut Vec::new())?;
+    }
+
+    read_line_hash(file)
+}
+
+fn read_line_hash(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let hash = line.trim().split(':').next().unwrap_or_default();
+    Ok((!hash.is_empty()).then(|| hash.to_uppercase()))
+}
+
+fn is_sha1_hex(hash: &str) -> bool {
+    hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+// A rough guess at how many bits of randomness a password holds: the size of the character
+// classes it draws from, counted once for every character that isn't just a repeat of or
+// a step on from the one before it, so "aaaaaaaa" and "12345678" score close to nothing.
+pub fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut pool_size = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool_size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool_size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool_size += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool_size += 33;
+    }
+    if !password.is_ascii() {
+        pool_size += 100;
+    }
+
+    if pool_size == 0 {
+        return 0.0;
+    }
+
+    let mut unpredictable_characters = 0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| (c as u32).abs_diff(p as u32) <= 1);
+        if !predictable {
+            unpredictable_characters += 1;
+        }
+        previous = Some(c);
+    }
+
+    unpredictable_characters as f64 * (pool_size as f64).log2()
+}
+
+fn contains_local_part(password: &str, email: &Email) -> bool {
+    let local_part = email
+        .as_ref()
+        .split('@')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    local_part.chars().count() >= MIN_CHECKED_LOCAL_PART_LENGTH
+        && password.to_lowercase().contains(&local_part)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("jane.doe@example.com").unwrap()
+    }
+
+    fn check(password: &str) -> Result<(), Vec<PasswordPolicyViolation>> {
+        PasswordPolicy::default().check(&Password::parse(password).unwrap(), &email())
+    }
+
+    #[test]
+    fn should_accept_strong_password() {
+        assert_eq!(check("Tr0ub4dor&3x"), Ok(()));
+        assert_eq!(check("correct horse battery staple"), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_common_passwords() {
+        assert_eq!(
+            check("password"),
+            Err(vec![
+                PasswordPolicyViolation::TooWeak,
+                PasswordPolicyViolation::Breached
+            ])
+        );
+        assert_eq!(
+            check("12345678"),
+            Err(vec![
+                PasswordPolicyViolation::TooWeak,
+                PasswordPolicyViolation::Breached
+            ])
+        );
+        assert_eq!(
+            check("P@ssw0rd"),
+            Err(vec![PasswordPolicyViolation::Breached])
+        );
+    }
+
+    #[test]
+    fn should_reject_password_containing_email_local_part() {
+        assert_eq!(
+            check("xx-Jane.Doe-1987!"),
+            Err(vec![PasswordPolicyViolation::ContainsEmail])
+        );
+    }
+
+    #[test]
+    fn should_not_check_short_local_parts() {
+        let policy = PasswordPolicy::default();
+        let email = Email::parse("al@example.com").unwrap();
+
+        assert_eq!(
+            policy.check(&Password::parse("Tr0ub4dor&3xal").unwrap(), &email),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_score_repeats_and_sequences_low() {
+        assert!(estimate_entropy_bits("aaaaaaaaaaaa") < 10.0);
+        assert!(estimate_entropy_bits("abcdefghijkl") < 10.0);
+        assert!(estimate_entropy_bits("9876543210") < 10.0);
+        assert!(estimate_entropy_bits("kq7#Rz2!") > 50.0);
+    }
+
+    #[test]
+    fn should_find_passwords_by_hash_prefix() {
+        let corpus = format!(
+            "# comment\n\n{}:42\n{}\n",
+            sha1_hex("hunter2"),
+            sha1_hex("swordfish").to_lowercase()
+        );
+        let breached_passwords = BreachedPasswords::from_reader(corpus.as_bytes()).unwrap();
+
+        assert!(breached_passwords.contains("hunter2"));
+        assert!(breached_passwords.contains("swordfish"));
+        assert!(!breached_passwords.contains("Hunter2"));
+    }
+
+    #[test]
+    fn should_search_sorted_file() {
+        let passwords: Vec<String> = (0..500).map(|i| format!("password-{}", i)).collect();
+        let mut hashes: Vec<String> = passwords.iter().map(|p| sha1_hex(p)).collect();
+        hashes.sort();
+        // Counts make the lines differ in length, like in the real download
+        let corpus: String = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| format!("{}:{}\r\n", hash, i * 37))
+            .collect();
+        let path = std::env::temp_dir().join(format!("{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, corpus).unwrap();
+
+        let breached_passwords = BreachedPasswords::load(&path).unwrap();
+
+        for password in &passwords {
+            assert!(breached_passwords.contains(password), "{}", password);
+        }
+        assert!(!breached_passwords.contains("password-500"));
+        assert!(!breached_passwords.contains("Tr0ub4dor&3x"));
+
+        std::fs::write(&path, "not-a-hash\n").unwrap();
+        assert_eq!(
+            BreachedPasswords::load(&path).err(),
+            Some("Line 1 is not a SHA-1 hash".to_owned())
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn should_reject_malformed_corpus() {
+        assert_eq!(
+            BreachedPasswords::from_reader("not-a-hash\n".as_bytes()).err(),
+            Some("Line 1 is not a SHA-1 hash".to_owned())
+        );
+    }
+}
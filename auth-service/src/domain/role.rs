@@ -0,0 +1,62 @@
+// Role names are short lowercase identifiers like `admin` or `billing-viewer`, so they read
+// the same in the database, in tokens and in `require_role` checks
+const MAX_ROLE_LENGTH: usize = 64;
+
+#[derive(Eq, Hash, PartialEq, PartialOrd, Ord, Debug, Clone)]
+pub struct Role(String);
+
+impl Role {
+    pub const ADMIN: &'static str = "admin";
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let valid = !name.is_empty()
+            && name.len() <= MAX_ROLE_LENGTH
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+
+        if !valid {
+            return Err(format!("Invalid role: {}", name));
+        }
+
+        Ok(Self(name.to_owned()))
+    }
+
+    pub fn admin() -> Self {
+        Self(Self::ADMIN.to_owned())
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        &self.0[..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_accept_valid_roles() {
+        for name in ["admin", "billing-viewer", "support_2"] {
+            assert_eq!(Role::parse(name).unwrap().as_ref(), name);
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_roles() {
+        for name in [
+            "",
+            "Admin",
+            "2fa",
+            "-admin",
+            "ad min",
+            "admin!",
+            &"a".repeat(65),
+        ] {
+            assert!(Role::parse(name).is_err(), "Accepted {:?}", name);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::net::IpAddr;
+
+use chrono::Utc;
+
+use super::Email;
+
+// A signed-in device. The id is shared by the session's refresh token family and carried as
+// the `jti` claim of every access token issued to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub email: Email,
+    pub device: String,
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+    // Seconds since the epoch
+    pub created_at: i64,
+    pub last_seen: i64,
+}
+
+impl Session {
+    pub fn new(id: String, email: Email, ip: IpAddr, user_agent: Option<String>) -> Self {
+        let now = Utc::now().timestamp();
+
+        Self {
+            id,
+            email,
+            device: describe_device(user_agent.as_deref()),
+            ip,
+            user_agent,
+            created_at: now,
+            last_seen: now,
+        }
+    }
+}
+
+// A rough "Browser on OS" label so users can tell their sessions apart
+pub fn describe_device(user_agent: Option<&str>) -> String {
+    let user_agent = match user_agent {
+        Some(user_agent) => user_agent,
+        None => return "Unknown device".to_owned(),
+    };
+
+    // Order matters: Edge claims to be Chrome, and Chrome claims to be Safari
+    let browser = [
+        ("Edg/", "Edge"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map(|(_, name)| *name);
+
+    // Android and iOS user agents also mention Linux and Mac OS X
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(token, _)| user_agent.contains(token))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(browser), None) => browser.to_owned(),
+        (None, Some(os)) => os.to_owned(),
+        (None, None) => "Unknown device".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_device() {
+        let test_cases = [
+            (
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 Edg/120.0.0.0",
+                "Edge on Windows",
+            ),
+            (
+                "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0",
+                "Firefox on Linux",
+            ),
+            (
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.2 Mobile/15E148 Safari/604.1",
+                "Safari on iOS",
+            ),
+            (
+                "Mozilla/5.0 (Linux; Android 14) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+                "Chrome on Android",
+            ),
+            ("curl/8.4.0", "Unknown device"),
+        ];
+
+        for (user_agent, expected) in test_cases {
+            assert_eq!(describe_device(Some(user_agent)), expected);
+        }
+        assert_eq!(describe_device(None), "Unknown device");
+    }
+}
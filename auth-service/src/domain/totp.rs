@@ -0,0 +1,174 @@
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+use super::{Email, TwoFACode};
+
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+// Codes from one step either side of the current one are accepted to allow for clock drift
+pub const TOTP_ALLOWED_SKEW_STEPS: u64 = 1;
+pub const TOTP_ISSUER: &str = "LGR Auth";
+
+const TOTP_SECRET_LENGTH: usize = 20;
+const BASE32_ALPHABET: Alphabet = Alphabet::Rfc4648 { padding: false };
+
+// Shared secret between the service and the user's authenticator app (RFC 6238, HMAC-SHA1)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String> {
+        if bytes.len() < 16 {
+            return Err("TOTP secret is too short".to_owned());
+        }
+        Ok(Self(bytes))
+    }
+
+    pub fn parse_base32(secret: &str) -> Result<Self, String> {
+        let bytes = base32::decode(BASE32_ALPHABET, &secret.to_ascii_uppercase())
+            .ok_or("Invalid TOTP secret".to_owned())?;
+        Self::from_bytes(bytes)
+    }
+
+    pub fn to_base32(&self) -> String {
+        base32::encode(BASE32_ALPHABET, &self.0)
+    }
+
+    // Key URI understood by authenticator apps, usually rendered as a QR code
+    pub fn otpauth_uri(&self, account: &Email) -> String {
+        let issuer = percent_encode(TOTP_ISSUER);
+        format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+            issuer,
+            percent_encode(account.as_ref()),
+            self.to_base32(),
+            issuer,
+            TOTP_DIGITS,
+            TOTP_STEP_SECONDS
+        )
+    }
+
+    pub fn code_at_step(&self, step: u64) -> TwoFACode {
+        let mut mac =
+            Hmac::<Sha1>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(&step.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+
+        // Dynamic truncation, RFC 4226 section 5.3
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+
+        TwoFACode(format!(
+            "{:0width$}",
+            binary % 10u32.pow(TOTP_DIGITS),
+            width = TOTP_DIGITS as usize
+        ))
+    }
+
+    // Returns the time step the code belongs to, so callers can refuse to accept it twice
+    pub fn verify(&self, code: &TwoFACode, unix_timestamp: u64) -> Option<u64> {
+        let current_step = unix_timestamp / TOTP_STEP_SECONDS;
+
+        (current_step.saturating_sub(TOTP_ALLOWED_SKEW_STEPS)
+            ..=current_step + TOTP_ALLOWED_SKEW_STEPS)
+            .find(|step| self.code_at_step(*step) == *code)
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = vec![0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+}
+
+impl AsRef<[u8]> for TotpSecret {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from RFC 6238 appendix B, truncated to six digits
+    fn rfc_secret() -> TotpSecret {
+        TotpSecret::from_bytes(b"12345678901234567890".to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_code_matches_rfc_6238_vectors() {
+        let secret = rfc_secret();
+        assert_eq!(
+            secret.code_at_step(59 / TOTP_STEP_SECONDS).as_ref(),
+            "287082"
+        );
+        assert_eq!(
+            secret.code_at_step(1111111109 / TOTP_STEP_SECONDS).as_ref(),
+            "081804"
+        );
+        assert_eq!(
+            secret.code_at_step(1234567890 / TOTP_STEP_SECONDS).as_ref(),
+            "005924"
+        );
+    }
+
+    #[test]
+    fn test_verify_allows_one_step_of_skew() {
+        let secret = rfc_secret();
+        let now = 1234567890;
+        let step = now / TOTP_STEP_SECONDS;
+
+        assert_eq!(secret.verify(&secret.code_at_step(step), now), Some(step));
+        assert_eq!(
+            secret.verify(&secret.code_at_step(step - 1), now),
+            Some(step - 1)
+        );
+        assert_eq!(
+            secret.verify(&secret.code_at_step(step + 1), now),
+            Some(step + 1)
+        );
+        assert_eq!(secret.verify(&secret.code_at_step(step - 2), now), None);
+        assert_eq!(secret.verify(&secret.code_at_step(step + 2), now), None);
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        let secret = TotpSecret::default();
+        let parsed = TotpSecret::parse_base32(&secret.to_base32()).unwrap();
+        assert_eq!(parsed, secret);
+        assert!(TotpSecret::parse_base32("not base32!").is_err());
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let secret = rfc_secret();
+        let email = Email::parse("test@example.com").unwrap();
+        assert_eq!(
+            secret.otpauth_uri(&email),
+            "otpauth://totp/LGR%20Auth:test%40example.com?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=LGR%20Auth&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+}
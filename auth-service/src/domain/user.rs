@@ -1,17 +1,57 @@
 use super::{Email, Password};
 
+#[derive(Clone)]
 pub struct User {
     pub email: Email,
     pub password: Password,
-    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub verified: bool,
+    // Set by admins to keep the user from logging in
+    pub disabled: bool,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    // New users start out with an unverified email address
+    pub fn new(email: Email, password: Password, two_fa_method: TwoFAMethod) -> Self {
         Self {
             email,
             password,
-            requires_2fa,
+            two_fa_method,
+            verified: false,
+            disabled: false,
+        }
+    }
+
+    pub fn requires_2fa(&self) -> bool {
+        self.two_fa_method != TwoFAMethod::None
+    }
+}
+
+// The second factor a user has to present after their password
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TwoFAMethod {
+    None,
+    Email,
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(method: &str) -> Result<Self, String> {
+        match method {
+            "none" => Ok(Self::None),
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            _ => Err(format!("Unknown 2FA method: {}", method)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::Email => "email",
+            Self::Totp => "totp",
         }
     }
 }
@@ -1,16 +1,18 @@
 use app_state::AppState;
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, AddExtension},
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{get, post},
     serve::Serve,
     Json, Router,
 };
-use domain::AuthAPIError;
-use redis::{Client, RedisResult};
+use domain::{AuthAPIError, Role};
+use redis::{aio::ConnectionManager, Client, RedisResult};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tower_http::{cors::CorsLayer, services::ServeDir};
 
 pub mod app_state;
@@ -20,9 +22,19 @@ pub mod services;
 pub mod utils;
 
 use routes::*;
+use utils::{
+    constants::{
+        REDIS_CONNECTION_TIMEOUT, REDIS_RECONNECT_ATTEMPTS, REDIS_RECONNECT_BACKOFF_MILLISECONDS,
+        REDIS_RESPONSE_TIMEOUT,
+    },
+    require_role::require_role,
+};
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     pub address: String,
 }
 
@@ -38,19 +50,61 @@ impl Application {
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        let admin_router = Router::new()
+            .route("/users", get(list_users))
+            .route("/users/:email", get(show_user))
+            .route("/users/disable", post(disable_user))
+            .route("/users/enable", post(enable_user))
+            .route("/users/password-reset", post(force_password_reset))
+            .route("/users/reset-2fa", post(reset_two_fa))
+            .route("/users/revoke-tokens", post(revoke_user_tokens))
+            .route("/roles/grant", post(grant_role))
+            .route("/roles/revoke", post(revoke_role))
+            .route("/audit-log", get(list_audit_log))
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                require_role(Role::ADMIN),
+            ));
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
+            .route("/verify-2fa/recovery", post(verify_recovery_code))
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
+            .route("/refresh", post(refresh))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route("/sessions", get(list_sessions))
+            .route("/sessions/revoke", post(revoke_session))
+            .route("/sessions/revoke-all", post(revoke_all_sessions))
+            .route("/password-reset/request", post(request_password_reset))
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/verify-email", post(verify_email))
+            .route("/verify-email/resend", post(resend_verification_email))
+            .route("/totp/enroll", post(enroll_totp))
+            .route("/totp/confirm", post(confirm_totp))
+            .route(
+                "/recovery-codes/regenerate",
+                post(regenerate_recovery_codes),
+            )
+            .route("/account/password", post(change_password))
+            .route("/account/email", post(request_email_change))
+            .route("/account/email/confirm", post(confirm_email_change))
+            .route("/account/2fa", post(set_two_fa))
+            .route("/account/delete", post(delete_account))
+            .nest("/admin", admin_router)
             .with_state(app_state)
             .layer(cors);
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Login throttling needs the client's address
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Self { server, address })
     }
@@ -64,10 +118,25 @@ impl Application {
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    // Machine readable causes, for errors that can have several at once
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        let retry_after = match self {
+            AuthAPIError::TooManyLoginAttempts(seconds)
+            | AuthAPIError::TooManyRequests(seconds) => Some(seconds),
+            _ => None,
+        };
+        let reasons = match &self {
+            AuthAPIError::WeakPassword(violations) => violations
+                .iter()
+                .map(|violation| violation.as_ref().to_owned())
+                .collect(),
+            _ => Vec::new(),
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
@@ -79,11 +148,35 @@ impl IntoResponse for AuthAPIError {
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email address not verified"),
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthAPIError::TwoFAAlreadyEnabled => (StatusCode::CONFLICT, "2FA is already enabled"),
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Insufficient permissions"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::UnknownRole => (StatusCode::BAD_REQUEST, "Unknown role"),
+            AuthAPIError::TooManyLoginAttempts(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many login attempts")
+            }
+            AuthAPIError::TooManyRequests(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many requests")
+            }
+            AuthAPIError::WeakPassword(_) => (
+                StatusCode::BAD_REQUEST,
+                "Password does not meet the password policy",
+            ),
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            reasons,
         });
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
@@ -95,3 +188,20 @@ pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     let redis_url = format!("redis://{}/", redis_hostname);
     redis::Client::open(redis_url)
 }
+
+// One multiplexed connection that every Redis store clones, so commands from concurrent
+// requests are pipelined rather than taking turns. A command that fails because the
+// connection dropped makes the manager reconnect, backing off between attempts.
+pub async fn get_redis_connection(redis_hostname: String) -> RedisResult<ConnectionManager> {
+    let client = get_redis_client(redis_hostname)?;
+
+    ConnectionManager::new_with_backoff_and_timeouts(
+        client,
+        2,
+        REDIS_RECONNECT_BACKOFF_MILLISECONDS,
+        REDIS_RECONNECT_ATTEMPTS,
+        REDIS_RESPONSE_TIMEOUT,
+        REDIS_CONNECTION_TIMEOUT,
+    )
+    .await
+}
@@ -1,34 +1,119 @@
+use argon2::Params;
+use chrono::Utc;
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
 use auth_service::{
-    app_state::AppState,
-    get_postgres_pool, get_redis_client,
+    app_state::{AppState, EmailClientType, EmailVerificationPolicy, KeyringType},
+    domain::{BreachedPasswords, LocalPartPolicy, PasswordPolicy, EMAIL_RETRY_POLICY},
+    get_postgres_pool, get_redis_connection,
     services::{
-        data_stores::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
+        data_stores::{
+            PostgresAdminAuditStore, PostgresEmailOutboxStore, PostgresRecoveryCodeStore,
+            PostgresTotpSecretStore, PostgresUserStore, RedisBannedTokenStore,
+            RedisEmailChangeTokenStore, RedisEmailVerificationTokenStore, RedisLoginAttemptStore,
+            RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisSessionStore,
+            RedisTwoFACodeStore,
+        },
+        email_outbox::{EmailOutbox, EMAIL_OUTBOX_POLL_INTERVAL},
+        file_email_client::FileEmailClient,
         mock_email_client::MockEmailClient,
+        smtp_email_client::{SmtpEmailClient, SmtpSettings},
+    },
+    utils::{
+        auth::{watch_keyring, Keyring, KeyringSource, KEYRING_RELOAD_INTERVAL},
+        constants::{
+            prod, ARGON2_MEMORY_COST_KIB, ARGON2_PARALLELISM, ARGON2_TIME_COST,
+            BREACHED_PASSWORDS_PATH, DATABASE_URL, EMAIL_BACKEND, EMAIL_FILE_DIRECTORY,
+            EMAIL_FILE_FORMAT, EMAIL_LOCAL_PART_POLICY, JWT_PREVIOUS_SECRETS, JWT_SECRET,
+            JWT_SIGNING_KEY_PATH, JWT_VERIFICATION_KEY_PATHS, PASSWORD_MIN_ENTROPY_BITS,
+            REDIS_HOST_NAME, REQUIRE_EMAIL_VERIFICATION, SMTP_CA_CERT_PATH, SMTP_HOST,
+            SMTP_MAX_RETRIES, SMTP_MAX_RETRY_DELAY_MILLISECONDS, SMTP_PASSWORD, SMTP_PORT,
+            SMTP_RETRY_DELAY_MILLISECONDS, SMTP_SECURITY, SMTP_SENDER, SMTP_TIMEOUT_SECONDS,
+            SMTP_USERNAME, TOTP_ENCRYPTION_KEY, TRUSTED_PROXY_HEADER,
+        },
     },
-    utils::constants::{prod, DATABASE_URL, REDIS_HOST_NAME},
     Application,
 };
 
 #[tokio::main]
 async fn main() {
+    // Arguments are an outbox maintenance command rather than a request to serve. Anything
+    // else is refused before touching the database.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = if args.is_empty() {
+        None
+    } else {
+        Some(OutboxCommand::parse(&args).unwrap_or_else(|| {
+            eprintln!("{}", OUTBOX_COMMAND_USAGE);
+            std::process::exit(2);
+        }))
+    };
+
     let pg_pool = configure_postgresql().await;
-    let redis_connection = Arc::new(RwLock::new(configure_redis()));
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
+    let email_outbox = EmailOutbox::new(Arc::new(PostgresEmailOutboxStore::new(pg_pool.clone())));
+
+    if let Some(command) = command {
+        run_outbox_command(command, &email_outbox).await;
+        return;
+    }
+
+    let redis_connection = configure_redis().await;
+    let user_store = Arc::new(
+        PostgresUserStore::new(pg_pool.clone())
+            .with_password_hash_params(configure_password_hash_params()),
+    );
+    check_email_local_part_policy(&user_store).await;
+    let banned_token_store = Arc::new(RedisBannedTokenStore::new(redis_connection.clone()));
+    let two_fa_code_store = Arc::new(RedisTwoFACodeStore::new(redis_connection.clone()));
+    let refresh_token_store = Arc::new(RedisRefreshTokenStore::new(redis_connection.clone()));
+    let password_reset_token_store =
+        Arc::new(RedisPasswordResetTokenStore::new(redis_connection.clone()));
+    let email_verification_token_store = Arc::new(RedisEmailVerificationTokenStore::new(
         redis_connection.clone(),
-    )));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-    let email_client = Arc::new(RwLock::new(MockEmailClient));
+    ));
+    let email_change_token_store =
+        Arc::new(RedisEmailChangeTokenStore::new(redis_connection.clone()));
+    let login_attempt_store = Arc::new(RedisLoginAttemptStore::new(redis_connection.clone()));
+    let session_store = Arc::new(RedisSessionStore::new(redis_connection));
+    let totp_secret_store = Arc::new(PostgresTotpSecretStore::new(
+        pg_pool.clone(),
+        *TOTP_ENCRYPTION_KEY,
+    ));
+    let recovery_code_store = Arc::new(PostgresRecoveryCodeStore::new(pg_pool.clone()));
+    let admin_audit_store = Arc::new(PostgresAdminAuditStore::new(pg_pool));
+    tokio::spawn(email_outbox.clone().run_worker(
+        configure_email_client(),
+        EMAIL_RETRY_POLICY,
+        EMAIL_OUTBOX_POLL_INTERVAL,
+    ));
+    let email_verification_policy = if *REQUIRE_EMAIL_VERIFICATION {
+        EmailVerificationPolicy::Required
+    } else {
+        EmailVerificationPolicy::Optional
+    };
     let app_state = AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
-        email_client,
-    );
+        refresh_token_store,
+        password_reset_token_store,
+        email_verification_token_store,
+        email_change_token_store,
+        totp_secret_store,
+        recovery_code_store,
+        login_attempt_store,
+        session_store,
+        admin_audit_store,
+        email_outbox,
+        configure_keyring(),
+    )
+    .with_email_verification_policy(email_verification_policy)
+    .with_password_policy(configure_password_policy())
+    .with_trusted_proxy_header(TRUSTED_PROXY_HEADER.clone());
     let app = Application::build(app_state, prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
@@ -36,6 +121,59 @@ async fn main() {
     app.run().await.expect("Failed to run app");
 }
 
+const OUTBOX_COMMAND_USAGE: &str = "Usage: auth-service [dead-letters | replay-dead-letter <id>]";
+
+// `dead-letters` lists emails that ran out of delivery attempts and `replay-dead-letter <id>`
+// queues one again. A running instance picks it up on its next poll.
+enum OutboxCommand {
+    DeadLetters,
+    ReplayDeadLetter(Uuid),
+}
+
+impl OutboxCommand {
+    fn parse(args: &[String]) -> Option<Self> {
+        match args {
+            [command] if command == "dead-letters" => Some(Self::DeadLetters),
+            [command, id] if command == "replay-dead-letter" => {
+                id.parse().ok().map(Self::ReplayDeadLetter)
+            }
+            _ => None,
+        }
+    }
+}
+
+async fn run_outbox_command(command: OutboxCommand, email_outbox: &EmailOutbox) {
+    let store = email_outbox.store();
+
+    match command {
+        OutboxCommand::DeadLetters => {
+            let dead_letters = store
+                .get_dead_letters()
+                .await
+                .expect("Failed to read dead letters");
+
+            for email in dead_letters {
+                println!(
+                    "{}\t{}\t{}\t{} attempts\t{}",
+                    email.id,
+                    email.recipient.as_ref(),
+                    email.message.subject,
+                    email.attempts,
+                    email.last_error.unwrap_or_default()
+                );
+            }
+        }
+        OutboxCommand::ReplayDeadLetter(id) => {
+            store
+                .replay_dead_letter(id, Utc::now().timestamp())
+                .await
+                .expect("Failed to replay dead letter");
+
+            println!("Queued {} for delivery", id);
+        }
+    }
+}
+
 async fn configure_postgresql() -> PgPool {
     let pg_pool = get_postgres_pool(&DATABASE_URL)
         .await
@@ -49,9 +187,120 @@ async fn configure_postgresql() -> PgPool {
     pg_pool
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+// Addresses stored before lookups became case-insensitive were lowercased in full, so with
+// the case-sensitive policy their owners would be locked out
+async fn check_email_local_part_policy(user_store: &PostgresUserStore) {
+    if *EMAIL_LOCAL_PART_POLICY != LocalPartPolicy::CaseSensitive {
+        return;
+    }
+
+    let lowercased = user_store
+        .count_lowercased_local_parts()
+        .await
+        .expect("Failed to check for lowercased email addresses");
+    if lowercased > 0 {
+        panic!(
+            "EMAIL_LOCAL_PART_POLICY=case-sensitive can't be used while {} addresses have a \
+             local part lowercased by migration. Restore them and clear \
+             lowercased_email_local_parts first.",
+            lowercased
+        );
+    }
+}
+
+// Key files are watched for changes, so rotating keys doesn't need a restart
+fn configure_keyring() -> KeyringType {
+    let source = KeyringSource {
+        signing_key_path: JWT_SIGNING_KEY_PATH.clone(),
+        verification_key_paths: JWT_VERIFICATION_KEY_PATHS.clone(),
+        secret: JWT_SECRET.clone(),
+        previous_secrets: JWT_PREVIOUS_SECRETS.clone(),
+    };
+    let keyring = Arc::new(RwLock::new(
+        Keyring::load(&source).expect("Failed to load JWT signing keys"),
+    ));
+
+    tokio::spawn(watch_keyring(
+        keyring.clone(),
+        source,
+        KEYRING_RELOAD_INTERVAL,
+    ));
+
+    keyring
+}
+
+// `EMAIL_BACKEND` is one of `smtp`, `file` or `log`. Without it, emails go out over SMTP
+// when `SMTP_HOST` is set and are only logged otherwise.
+fn configure_email_client() -> EmailClientType {
+    let backend = EMAIL_BACKEND
+        .clone()
+        .unwrap_or_else(|| if SMTP_HOST.is_some() { "smtp" } else { "log" }.to_owned());
+
+    match backend.to_lowercase().as_str() {
+        "smtp" => configure_smtp_email_client(),
+        "file" => {
+            let client = FileEmailClient::new(
+                EMAIL_FILE_DIRECTORY.as_str(),
+                EMAIL_FILE_FORMAT
+                    .parse()
+                    .expect("Invalid EMAIL_FILE_FORMAT"),
+                &SMTP_SENDER,
+            )
+            .expect("Invalid email file settings");
+            println!("Writing emails to {}", client.directory().display());
+
+            Arc::new(client)
+        }
+        "log" => Arc::new(MockEmailClient),
+        _ => panic!("Unknown EMAIL_BACKEND: {}", backend),
+    }
+}
+
+fn configure_smtp_email_client() -> EmailClientType {
+    let host = SMTP_HOST
+        .clone()
+        .expect("SMTP_HOST must be set to send emails over SMTP.");
+
+    let settings = SmtpSettings {
+        host,
+        port: *SMTP_PORT,
+        security: SMTP_SECURITY.parse().expect("Invalid SMTP_SECURITY"),
+        username: SMTP_USERNAME.clone(),
+        password: SMTP_PASSWORD.clone(),
+        sender: SMTP_SENDER.clone(),
+        timeout: Duration::from_secs(*SMTP_TIMEOUT_SECONDS),
+        max_retries: *SMTP_MAX_RETRIES,
+        retry_delay: Duration::from_millis(SMTP_RETRY_DELAY_MILLISECONDS),
+        max_retry_delay: Duration::from_millis(SMTP_MAX_RETRY_DELAY_MILLISECONDS),
+        ca_certificate: SMTP_CA_CERT_PATH
+            .as_ref()
+            .map(|path| std::fs::read(path).expect("Failed to read SMTP_CA_CERT_PATH")),
+    };
+
+    Arc::new(SmtpEmailClient::new(settings).expect("Invalid SMTP settings"))
+}
+
+fn configure_password_policy() -> PasswordPolicy {
+    let breached_passwords = match BREACHED_PASSWORDS_PATH.as_ref() {
+        Some(path) => BreachedPasswords::load(path).expect("Failed to load breached passwords"),
+        None => BreachedPasswords::bundled(),
+    };
+
+    PasswordPolicy::new(*PASSWORD_MIN_ENTROPY_BITS, breached_passwords)
+}
+
+fn configure_password_hash_params() -> Params {
+    Params::new(
+        *ARGON2_MEMORY_COST_KIB,
+        *ARGON2_TIME_COST,
+        *ARGON2_PARALLELISM,
+        None,
+    )
+    .expect("Invalid Argon2 parameters")
+}
+
+async fn configure_redis() -> ConnectionManager {
+    get_redis_connection(REDIS_HOST_NAME.to_owned())
+        .await
+        .expect("Failed to connect to Redis")
 }
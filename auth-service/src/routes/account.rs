@@ -0,0 +1,329 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailChange, EmailChangeToken, LoginAttemptKey, Password, TwoFAMethod,
+        UserStoreError,
+    },
+    services::email_templates::{EmailChangeEmail, EmailTemplate},
+    utils::{
+        authenticated_user::AuthenticatedUser, client_info::ClientInfo, constants::AUTH_SERVICE_URL,
+    },
+};
+
+use super::{
+    begin_login_attempt, end_all_sessions, issue_recovery_codes, record_failed_login,
+    record_successful_login, remove_session_cookies,
+};
+
+// Every change to the account asks for the current password again, so a session left open
+// on a shared device isn't enough to take the account over. Wrong passwords count towards
+// the same limits as failed logins.
+pub(crate) async fn reauthenticate(
+    email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
+    current_password: &str,
+) -> Result<(), AuthAPIError> {
+    let password =
+        Password::parse(current_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let attempt_keys = [
+        LoginAttemptKey::Ip(client.ip),
+        LoginAttemptKey::Email(email.clone()),
+    ];
+
+    begin_login_attempt(&attempt_keys, state).await?;
+
+    if state
+        .user_store
+        .validate_user(email, &password)
+        .await
+        .is_err()
+    {
+        record_failed_login(&attempt_keys, state).await?;
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    record_successful_login(&attempt_keys, state).await
+}
+
+// Keeps the session making the request and signs every other device out
+pub async fn change_password(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, claims, .. }: AuthenticatedUser,
+    client: ClientInfo,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&email, &client, &state, &request.current_password).await?;
+
+    let new_password =
+        Password::parse(&request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    state
+        .password_policy
+        .check(&new_password, &email)
+        .map_err(AuthAPIError::WeakPassword)?;
+
+    if state
+        .user_store
+        .update_password(&email, new_password)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let sessions = state
+        .session_store
+        .list_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Families go before their sessions, so a refresh racing with this can't register the
+    // session again
+    for session in sessions.iter().filter(|session| session.id != claims.jti) {
+        if state
+            .refresh_token_store
+            .revoke_family(&session.id)
+            .await
+            .is_err()
+            || state
+                .session_store
+                .remove_session(&session.id)
+                .await
+                .is_err()
+        {
+            return Err(AuthAPIError::UnexpectedError);
+        }
+    }
+
+    let response = Json(AccountResponse {
+        message: "Password updated successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Nothing changes until the link sent to the new address is followed, so a typo can't lock
+// the user out of their account
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    client: ClientInfo,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&email, &client, &state, &request.current_password).await?;
+
+    let new_email =
+        Email::parse(&request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.user_store.get_user(&new_email).await {
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => (),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token = EmailChangeToken::default();
+
+    if state
+        .email_change_token_store
+        .add_token(
+            EmailChange {
+                email,
+                new_email: new_email.clone(),
+            },
+            token.clone(),
+        )
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let message = EmailChangeEmail {
+        confirmation_link: format!(
+            "{}/?email_change_token={}",
+            AUTH_SERVICE_URL.as_str(),
+            token.as_ref()
+        ),
+    }
+    .render(client.locale)
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if state
+        .email_outbox
+        .enqueue(&new_email, message)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let response = Json(AccountResponse {
+        message: "A confirmation link has been sent to the new email address".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// The link may well be opened on another device, so the token is all that's needed. Tokens
+// name the old address, so every session is ended and the user logs in again with the new one.
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<ConfirmEmailChangeRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let token = match EmailChangeToken::parse(request.token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let change = match state.email_change_token_store.consume_token(&token).await {
+        Ok(change) => change,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // The new address may have been taken since the link was sent
+    match state
+        .user_store
+        .update_email(&change.email, change.new_email)
+        .await
+    {
+        Ok(()) => (),
+        Err(UserStoreError::UserAlreadyExists) => {
+            return (jar, Err(AuthAPIError::UserAlreadyExists))
+        }
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    if let Err(e) = end_all_sessions(&change.email, &state).await {
+        return (jar, Err(e));
+    }
+
+    let response = Json(AccountResponse {
+        message: "Email updated successfully!".to_owned(),
+    });
+
+    (remove_session_cookies(jar), Ok((StatusCode::OK, response)))
+}
+
+// Turning 2FA on sends codes by email unless an authenticator app is already set up. Users
+// who had no 2FA before get a batch of recovery codes with it.
+pub async fn set_two_fa(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    client: ClientInfo,
+    Json(request): Json<SetTwoFARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&email, &client, &state, &request.current_password).await?;
+
+    let previous_method = match state.user_store.get_user(&email).await {
+        Ok(user) => user.two_fa_method,
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if state
+        .user_store
+        .set_requires_2fa(&email, request.requires_2fa)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let recovery_codes = if request.requires_2fa && previous_method == TwoFAMethod::None {
+        issue_recovery_codes(&email, &state).await?
+    } else {
+        Vec::new()
+    };
+
+    let response = Json(SetTwoFAResponse {
+        message: "2FA settings updated successfully!".to_owned(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn delete_account(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    if let Err(e) = reauthenticate(&email, &client, &state, &request.current_password).await {
+        return (jar, Err(e));
+    }
+
+    if state.user_store.delete_user(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    if let Err(e) = end_all_sessions(&email, &state).await {
+        return (jar, Err(e));
+    }
+
+    let response = Json(AccountResponse {
+        message: "Account deleted successfully!".to_owned(),
+    });
+
+    (remove_session_cookies(jar), Ok((StatusCode::OK, response)))
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct SetTwoFARequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AccountResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetTwoFAResponse {
+    pub message: String,
+    // Only when 2FA was just turned on
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub recovery_codes: Vec<String>,
+}
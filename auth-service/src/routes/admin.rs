@@ -0,0 +1,538 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Json,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AdminAction, AdminAuditEntry, AuthAPIError, Email, Locale, LoginAttemptKey, Password, Role,
+        TwoFAMethod, User, UserStoreError,
+    },
+    utils::authenticated_user::AuthenticatedUser,
+};
+
+use super::{end_all_sessions, send_password_reset_link};
+
+// Routes under /admin sit behind `require_role(Role::ADMIN)`, which hands them the admin as
+// an `Extension<AuthenticatedUser>`. Every action is written to the audit trail before it is
+// carried out; if that fails, the request fails and nothing changes. Entries record what was
+// attempted, so one may stand for an action that then failed.
+
+const DEFAULT_PAGE_SIZE: u64 = 20;
+const MAX_PAGE_SIZE: u64 = 100;
+
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let page = PageBounds::new(query.page, query.per_page);
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|search| !search.is_empty());
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::ListUsers,
+        None,
+        search.map(str::to_owned),
+    )
+    .await?;
+
+    let users = state
+        .user_store
+        .list_users(search, page.offset(), page.per_page)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(UsersResponse {
+        users: users.users.iter().map(UserSummary::from).collect(),
+        page: page.page,
+        per_page: page.per_page,
+        total: users.total,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn show_user(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::ViewUser,
+        Some(&user.email),
+        None,
+    )
+    .await?;
+
+    let roles = state
+        .user_store
+        .get_roles(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let sessions = state
+        .session_store
+        .list_sessions(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(UserDetailsResponse {
+        user: UserSummary::from(&user),
+        roles: roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+        active_sessions: sessions.len(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Signs the user out everywhere and keeps them from logging in again
+pub async fn disable_user(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<AdminUserRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&request.email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::DisableUser,
+        Some(&user.email),
+        None,
+    )
+    .await?;
+
+    state
+        .user_store
+        .set_disabled(&user.email, true)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    end_all_sessions(&user.email, &state).await?;
+
+    Ok(admin_response("User disabled"))
+}
+
+// Also lifts the lockout from failed logins on the account
+pub async fn enable_user(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<AdminUserRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&request.email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::EnableUser,
+        Some(&user.email),
+        None,
+    )
+    .await?;
+
+    state
+        .user_store
+        .set_disabled(&user.email, false)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    clear_login_failures(&user.email, &state).await?;
+
+    Ok(admin_response("User enabled"))
+}
+
+// The current password stops working straight away: it is replaced with a random one, every
+// session is ended and the user is emailed a link to choose a new password
+pub async fn force_password_reset(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<AdminUserRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&request.email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::ForcePasswordReset,
+        Some(&user.email),
+        None,
+    )
+    .await?;
+
+    let random_password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let random_password =
+        Password::parse(&random_password).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .update_password(&user.email, random_password)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    end_all_sessions(&user.email, &state).await?;
+    clear_login_failures(&user.email, &state).await?;
+
+    // The user's language isn't known, so the link goes out in the default one
+    send_password_reset_link(&user.email, Locale::default(), &state).await?;
+
+    Ok(admin_response("Password reset link sent"))
+}
+
+// For users who lost their authenticator app: its secret is dropped along with their recovery
+// codes, and logins fall back to codes sent by email until a new app is enrolled. Users with
+// email codes or without 2FA are left as they are.
+pub async fn reset_two_fa(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<AdminUserRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&request.email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::ResetTwoFA,
+        Some(&user.email),
+        Some(user.two_fa_method.as_ref().to_owned()),
+    )
+    .await?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        state
+            .user_store
+            .set_two_fa_method(&user.email, TwoFAMethod::Email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        state
+            .totp_secret_store
+            .delete_secret(&user.email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        state
+            .recovery_code_store
+            .replace_codes(&user.email, Vec::new())
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(admin_response("2FA reset"))
+}
+
+pub async fn revoke_user_tokens(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<AdminUserRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&request.email, &state).await?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::RevokeTokens,
+        Some(&user.email),
+        None,
+    )
+    .await?;
+
+    end_all_sessions(&user.email, &state).await?;
+
+    Ok(admin_response("All tokens revoked"))
+}
+
+// Roles take effect once the user's access token is next refreshed
+pub async fn grant_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<RoleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, role) = request.parse()?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::GrantRole,
+        Some(&email),
+        Some(role.as_ref().to_owned()),
+    )
+    .await?;
+
+    match state.user_store.assign_role(&email, role.clone()).await {
+        Ok(()) => (),
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+        Err(UserStoreError::RoleNotFound) => return Err(AuthAPIError::UnknownRole),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    roles_response(email, &state).await
+}
+
+// Tokens carry the roles they were issued with, so the user's sessions are ended to take the
+// role away straight away rather than once their access token expires
+pub async fn revoke_role(
+    State(state): State<AppState>,
+    Extension(admin): Extension<AuthenticatedUser>,
+    Json(request): Json<RoleRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, role) = request.parse()?;
+
+    audit(
+        &state,
+        &admin,
+        AdminAction::RevokeRole,
+        Some(&email),
+        Some(role.as_ref().to_owned()),
+    )
+    .await?;
+
+    match state.user_store.remove_role(&email, &role).await {
+        Ok(()) => (),
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    end_all_sessions(&email, &state).await?;
+
+    roles_response(email, &state).await
+}
+
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let page = PageBounds::new(query.page, query.per_page);
+    let target = query
+        .target
+        .as_deref()
+        .map(Email::parse)
+        .transpose()
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let entries = state
+        .admin_audit_store
+        .list_entries(target.as_ref(), page.offset(), page.per_page)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(AuditLogResponse {
+        entries: entries
+            .entries
+            .into_iter()
+            .map(|entry| AuditEntryResponse {
+                actor: entry.actor.as_ref().to_owned(),
+                action: entry.action.as_ref().to_owned(),
+                target: entry.target.map(|target| target.as_ref().to_owned()),
+                details: entry.details,
+                created_at: entry.created_at,
+            })
+            .collect(),
+        page: page.page,
+        per_page: page.per_page,
+        total: entries.total,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+async fn find_user(email: &str, state: &AppState) -> Result<User, AuthAPIError> {
+    let email = Email::parse(email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.user_store.get_user(&email).await {
+        Ok(user) => Ok(user),
+        Err(UserStoreError::UserNotFound) => Err(AuthAPIError::UserNotFound),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+async fn audit(
+    state: &AppState,
+    admin: &AuthenticatedUser,
+    action: AdminAction,
+    target: Option<&Email>,
+    details: Option<String>,
+) -> Result<(), AuthAPIError> {
+    state
+        .admin_audit_store
+        .record(AdminAuditEntry::new(
+            admin.email.clone(),
+            action,
+            target.cloned(),
+            details,
+        ))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+async fn clear_login_failures(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    state
+        .login_attempt_store
+        .clear_failures(&LoginAttemptKey::Email(email.clone()))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+async fn roles_response(email: Email, state: &AppState) -> Result<impl IntoResponse, AuthAPIError> {
+    let roles = state
+        .user_store
+        .get_roles(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(RolesResponse {
+        email: email.as_ref().to_owned(),
+        roles: roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+fn admin_response(message: &str) -> impl IntoResponse {
+    let response = Json(AdminResponse {
+        message: message.to_owned(),
+    });
+
+    (StatusCode::OK, response)
+}
+
+// Pages are numbered from 1. Out of range values are clamped rather than rejected.
+struct PageBounds {
+    page: u64,
+    per_page: u64,
+}
+
+impl PageBounds {
+    fn new(page: Option<u64>, per_page: Option<u64>) -> Self {
+        Self {
+            page: page.unwrap_or(1).max(1),
+            per_page: per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    pub search: Option<String>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    pub target: Option<String>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUserRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct RoleRequest {
+    pub email: String,
+    pub role: String,
+}
+
+impl RoleRequest {
+    fn parse(self) -> Result<(Email, Role), AuthAPIError> {
+        let email = Email::parse(&self.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        let role = Role::parse(&self.role).map_err(|_| AuthAPIError::UnknownRole)?;
+
+        Ok((email, role))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserSummary {
+    pub email: String,
+    pub verified: bool,
+    pub disabled: bool,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: String,
+}
+
+impl From<&User> for UserSummary {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_owned(),
+            verified: user.verified,
+            disabled: user.disabled,
+            two_fa_method: user.two_fa_method.as_ref().to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UsersResponse {
+    pub users: Vec<UserSummary>,
+    pub page: u64,
+    #[serde(rename = "perPage")]
+    pub per_page: u64,
+    pub total: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct UserDetailsResponse {
+    #[serde(flatten)]
+    pub user: UserSummary,
+    pub roles: Vec<String>,
+    #[serde(rename = "activeSessions")]
+    pub active_sessions: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AdminResponse {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RolesResponse {
+    pub email: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntryResponse {
+    pub actor: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditEntryResponse>,
+    pub page: u64,
+    #[serde(rename = "perPage")]
+    pub per_page: u64,
+    pub total: u64,
+}
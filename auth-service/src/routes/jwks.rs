@@ -0,0 +1,10 @@
+use axum::{extract::State, Json};
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::app_state::AppState;
+
+// Public keys for verifying access tokens locally. Empty while tokens are signed with a
+// shared secret.
+pub async fn jwks(State(state): State<AppState>) -> Json<JwkSet> {
+    Json(state.keyring.read().await.jwks())
+}
@@ -1,15 +1,23 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    app_state::{AppState, EmailVerificationPolicy},
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, LoginAttemptKey, Password, TwoFACode, TwoFAMethod,
+    },
+    services::email_templates::{EmailTemplate, TwoFACodeEmail},
+    utils::{client_info::ClientInfo, token_delivery::TokenDelivery},
 };
 
+use super::{start_session, TokensResponse};
+
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
+    delivery: TokenDelivery,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -23,25 +31,129 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let user_store = &state.user_store.read().await;
+    let attempt_keys = [
+        LoginAttemptKey::Ip(client.ip),
+        LoginAttemptKey::Email(email.clone()),
+    ];
+
+    // Throttled attempts are turned away before paying for a password hash
+    if let Err(e) = begin_login_attempt(&attempt_keys, &state).await {
+        return (jar, Err(e));
+    }
 
-    if user_store.validate_user(&email, &password).await.is_err() {
+    if state
+        .user_store
+        .validate_user(&email, &password)
+        .await
+        .is_err()
+    {
+        if let Err(e) = record_failed_login(&attempt_keys, &state).await {
+            return (jar, Err(e));
+        }
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
-    let user = match user_store.get_user(&email).await {
+    if let Err(e) = record_successful_login(&attempt_keys, &state).await {
+        return (jar, Err(e));
+    }
+
+    let user = match state.user_store.get_user(&email).await {
         Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    if state.email_verification_policy == EmailVerificationPolicy::Required && !user.verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
+    if user.disabled {
+        return (jar, Err(AuthAPIError::AccountDisabled));
+    }
+
+    match user.two_fa_method {
+        TwoFAMethod::None => handle_no_2fa(&user.email, &client, delivery, &state, jar).await,
+        two_fa_method => handle_2fa(&user.email, two_fa_method, &client, &state, jar).await,
+    }
+}
+
+// The attempt is counted before the password is checked, so parallel guesses can't all get
+// past the throttle before any of them has failed. An attempt that is turned away isn't
+// held against the client.
+pub(super) async fn begin_login_attempt(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let now = Utc::now().timestamp();
+    let mut retry_after = None;
+    for key in keys {
+        let failures = state
+            .login_attempt_store
+            .record_attempt(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        retry_after = retry_after.max(failures.retry_after(key.policy(), now));
+    }
+
+    if let Some(retry_after) = retry_after {
+        forget_login_attempt(keys, state).await?;
+        return Err(AuthAPIError::TooManyLoginAttempts(retry_after));
     }
+
+    Ok(())
+}
+
+async fn forget_login_attempt(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    for key in keys {
+        state
+            .login_attempt_store
+            .forget_attempt(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
+
+// A correct password resets the account's count. Only this attempt is taken off the IP's
+// count, so one known password doesn't unlock guessing at other accounts.
+pub(super) async fn record_successful_login(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    for key in keys {
+        let result = match key {
+            LoginAttemptKey::Email(_) => state.login_attempt_store.clear_failures(key).await,
+            LoginAttemptKey::Ip(_) => state.login_attempt_store.forget_attempt(key).await,
+        };
+        result.map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
+
+pub(super) async fn record_failed_login(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    for key in keys {
+        state
+            .login_attempt_store
+            .record_failure(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
 }
 
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
+    client: &ClientInfo,
     state: &AppState,
     jar: CookieJar,
 ) -> (
@@ -49,12 +161,12 @@ async fn handle_2fa(
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
     let login_attempt_id = LoginAttemptId::default();
+    // TOTP users read their code off their authenticator app, so the generated code is never
+    // sent. Storing it still ties the login attempt id to the email for `verify_2fa`.
     let two_fa_code = TwoFACode::default();
 
     match state
         .two_fa_code_store
-        .write()
-        .await
         .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
     {
@@ -62,14 +174,20 @@ async fn handle_2fa(
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    if let Err(_) = state
-        .email_client
-        .write()
-        .await
-        .send_email(email, "Your 2FA Code", two_fa_code.as_ref())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError));
+    if two_fa_method == TwoFAMethod::Email {
+        let message = match (TwoFACodeEmail {
+            code: two_fa_code.as_ref().to_owned(),
+        })
+        .render(client.locale)
+        {
+            Ok(message) => message,
+            Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+        };
+
+        // Delivery happens in the background, so a relay outage doesn't fail the login
+        if state.email_outbox.enqueue(email, message).await.is_err() {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
     }
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
@@ -82,22 +200,26 @@ async fn handle_2fa(
 
 async fn handle_no_2fa(
     email: &Email,
+    client: &ClientInfo,
+    delivery: TokenDelivery,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Err(_e) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    let tokens = match start_session(email, client, state).await {
+        Err(e) => return (jar, Err(e)),
         Ok(val) => val,
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let (updated_jar, body) = tokens.deliver(delivery, jar);
+    let response = match body {
+        Some(tokens) => LoginResponse::Tokens(tokens),
+        None => LoginResponse::RegularAuth,
+    };
 
-    (
-        updated_jar,
-        Ok((StatusCode::OK, Json(LoginResponse::RegularAuth))),
-    )
+    (updated_jar, Ok((StatusCode::OK, Json(response))))
 }
 
 #[derive(Deserialize)]
@@ -111,6 +233,7 @@ pub struct LoginRequest {
 pub enum LoginResponse {
     RegularAuth,
     TwoFactorAuth(TwoFactorAuthResponse),
+    Tokens(TokensResponse),
 }
 
 // If a user requires 2FA, this JSON body should be returned!
@@ -3,40 +3,43 @@ use axum_extra::extract::{cookie, CookieJar};
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, SessionStoreError},
+    utils::{
+        authenticated_user::AuthenticatedUser,
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
 };
 
 pub async fn logout(
     State(state): State<AppState>,
+    AuthenticatedUser { claims, token, .. }: AuthenticatedUser,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
-        Some(cookie) => cookie,
-        None => return (jar, Err(AuthAPIError::MissingToken)),
-    };
-
-    // Validate token
-    let token = cookie.value().to_owned();
-    let _ = match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(claims) => claims,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
-    };
-
-    // Add token to banned list
+    // End the session. Its refresh token family shares its id, which covers clients that keep
+    // the refresh token themselves.
     if state
-        .banned_token_store
-        .write()
-        .await
-        .add_token(token.to_owned())
+        .refresh_token_store
+        .revoke_family(&claims.jti)
         .await
         .is_err()
     {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
-    // Remove jwt cookie
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    match state.session_store.remove_session(&claims.jti).await {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => (),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    // Add token to banned list
+    if state.banned_token_store.add_token(token).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Remove jwt and refresh cookies
+    let jar = jar
+        .remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME));
 
     (jar, Ok(StatusCode::OK))
 }
@@ -1,12 +1,30 @@
+mod account;
+mod admin;
+mod jwks;
 mod login;
 mod logout;
+mod password_reset;
+mod recovery_codes;
+mod refresh;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
 // re-export items from sub-modules
+pub use account::*;
+pub use admin::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
@@ -0,0 +1,129 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Locale, Password, PasswordResetToken},
+    services::email_templates::{EmailTemplate, PasswordResetEmail},
+    utils::{client_info::ClientInfo, constants::AUTH_SERVICE_URL},
+};
+
+use super::end_all_sessions;
+
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let response = Json(PasswordResetResponse {
+        message: "If the account exists, a password reset link has been sent".to_owned(),
+    });
+
+    // Respond the same way whether or not the account exists, so the route can't be used
+    // to find out which emails are registered
+    if state.user_store.get_user(&email).await.is_err() {
+        return Ok((StatusCode::OK, response));
+    }
+
+    send_password_reset_link(&email, client.locale, &state).await?;
+
+    Ok((StatusCode::OK, response))
+}
+
+pub(crate) async fn send_password_reset_link(
+    email: &Email,
+    locale: Locale,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = PasswordResetToken::default();
+
+    if state
+        .password_reset_token_store
+        .add_token(email.clone(), token.clone())
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let message = PasswordResetEmail {
+        reset_link: format!(
+            "{}/?reset_token={}",
+            AUTH_SERVICE_URL.as_str(),
+            token.as_ref()
+        ),
+    }
+    .render(locale)
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .enqueue(email, message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<ConfirmPasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(&request.new_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let token = PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // The password is checked before the token is used up, so after a rejected password the
+    // link still works for another try, and still expires when it was meant to
+    let email = state
+        .password_reset_token_store
+        .get_email(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    state
+        .password_policy
+        .check(&password, &email)
+        .map_err(AuthAPIError::WeakPassword)?;
+
+    let email = state
+        .password_reset_token_store
+        .consume_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if state
+        .user_store
+        .update_password(&email, password)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    // Whoever knew the old password may still hold a session, so log out everywhere
+    end_all_sessions(&email, &state).await?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password updated successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmPasswordResetRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
@@ -0,0 +1,62 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, RecoveryCode},
+    utils::{authenticated_user::AuthenticatedUser, client_info::ClientInfo},
+};
+
+use super::reauthenticate;
+
+// Replaces the logged in user's recovery codes with a fresh batch. The old codes stop
+// working, so this asks for the password like the /account routes do.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    client: ClientInfo,
+    Json(request): Json<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&email, &client, &state, &request.current_password).await?;
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+// The plain codes are only ever shown to the user here; the store keeps hashes
+pub(crate) async fn issue_recovery_codes(
+    email: &Email,
+    state: &AppState,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = RecoveryCode::generate_batch();
+
+    if state
+        .recovery_code_store
+        .replace_codes(email, codes.clone())
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    Ok(codes
+        .into_iter()
+        .map(|code| code.as_ref().to_owned())
+        .collect())
+}
+
+#[derive(Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, RefreshToken, RefreshTokenFamily, RefreshTokenStoreError, Session,
+        SessionStoreError,
+    },
+    utils::{
+        client_info::ClientInfo, constants::REFRESH_COOKIE_NAME, token_delivery::TokenDelivery,
+    },
+};
+
+use super::issue_session_tokens;
+
+pub async fn refresh(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    delivery: TokenDelivery,
+    jar: CookieJar,
+    request: Option<Json<RefreshRequest>>,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+    // Clients that keep their tokens themselves send the refresh token in the body
+    let token = match request
+        .map(|Json(request)| request.refresh_token)
+        .or_else(|| {
+            jar.get(REFRESH_COOKIE_NAME)
+                .map(|cookie| cookie.value().to_owned())
+        }) {
+        Some(token) => token,
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(token) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let family = match state.refresh_token_store.consume_token(&token).await {
+        Ok(family) => family,
+        Err(RefreshTokenStoreError::TokenReused(family)) => {
+            // A rotated token was presented again, so the whole family is compromised
+            if state
+                .refresh_token_store
+                .revoke_family(&family.id)
+                .await
+                .is_err()
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError));
+            }
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(RefreshTokenStoreError::UnexpectedError) => {
+            return (jar, Err(AuthAPIError::UnexpectedError))
+        }
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    // The family id is the session id. Touching never brings back a session that was removed
+    // in the meantime; families that predate the session registry get theirs registered here.
+    match state
+        .session_store
+        .touch_session(&family.id, Utc::now().timestamp())
+        .await
+    {
+        Ok(()) => (),
+        Err(SessionStoreError::SessionNotFound) => {
+            if let Err(e) = register_session(&family, client, &state).await {
+                return (jar, Err(e));
+            }
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    let tokens = match issue_session_tokens(family, &state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let (updated_jar, body) = tokens.deliver(delivery, jar);
+    let response = match body {
+        Some(tokens) => Json(tokens).into_response(),
+        None => StatusCode::OK.into_response(),
+    };
+
+    (updated_jar, Ok(response))
+}
+
+// Sessions are revoked by revoking the family before removing the session, so checking the
+// family after registering catches a revocation that raced with this refresh
+async fn register_session(
+    family: &RefreshTokenFamily,
+    client: ClientInfo,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    state
+        .session_store
+        .add_session(Session::new(
+            family.id.clone(),
+            family.email.clone(),
+            client.ip,
+            client.user_agent,
+        ))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let is_revoked = state
+        .refresh_token_store
+        .is_family_revoked(&family.id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if is_revoked {
+        state
+            .session_store
+            .remove_session(&family.id)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+}
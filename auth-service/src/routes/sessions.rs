@@ -0,0 +1,281 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::{cookie, CookieJar};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, RefreshToken, RefreshTokenFamily, Session, SessionStoreError},
+    utils::{
+        auth::{
+            create_auth_cookie, create_refresh_cookie, generate_access_token,
+            generate_refresh_token, TOKEN_TTL_SECONDS,
+        },
+        authenticated_user::AuthenticatedUser,
+        client_info::ClientInfo,
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+        token_delivery::TokenDelivery,
+    },
+};
+
+// Registers a new session for `email` and issues its first access and refresh tokens
+pub(crate) async fn start_session(
+    email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
+) -> Result<SessionTokens, AuthAPIError> {
+    let family = RefreshTokenFamily::new(email.clone());
+    let session = Session::new(
+        family.id.clone(),
+        email.clone(),
+        client.ip,
+        client.user_agent.clone(),
+    );
+
+    state
+        .session_store
+        .add_session(session)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    issue_session_tokens(family, state).await
+}
+
+// Signs an access token for the family's session and rotates in its next refresh token. The
+// user's roles are looked up each time, so a refresh picks up roles granted or taken away.
+// Disabled users get no tokens, even if they were mid-login or held a session when disabled.
+pub(crate) async fn issue_session_tokens(
+    family: RefreshTokenFamily,
+    state: &AppState,
+) -> Result<SessionTokens, AuthAPIError> {
+    let user = state
+        .user_store
+        .get_user(&family.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if user.disabled {
+        return Err(AuthAPIError::AccountDisabled);
+    }
+
+    let roles = state
+        .user_store
+        .get_roles(&family.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let access_token =
+        generate_access_token(&family.email, &family.id, &roles, state.keyring.clone())
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let refresh_token = generate_refresh_token(family, state.refresh_token_store.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(SessionTokens {
+        access_token,
+        refresh_token,
+    })
+}
+
+pub(crate) struct SessionTokens {
+    access_token: String,
+    refresh_token: RefreshToken,
+}
+
+impl SessionTokens {
+    // Sets the session cookies, or hands back the body for clients that asked for the tokens
+    pub(crate) fn deliver(
+        self,
+        delivery: TokenDelivery,
+        jar: CookieJar,
+    ) -> (CookieJar, Option<TokensResponse>) {
+        match delivery {
+            TokenDelivery::Cookies => (
+                jar.add(create_auth_cookie(self.access_token))
+                    .add(create_refresh_cookie(self.refresh_token)),
+                None,
+            ),
+            TokenDelivery::Body => (
+                jar,
+                Some(TokensResponse {
+                    access_token: self.access_token,
+                    refresh_token: self.refresh_token.as_ref().to_owned(),
+                    token_type: "Bearer".to_owned(),
+                    expires_in: TOKEN_TTL_SECONDS,
+                }),
+            ),
+        }
+    }
+}
+
+// Logs the user out everywhere: access tokens issued until now stop working, refresh tokens
+// can't be used any more and no session is left to resume
+pub(crate) async fn end_all_sessions(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let timestamp: usize = Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .banned_token_store
+        .revoke_user_tokens(email, timestamp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .refresh_token_store
+        .revoke_user_families(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .session_store
+        .remove_user_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, claims, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let sessions = state
+        .session_store
+        .list_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse {
+            current: session.id == claims.jti,
+            id: session.id,
+            device: session.device,
+            ip: session.ip.to_string(),
+            user_agent: session.user_agent,
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(SessionsResponse { sessions })))
+}
+
+// Signs one of the user's devices out. Its access token stops working immediately and its
+// refresh token can't be used to start over.
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, claims, .. }: AuthenticatedUser,
+    jar: CookieJar,
+    Json(request): Json<RevokeSessionRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    // Other users' sessions are reported as missing rather than forbidden
+    match state.session_store.get_session(&request.session_id).await {
+        Ok(session) if session.email == email => (),
+        Ok(_) | Err(SessionStoreError::SessionNotFound) => {
+            return (jar, Err(AuthAPIError::SessionNotFound))
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+
+    // The family goes first, so a refresh racing with this can't register the session again
+    if state
+        .refresh_token_store
+        .revoke_family(&request.session_id)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    if state
+        .session_store
+        .remove_session(&request.session_id)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let jar = if request.session_id == claims.jti {
+        remove_session_cookies(jar)
+    } else {
+        jar
+    };
+
+    (jar, Ok(StatusCode::OK))
+}
+
+// Log out everywhere, including the device making the request
+pub async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    if state
+        .refresh_token_store
+        .revoke_user_families(&email)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    if state
+        .session_store
+        .remove_user_sessions(&email)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    (remove_session_cookies(jar), Ok(StatusCode::OK))
+}
+
+pub(crate) fn remove_session_cookies(jar: CookieJar) -> CookieJar {
+    jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME))
+        .remove(cookie::Cookie::from(REFRESH_COOKIE_NAME))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevokeSessionRequest {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: String,
+    pub device: String,
+    pub ip: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    #[serde(rename = "lastSeen")]
+    pub last_seen: i64,
+    // Whether this is the session making the request
+    pub current: bool,
+}
+
+// Returned instead of cookies to clients that asked for their tokens in the body
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokensResponse {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "refreshToken")]
+    pub refresh_token: String,
+    #[serde(rename = "tokenType")]
+    pub token_type: String,
+    // Seconds until the access token expires
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
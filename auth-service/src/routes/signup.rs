@@ -3,37 +3,99 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    domain::{
+        AuthAPIError, Email, EmailVerificationToken, Locale, Password, TwoFAMethod, User,
+        UserStoreError,
+    },
+    services::email_templates::{EmailTemplate, EmailVerificationEmail},
+    utils::{client_info::ClientInfo, constants::AUTH_SERVICE_URL},
 };
 
+use super::issue_recovery_codes;
+
 pub async fn signup(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
     let email =
         Email::parse(&request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
     let password =
         Password::parse(&request.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    state
+        .password_policy
+        .check(&password, &email)
+        .map_err(AuthAPIError::WeakPassword)?;
 
-    let user = User::new(email, password, request.requires_2fa);
-
-    let mut user_store = state.user_store.write().await;
+    // Authenticator apps are enrolled after signup, so new accounts can only ask for email codes
+    let two_fa_method = if request.requires_2fa {
+        TwoFAMethod::Email
+    } else {
+        TwoFAMethod::None
+    };
+    let user = User::new(email.clone(), password, two_fa_method);
 
-    if user_store.get_user(&user.email).await.is_ok() {
+    if state.user_store.get_user(&user.email).await.is_ok() {
         return Err(AuthAPIError::UserAlreadyExists);
     }
 
-    if user_store.add_user(user).await.is_err() {
-        return Err(AuthAPIError::UnexpectedError);
+    // Two signups for the same email can both get past the check above; the store turns
+    // away whichever comes second
+    match state.user_store.add_user(user).await {
+        Ok(()) => (),
+        Err(UserStoreError::UserAlreadyExists) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
     }
 
+    // Email codes are no help to a user who loses their mailbox, so they get recovery codes too
+    let recovery_codes = match two_fa_method {
+        TwoFAMethod::None => Vec::new(),
+        _ => issue_recovery_codes(&email, &state).await?,
+    };
+
+    send_verification_email(&email, client.locale, &state).await?;
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
 }
 
+pub(crate) async fn send_verification_email(
+    email: &Email,
+    locale: Locale,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = EmailVerificationToken::default();
+
+    if state
+        .email_verification_token_store
+        .add_token(email.clone(), token.clone())
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let message = EmailVerificationEmail {
+        verification_link: format!(
+            "{}/?verify_email_token={}",
+            AUTH_SERVICE_URL.as_str(),
+            token.as_ref()
+        ),
+    }
+    .render(locale)
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .enqueue(email, message)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: String,
@@ -45,4 +107,11 @@ pub struct SignupRequest {
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct SignupResponse {
     pub message: String,
-}
\ No newline at end of file
+    // Only when signing up with 2FA
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub recovery_codes: Vec<String>,
+}
@@ -0,0 +1,144 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, TotpEnrollment, TotpSecret, TotpSecretStoreError, TwoFACode,
+        TwoFAMethod,
+    },
+    utils::authenticated_user::AuthenticatedUser,
+};
+
+use super::issue_recovery_codes;
+
+// Starts authenticator app enrollment for the logged in user. The secret only becomes active
+// once the user confirms it with a code through `confirm_totp`.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    let secret = TotpSecret::default();
+
+    if state
+        .totp_secret_store
+        .add_secret(&email, secret.clone())
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let response = Json(EnrollTotpResponse {
+        secret: secret.to_base32(),
+        otpauth_uri: secret.otpauth_uri(&email),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    AuthenticatedUser { email, .. }: AuthenticatedUser,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let enrollment = match state.totp_secret_store.get_secret(&email).await {
+        Ok(enrollment) => enrollment,
+        Err(TotpSecretStoreError::SecretNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if enrollment.confirmed {
+        return Err(AuthAPIError::TwoFAAlreadyEnabled);
+    }
+
+    if !verify_totp_code(&email, &two_fa_code, &enrollment, &state).await? {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    if state
+        .totp_secret_store
+        .confirm_secret(&email)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    if state
+        .user_store
+        .set_two_fa_method(&email, TwoFAMethod::Totp)
+        .await
+        .is_err()
+    {
+        return Err(AuthAPIError::UnexpectedError);
+    }
+
+    let recovery_codes = issue_recovery_codes(&email, &state).await?;
+
+    let response = Json(ConfirmTotpResponse {
+        message: "TOTP enabled successfully!".to_owned(),
+        recovery_codes,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Checks `code` against the enrolled secret. An accepted code burns its time step, so the
+// same code (or any older one) is refused from then on.
+pub(crate) async fn verify_totp_code(
+    email: &Email,
+    code: &TwoFACode,
+    enrollment: &TotpEnrollment,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    let step = match enrollment
+        .secret
+        .verify(code, Utc::now().timestamp() as u64)
+    {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+
+    match state.totp_secret_store.use_step(email, step).await {
+        Ok(()) => Ok(true),
+        Err(TotpSecretStoreError::StepAlreadyUsed) => Ok(false),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EnrollTotpResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConfirmTotpResponse {
+    pub message: String,
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
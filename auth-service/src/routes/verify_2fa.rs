@@ -1,13 +1,25 @@
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError, TwoFACode,
+        TwoFACodeStoreError, TwoFAMethod,
+    },
+    utils::{client_info::ClientInfo, token_delivery::TokenDelivery},
+};
+
+use super::{start_session, verify_totp_code};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
 };
-use axum::{extract::State, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 use serde::Deserialize;
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    client: ClientInfo,
+    delivery: TokenDelivery,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
@@ -24,23 +36,129 @@ pub async fn verify_2fa(
         Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let code_tuple = match two_fa_code_store.get_code(&email).await {
+    let code_tuple = match state.two_fa_code_store.get_code(&email).await {
         Ok(code_tuple) => code_tuple,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
-    if !code_tuple.0.eq(&login_attempt_id) || !code_tuple.1.eq(&two_fa_code) {
+    if !code_tuple.0.eq(&login_attempt_id) {
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
-    if two_fa_code_store.remove_code(&email).await.is_err() {
-        return (jar, Err(AuthAPIError::UnexpectedError));
+    if let Err(e) = record_attempt(&email, &login_attempt_id, &state).await {
+        return (jar, Err(e));
     }
-    let cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    let two_fa_method = match state.user_store.get_user(&email).await {
+        Ok(user) => user.two_fa_method,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+    let code_is_valid = match two_fa_method {
+        TwoFAMethod::Totp => {
+            let enrollment = match state.totp_secret_store.get_secret(&email).await {
+                Ok(enrollment) if enrollment.confirmed => enrollment,
+                _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+            };
+            match verify_totp_code(&email, &two_fa_code, &enrollment, &state).await {
+                Ok(is_valid) => is_valid,
+                Err(e) => return (jar, Err(e)),
+            }
+        }
+        _ => code_tuple.1.eq(&two_fa_code),
+    };
+    if !code_is_valid {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+    match state.two_fa_code_store.remove_code(&email).await {
+        Ok(()) => (),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+    issue_session(email, &client, delivery, &state, jar).await
+}
+
+// Stands in for the 2FA code when the user can't produce one. Each recovery code works once.
+pub async fn verify_recovery_code(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    delivery: TokenDelivery,
+    jar: CookieJar,
+    Json(request): Json<VerifyRecoveryCodeRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(&request.email) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id) {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
     };
-    let updated_jar = jar.add(cookie);
-    (updated_jar, Ok(()))
+    let recovery_code = match RecoveryCode::parse(request.recovery_code) {
+        Ok(recovery_code) => recovery_code,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    match state.two_fa_code_store.get_code(&email).await {
+        Ok((stored_login_attempt_id, _)) if stored_login_attempt_id == login_attempt_id => (),
+        _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    }
+    if let Err(e) = record_attempt(&email, &login_attempt_id, &state).await {
+        return (jar, Err(e));
+    }
+    match state
+        .recovery_code_store
+        .consume_code(&email, &recovery_code)
+        .await
+    {
+        Ok(()) => (),
+        Err(RecoveryCodeStoreError::CodeNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+    match state.two_fa_code_store.remove_code(&email).await {
+        Ok(()) => (),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    }
+    issue_session(email, &client, delivery, &state, jar).await
+}
+
+// Counts the attempt before the code is checked, so parallel guesses can't get past the limit
+async fn record_attempt(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    match state
+        .two_fa_code_store
+        .record_attempt(email, login_attempt_id)
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+async fn issue_session(
+    email: Email,
+    client: &ClientInfo,
+    delivery: TokenDelivery,
+    state: &AppState,
+    jar: CookieJar,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+    let tokens = match start_session(&email, client, state).await {
+        Ok(tokens) => tokens,
+        Err(e) => return (jar, Err(e)),
+    };
+    let (updated_jar, body) = tokens.deliver(delivery, jar);
+    let response = match body {
+        Some(tokens) => Json(tokens).into_response(),
+        None => StatusCode::OK.into_response(),
+    };
+    (updated_jar, Ok(response))
 }
 #[derive(Debug, Deserialize)]
 pub struct Verify2FARequest {
@@ -50,3 +168,12 @@ pub struct Verify2FARequest {
     #[serde(rename = "2FACode")]
     pub two_fa_code: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRecoveryCodeRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: String,
+}
@@ -0,0 +1,94 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, EmailVerificationToken, EmailVerificationTokenStoreError,
+        UserStoreError, EMAIL_VERIFICATION_RESEND_SECONDS,
+    },
+    utils::client_info::ClientInfo,
+};
+
+use super::send_verification_email;
+
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token =
+        EmailVerificationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = state
+        .email_verification_token_store
+        .consume_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // The address the link was sent to is gone if the account changed its email or was
+    // deleted since, which makes the link as good as expired
+    match state.user_store.mark_verified(&email).await {
+        Ok(()) => (),
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// For links that expired or never arrived. Responds the same way whether or not the account
+// exists or still needs verifying, so the route can't be used to find out which emails are
+// registered.
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Claimed for unknown emails too, so throttling doesn't give them away either
+    match state
+        .email_verification_token_store
+        .claim_resend(&email)
+        .await
+    {
+        Ok(()) => (),
+        Err(EmailVerificationTokenStoreError::ResendTooSoon) => {
+            return Err(AuthAPIError::TooManyRequests(
+                EMAIL_VERIFICATION_RESEND_SECONDS,
+            ))
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    if let Ok(user) = state.user_store.get_user(&email).await {
+        if !user.verified {
+            send_verification_email(&email, client.locale, &state).await?;
+        }
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "If the account needs verifying, a new link has been sent".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
@@ -1,18 +1,56 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{rejection::JsonRejection, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::{auth::validate_token, authenticated_user::get_access_token},
+};
 
+// Checks the token in the body. Requests without a JSON body have the token they carry
+// checked instead, from an `Authorization: Bearer` header or the `jwt` cookie.
+//
+// With `?include_claims=true` the token's claims come back in the body, so other services
+// can authorize the user by their roles without looking them up.
 pub async fn verify_token(
     State(state): State<AppState>,
-    Json(request): Json<VerifyTokenRequest>,
-) -> Result<StatusCode, AuthAPIError> {
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
-        Err(_) => Err(AuthAPIError::InvalidToken),
+    Query(query): Query<VerifyTokenQuery>,
+    headers: HeaderMap,
+    request: Result<Json<VerifyTokenRequest>, JsonRejection>,
+) -> Result<Response, Response> {
+    let token = match request {
+        Ok(Json(request)) => request.token,
+        Err(JsonRejection::MissingJsonContentType(_)) => {
+            get_access_token(&headers).map_err(IntoResponse::into_response)?
+        }
+        Err(rejection) => return Err(rejection.into_response()),
+    };
+
+    match validate_token(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        state.keyring.clone(),
+    )
+    .await
+    {
+        Ok(claims) if query.include_claims => Ok(Json(claims).into_response()),
+        Ok(_) => Ok(StatusCode::OK.into_response()),
+        Err(_) => Err(AuthAPIError::InvalidToken.into_response()),
     }
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct VerifyTokenQuery {
+    #[serde(default)]
+    include_claims: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct VerifyTokenRequest {
     token: String,
@@ -0,0 +1,113 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SentEmail {
+    pub recipient: Email,
+    pub subject: String,
+    pub text_body: String,
+    pub html_body: String,
+}
+
+// Keeps every email in memory instead of sending it. Useful in tests, which read codes and
+// links back out through the handle returned by `CapturingEmailClient::handle`.
+#[derive(Default)]
+pub struct CapturingEmailClient {
+    emails: CapturedEmails,
+}
+
+impl CapturingEmailClient {
+    // The handle stays usable after the client itself has been handed to the app
+    pub fn handle(&self) -> CapturedEmails {
+        self.emails.clone()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct CapturedEmails {
+    sent: Arc<Mutex<Vec<SentEmail>>>,
+    failing: Arc<AtomicBool>,
+}
+
+impl CapturedEmails {
+    pub fn all(&self) -> Vec<SentEmail> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn last_email_to(&self, recipient: &Email) -> Option<SentEmail> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|email| email.recipient == *recipient)
+            .cloned()
+    }
+
+    // Makes every send fail, like a relay that is down
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for CapturingEmailClient {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        if self.emails.failing.load(Ordering::SeqCst) {
+            return Err("Relay unavailable".to_owned());
+        }
+
+        self.emails.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.clone(),
+            subject: message.subject.clone(),
+            text_body: message.text_body.clone(),
+            html_body: message.html_body.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(subject: &str) -> EmailMessage {
+        EmailMessage {
+            subject: subject.to_owned(),
+            html_body: "<p>Body</p>".to_owned(),
+            text_body: "Body".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_return_last_email_to_recipient() {
+        let client = CapturingEmailClient::default();
+        let emails = client.handle();
+        let alice = Email::parse("alice@example.com").unwrap();
+        let bob = Email::parse("bob@example.com").unwrap();
+
+        client.send_email(&alice, &message("First")).await.unwrap();
+        client.send_email(&alice, &message("Second")).await.unwrap();
+        client.send_email(&bob, &message("Third")).await.unwrap();
+
+        assert_eq!(emails.last_email_to(&alice).unwrap().subject, "Second");
+        assert_eq!(emails.all().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_fail_and_capture_nothing_while_failing() {
+        let client = CapturingEmailClient::default();
+        let emails = client.handle();
+        let alice = Email::parse("alice@example.com").unwrap();
+
+        emails.set_failing(true);
+
+        assert!(client.send_email(&alice, &message("Lost")).await.is_err());
+        assert!(emails.last_email_to(&alice).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{AdminAuditPage, AdminAuditStore, AdminAuditStoreError},
+    AdminAuditEntry, Email,
+};
+
+#[derive(Default)]
+pub struct HashmapAdminAuditStore {
+    // Oldest first, in the order they were recorded
+    entries: RwLock<Vec<AdminAuditEntry>>,
+}
+
+#[async_trait::async_trait]
+impl AdminAuditStore for HashmapAdminAuditStore {
+    async fn record(&self, entry: AdminAuditEntry) -> Result<(), AdminAuditStoreError> {
+        self.entries.write().await.push(entry);
+        Ok(())
+    }
+
+    async fn list_entries(
+        &self,
+        target: Option<&Email>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<AdminAuditPage, AdminAuditStoreError> {
+        let entries = self.entries.read().await;
+        let matching: Vec<&AdminAuditEntry> = entries
+            .iter()
+            .rev()
+            .filter(|entry| target.is_none() || entry.target.as_ref() == target)
+            .collect();
+
+        Ok(AdminAuditPage {
+            total: matching.len() as u64,
+            entries: matching
+                .into_iter()
+                .skip(offset.try_into().unwrap_or(usize::MAX))
+                .take(limit.try_into().unwrap_or(usize::MAX))
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::AdminAction;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_list_newest_entries_first() {
+        let store = HashmapAdminAuditStore::default();
+        let admin = Email::parse("admin@example.com").unwrap();
+        let alice = Email::parse("alice@example.com").unwrap();
+        let bob = Email::parse("bob@example.com").unwrap();
+
+        for (action, target) in [
+            (AdminAction::ListUsers, None),
+            (AdminAction::DisableUser, Some(alice.clone())),
+            (AdminAction::RevokeTokens, Some(bob.clone())),
+            (AdminAction::EnableUser, Some(alice.clone())),
+        ] {
+            store
+                .record(AdminAuditEntry::new(admin.clone(), action, target, None))
+                .await
+                .unwrap();
+        }
+
+        let page = store.list_entries(None, 1, 2).await.unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(
+            page.entries
+                .iter()
+                .map(|entry| entry.action)
+                .collect::<Vec<_>>(),
+            vec![AdminAction::RevokeTokens, AdminAction::DisableUser]
+        );
+
+        let page = store.list_entries(Some(&alice), 0, 10).await.unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(
+            page.entries
+                .iter()
+                .map(|entry| entry.action)
+                .collect::<Vec<_>>(),
+            vec![AdminAction::EnableUser, AdminAction::DisableUser]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::data_stores::{
+    EmailChange, EmailChangeToken, EmailChangeTokenStore, EmailChangeTokenStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapEmailChangeTokenStore {
+    tokens: RwLock<HashMap<EmailChangeToken, EmailChange>>,
+}
+
+#[async_trait::async_trait]
+impl EmailChangeTokenStore for HashmapEmailChangeTokenStore {
+    async fn add_token(
+        &self,
+        change: EmailChange,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError> {
+        self.tokens.write().await.insert(token, change);
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<EmailChange, EmailChangeTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .remove(token)
+            .ok_or(EmailChangeTokenStoreError::TokenNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    fn change() -> EmailChange {
+        EmailChange {
+            email: Email::parse("old@example.com").unwrap(),
+            new_email: Email::parse("new@example.com").unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let store = HashmapEmailChangeTokenStore::default();
+        let token = EmailChangeToken::default();
+
+        store.add_token(change(), token.clone()).await.unwrap();
+
+        assert_eq!(store.consume_token(&token).await, Ok(change()));
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_twice() {
+        let store = HashmapEmailChangeTokenStore::default();
+        let token = EmailChangeToken::default();
+
+        store.add_token(change(), token.clone()).await.unwrap();
+        store.consume_token(&token).await.unwrap();
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailChangeTokenStoreError::TokenNotFound)
+        );
+    }
+}
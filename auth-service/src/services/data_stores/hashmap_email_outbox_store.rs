@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{EmailOutboxStore, EmailOutboxStoreError},
+    OutboxEmail,
+};
+
+struct Entry {
+    email: OutboxEmail,
+    position: u64,
+    next_attempt_at: i64,
+    dead: bool,
+}
+
+#[derive(Default)]
+pub struct HashmapEmailOutboxStore {
+    inner: RwLock<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    entries: HashMap<Uuid, Entry>,
+    next_position: u64,
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for HashmapEmailOutboxStore {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        let mut inner = self.inner.write().await;
+        let entry = Entry {
+            position: inner.next_position,
+            next_attempt_at: email.created_at,
+            email,
+            dead: false,
+        };
+        inner.next_position += 1;
+        inner.entries.insert(entry.email.id, entry);
+        Ok(())
+    }
+
+    async fn claim_next(
+        &self,
+        now: i64,
+        lease_seconds: u64,
+        skip: &[Uuid],
+    ) -> Result<Option<OutboxEmail>, EmailOutboxStoreError> {
+        let mut inner = self.inner.write().await;
+        let next = inner
+            .entries
+            .values_mut()
+            .filter(|entry| {
+                !entry.dead && entry.next_attempt_at <= now && !skip.contains(&entry.email.id)
+            })
+            .min_by_key(|entry| (entry.next_attempt_at, entry.position));
+
+        Ok(next.map(|entry| {
+            entry.next_attempt_at = now + lease_seconds as i64;
+            entry.email.clone()
+        }))
+    }
+
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxStoreError> {
+        self.inner
+            .write()
+            .await
+            .entries
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(EmailOutboxStoreError::EmailNotFound)
+    }
+
+    async fn mark_failed(
+        &self,
+        id: Uuid,
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let mut inner = self.inner.write().await;
+        let entry = inner
+            .entries
+            .get_mut(&id)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+
+        entry.email.attempts += 1;
+        entry.email.last_error = Some(error.to_owned());
+        match retry_at {
+            Some(retry_at) => entry.next_attempt_at = retry_at,
+            None => entry.dead = true,
+        }
+        Ok(())
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let inner = self.inner.read().await;
+        let mut dead_letters: Vec<&Entry> =
+            inner.entries.values().filter(|entry| entry.dead).collect();
+        dead_letters.sort_by_key(|entry| entry.position);
+        Ok(dead_letters
+            .into_iter()
+            .map(|entry| entry.email.clone())
+            .collect())
+    }
+
+    async fn replay_dead_letter(&self, id: Uuid, now: i64) -> Result<(), EmailOutboxStoreError> {
+        let mut inner = self.inner.write().await;
+        let entry = inner
+            .entries
+            .get_mut(&id)
+            .filter(|entry| entry.dead)
+            .ok_or(EmailOutboxStoreError::EmailNotFound)?;
+
+        entry.dead = false;
+        entry.email.attempts = 0;
+        entry.next_attempt_at = now;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Email, EmailMessage};
+
+    fn outbox_email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse("test@example.com").unwrap(),
+            EmailMessage {
+                subject: "Subject".to_owned(),
+                html_body: "<p>Body</p>".to_owned(),
+                text_body: "Body".to_owned(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_claim_lease_and_retry() {
+        let store = HashmapEmailOutboxStore::default();
+        let email = outbox_email();
+        let now = email.created_at;
+
+        store.enqueue(email.clone()).await.unwrap();
+
+        assert_eq!(
+            store.claim_next(now, 60, &[]).await.unwrap(),
+            Some(email.clone())
+        );
+        // Leased to the first claim
+        assert_eq!(store.claim_next(now, 60, &[]).await.unwrap(), None);
+
+        store
+            .mark_failed(email.id, "Relay unavailable", Some(now + 5))
+            .await
+            .unwrap();
+        assert_eq!(store.claim_next(now + 4, 60, &[]).await.unwrap(), None);
+
+        let retried = store.claim_next(now + 5, 60, &[]).await.unwrap().unwrap();
+        assert_eq!(retried.attempts, 1);
+        assert_eq!(retried.last_error.as_deref(), Some("Relay unavailable"));
+
+        store.mark_sent(email.id).await.unwrap();
+        assert_eq!(store.claim_next(now + 3600, 60, &[]).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_claim_in_order_and_skip() {
+        let store = HashmapEmailOutboxStore::default();
+        let first = outbox_email();
+        let second = outbox_email();
+        let now = first.created_at.max(second.created_at);
+
+        store.enqueue(first.clone()).await.unwrap();
+        store.enqueue(second.clone()).await.unwrap();
+
+        assert_eq!(
+            store.claim_next(now, 60, &[]).await.unwrap(),
+            Some(first.clone())
+        );
+        store
+            .mark_failed(first.id, "Relay unavailable", Some(now))
+            .await
+            .unwrap();
+
+        // Due again, but already tried
+        assert_eq!(
+            store.claim_next(now, 60, &[first.id]).await.unwrap(),
+            Some(second)
+        );
+        assert_eq!(store.claim_next(now, 60, &[first.id]).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_and_replay() {
+        let store = HashmapEmailOutboxStore::default();
+        let email = outbox_email();
+        let now = email.created_at;
+
+        store.enqueue(email.clone()).await.unwrap();
+        store.claim_next(now, 60, &[]).await.unwrap();
+        store
+            .mark_failed(email.id, "Mailbox unavailable", None)
+            .await
+            .unwrap();
+
+        assert_eq!(store.claim_next(now + 3600, 60, &[]).await.unwrap(), None);
+        let dead_letters = store.get_dead_letters().await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].id, email.id);
+
+        store.replay_dead_letter(email.id, now).await.unwrap();
+        assert!(store.get_dead_letters().await.unwrap().is_empty());
+        let replayed = store.claim_next(now, 60, &[]).await.unwrap().unwrap();
+        assert_eq!(replayed.attempts, 0);
+
+        assert_eq!(
+            store.replay_dead_letter(email.id, now).await,
+            Err(EmailOutboxStoreError::EmailNotFound)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{
+        EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError,
+        EMAIL_VERIFICATION_RESEND_SECONDS,
+    },
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapEmailVerificationTokenStore {
+    tokens: RwLock<HashMap<EmailVerificationToken, Email>>,
+    // When each email last had a link resent, in seconds since the epoch
+    resends: RwLock<HashMap<Email, i64>>,
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for HashmapEmailVerificationTokenStore {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        self.tokens.write().await.insert(token, email);
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .remove(token)
+            .ok_or(EmailVerificationTokenStoreError::TokenNotFound)
+    }
+
+    async fn claim_resend(&self, email: &Email) -> Result<(), EmailVerificationTokenStoreError> {
+        let now = Utc::now().timestamp();
+        let mut resends = self.resends.write().await;
+
+        if let Some(last_resend) = resends.get(email) {
+            if now - last_resend < EMAIL_VERIFICATION_RESEND_SECONDS as i64 {
+                return Err(EmailVerificationTokenStoreError::ResendTooSoon);
+            }
+        }
+        resends.insert(email.clone(), now);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = EmailVerificationToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_twice() {
+        let store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = EmailVerificationToken::default();
+
+        store.add_token(email, token.clone()).await.unwrap();
+        store.consume_token(&token).await.unwrap();
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(EmailVerificationTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claim_resend_only_once_per_cooldown() {
+        let store = HashmapEmailVerificationTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        assert_eq!(store.claim_resend(&email).await, Ok(()));
+        assert_eq!(
+            store.claim_resend(&email).await,
+            Err(EmailVerificationTokenStoreError::ResendTooSoon)
+        );
+
+        // The cooldown is per email address
+        let other_email = Email::parse("other@example.com").unwrap();
+        assert_eq!(store.claim_resend(&other_email).await, Ok(()));
+    }
+}
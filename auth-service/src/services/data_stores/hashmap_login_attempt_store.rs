@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{LoginAttemptStore, LoginAttemptStoreError},
+    LoginAttemptKey, LoginFailures,
+};
+
+#[derive(Default)]
+pub struct HashmapLoginAttemptStore {
+    failures: RwLock<HashMap<LoginAttemptKey, LoginFailures>>,
+}
+
+fn current_failures(
+    failures: &HashMap<LoginAttemptKey, LoginFailures>,
+    key: &LoginAttemptKey,
+    now: i64,
+) -> Option<LoginFailures> {
+    failures
+        .get(key)
+        .filter(|failures| now - failures.last_failure < key.policy().lockout_seconds as i64)
+        .copied()
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for HashmapLoginAttemptStore {
+    async fn record_attempt(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<LoginFailures, LoginAttemptStoreError> {
+        let now = Utc::now().timestamp();
+        let mut all_failures = self.failures.write().await;
+        let failures = current_failures(&all_failures, key, now).unwrap_or(LoginFailures {
+            count: 0,
+            last_failure: now,
+        });
+
+        all_failures.insert(
+            key.clone(),
+            LoginFailures {
+                count: failures.count + 1,
+                ..failures
+            },
+        );
+
+        Ok(failures)
+    }
+
+    async fn forget_attempt(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        if let Some(failures) = self.failures.write().await.get_mut(key) {
+            failures.count = failures.count.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    async fn record_failure(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        if let Some(failures) = self.failures.write().await.get_mut(key) {
+            failures.last_failure = Utc::now().timestamp();
+        }
+        Ok(())
+    }
+
+    async fn get_failures(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        Ok(current_failures(
+            &*self.failures.read().await,
+            key,
+            Utc::now().timestamp(),
+        ))
+    }
+
+    async fn clear_failures(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        self.failures.write().await.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::Email;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_attempt_counts_per_key() {
+        let store = HashmapLoginAttemptStore::default();
+        let email_key = LoginAttemptKey::Email(Email::parse("test@example.com").unwrap());
+        let ip_key = LoginAttemptKey::Ip("127.0.0.1".parse().unwrap());
+
+        assert_eq!(store.get_failures(&email_key).await, Ok(None));
+
+        assert_eq!(store.record_attempt(&email_key).await.unwrap().count, 0);
+        assert_eq!(store.record_attempt(&email_key).await.unwrap().count, 1);
+        assert_eq!(
+            store.get_failures(&email_key).await.unwrap().unwrap().count,
+            2
+        );
+
+        assert_eq!(store.record_attempt(&ip_key).await.unwrap().count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_forget_attempt() {
+        let store = HashmapLoginAttemptStore::default();
+        let key = LoginAttemptKey::Email(Email::parse("test@example.com").unwrap());
+
+        store.record_attempt(&key).await.unwrap();
+        store.record_attempt(&key).await.unwrap();
+        store.forget_attempt(&key).await.unwrap();
+
+        assert_eq!(store.get_failures(&key).await.unwrap().unwrap().count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_failure_restarts_delay() {
+        let store = HashmapLoginAttemptStore::default();
+        let key = LoginAttemptKey::Email(Email::parse("test@example.com").unwrap());
+        let earlier = Utc::now().timestamp() - 60;
+        store.failures.write().await.insert(
+            key.clone(),
+            LoginFailures {
+                count: 5,
+                last_failure: earlier,
+            },
+        );
+
+        store.record_failure(&key).await.unwrap();
+
+        let failures = store.get_failures(&key).await.unwrap().unwrap();
+        assert_eq!(failures.count, 5);
+        assert!(failures.last_failure > earlier);
+    }
+
+    #[tokio::test]
+    async fn test_clear_failures() {
+        let store = HashmapLoginAttemptStore::default();
+        let key = LoginAttemptKey::Email(Email::parse("test@example.com").unwrap());
+
+        store.record_attempt(&key).await.unwrap();
+        store.clear_failures(&key).await.unwrap();
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_failures_expire() {
+        let store = HashmapLoginAttemptStore::default();
+        let key = LoginAttemptKey::Email(Email::parse("test@example.com").unwrap());
+        let expired = Utc::now().timestamp() - key.policy().lockout_seconds as i64;
+        store.failures.write().await.insert(
+            key.clone(),
+            LoginFailures {
+                count: 5,
+                last_failure: expired,
+            },
+        );
+
+        assert_eq!(store.get_failures(&key).await, Ok(None));
+        assert_eq!(store.record_attempt(&key).await.unwrap().count, 0);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapPasswordResetTokenStore {
+    tokens: RwLock<HashMap<PasswordResetToken, Email>>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashmapPasswordResetTokenStore {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens.write().await.insert(token, email);
+        Ok(())
+    }
+
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        self.tokens
+            .read()
+            .await
+            .get(token)
+            .cloned()
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+
+    async fn consume_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .remove(token)
+            .ok_or(PasswordResetTokenStoreError::TokenNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_get_email_keeps_token() {
+        let store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(email.clone(), token.clone()).await.unwrap();
+
+        assert_eq!(store.get_email(&token).await, Ok(email.clone()));
+        assert_eq!(store.consume_token(&token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_twice() {
+        let store = HashmapPasswordResetTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = PasswordResetToken::default();
+
+        store.add_token(email, token.clone()).await.unwrap();
+        store.consume_token(&token).await.unwrap();
+
+        assert_eq!(
+            store.consume_token(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapRecoveryCodeStore {
+    codes: RwLock<HashMap<Email, Vec<RecoveryCode>>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashmapRecoveryCodeStore {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes.write().await.insert(email.clone(), codes);
+        Ok(())
+    }
+
+    async fn consume_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let mut all_codes = self.codes.write().await;
+        let codes = all_codes
+            .get_mut(email)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        let position = codes
+            .iter()
+            .position(|stored| stored == code)
+            .ok_or(RecoveryCodeStoreError::CodeNotFound)?;
+
+        codes.remove(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_consume_code_once() {
+        let store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let codes = RecoveryCode::generate_batch();
+
+        store.replace_codes(&email, codes.clone()).await.unwrap();
+
+        assert_eq!(store.consume_code(&email, &codes[0]).await, Ok(()));
+        assert_eq!(
+            store.consume_code(&email, &codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.consume_code(&email, &codes[1]).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_replace_codes_invalidates_old_batch() {
+        let store = HashmapRecoveryCodeStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let old_codes = RecoveryCode::generate_batch();
+        let new_codes = RecoveryCode::generate_batch();
+
+        store
+            .replace_codes(&email, old_codes.clone())
+            .await
+            .unwrap();
+        store
+            .replace_codes(&email, new_codes.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.consume_code(&email, &old_codes[0]).await,
+            Err(RecoveryCodeStoreError::CodeNotFound)
+        );
+        assert_eq!(store.consume_code(&email, &new_codes[0]).await, Ok(()));
+    }
+}
@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{RefreshToken, RefreshTokenFamily, RefreshTokenStore, RefreshTokenStoreError},
+    Email,
+};
+
+#[derive(Default)]
+pub struct HashmapRefreshTokenStore {
+    // Both maps sit behind one lock so that consuming a token sees a consistent view
+    inner: RwLock<Tokens>,
+}
+
+#[derive(Default)]
+struct Tokens {
+    // Maps each issued token to its family and whether it has already been rotated.
+    tokens: HashMap<RefreshToken, (RefreshTokenFamily, bool)>,
+    revoked_families: HashSet<String>,
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashmapRefreshTokenStore {
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.inner
+            .write()
+            .await
+            .tokens
+            .insert(token, (family, false));
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError> {
+        let mut inner = self.inner.write().await;
+        let Tokens {
+            tokens,
+            revoked_families,
+        } = &mut *inner;
+        let (family, used) = tokens
+            .get_mut(token)
+            .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        if revoked_families.contains(&family.id) {
+            return Err(RefreshTokenStoreError::FamilyRevoked);
+        }
+
+        if *used {
+            return Err(RefreshTokenStoreError::TokenReused(family.clone()));
+        }
+
+        *used = true;
+        Ok(family.clone())
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenStoreError> {
+        self.inner
+            .write()
+            .await
+            .revoked_families
+            .insert(family_id.to_owned());
+        Ok(())
+    }
+
+    async fn revoke_user_families(&self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let mut inner = self.inner.write().await;
+        let family_ids: Vec<String> = inner
+            .tokens
+            .values()
+            .filter(|(family, _)| family.email == *email)
+            .map(|(family, _)| family.id.clone())
+            .collect();
+
+        inner.revoked_families.extend(family_ids);
+        Ok(())
+    }
+
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError> {
+        Ok(self.inner.read().await.revoked_families.contains(family_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family() -> RefreshTokenFamily {
+        RefreshTokenFamily::new(Email::parse("test@example.com").unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_consume_token() {
+        let store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let family = family();
+
+        store
+            .add_token(token.clone(), family.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.consume_token(&token).await, Ok(family));
+    }
+
+    #[tokio::test]
+    async fn test_consume_unknown_token() {
+        let store = HashmapRefreshTokenStore::default();
+
+        let result = store.consume_token(&RefreshToken::default()).await;
+
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_consume_token_twice() {
+        let store = HashmapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let family = family();
+
+        store
+            .add_token(token.clone(), family.clone())
+            .await
+            .unwrap();
+        store.consume_token(&token).await.unwrap();
+
+        let result = store.consume_token(&token).await;
+
+        assert_eq!(result, Err(RefreshTokenStoreError::TokenReused(family)));
+    }
+
+    #[tokio::test]
+    async fn test_revoke_family() {
+        let store = HashmapRefreshTokenStore::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let family = family();
+
+        store
+            .add_token(first.clone(), family.clone())
+            .await
+            .unwrap();
+        store
+            .add_token(second.clone(), family.clone())
+            .await
+            .unwrap();
+        store.revoke_family(&family.id).await.unwrap();
+
+        assert_eq!(
+            store.consume_token(&second).await,
+            Err(RefreshTokenStoreError::FamilyRevoked)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revoke_user_families() {
+        let store = HashmapRefreshTokenStore::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        let other_family = RefreshTokenFamily::new(Email::parse("other@example.com").unwrap());
+
+        store.add_token(first.clone(), family()).await.unwrap();
+        store.add_token(second.clone(), family()).await.unwrap();
+        store
+            .add_token(other.clone(), other_family.clone())
+            .await
+            .unwrap();
+        store
+            .revoke_user_families(&Email::parse("test@example.com").unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.consume_token(&first).await,
+            Err(RefreshTokenStoreError::FamilyRevoked)
+        );
+        assert_eq!(
+            store.consume_token(&second).await,
+            Err(RefreshTokenStoreError::FamilyRevoked)
+        );
+        assert_eq!(store.consume_token(&other).await, Ok(other_family));
+    }
+
+    #[tokio::test]
+    async fn test_is_family_revoked() {
+        let store = HashmapRefreshTokenStore::default();
+        let family = family();
+
+        store
+            .add_token(RefreshToken::default(), family.clone())
+            .await
+            .unwrap();
+        assert_eq!(store.is_family_revoked(&family.id).await, Ok(false));
+
+        store.revoke_family(&family.id).await.unwrap();
+        assert_eq!(store.is_family_revoked(&family.id).await, Ok(true));
+    }
+}
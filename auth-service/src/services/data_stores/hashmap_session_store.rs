@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{SessionStore, SessionStoreError},
+    Email, Session,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions
+            .write()
+            .await
+            .insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn touch_session(&self, id: &str, last_seen: i64) -> Result<(), SessionStoreError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen = last_seen;
+        Ok(())
+    }
+
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .read()
+            .await
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    async fn remove_session(&self, id: &str) -> Result<(), SessionStoreError> {
+        self.sessions
+            .write()
+            .await
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_user_sessions(&self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions
+            .write()
+            .await
+            .retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(email: &Email) -> Session {
+        Session::new(
+            uuid::Uuid::new_v4().to_string(),
+            email.clone(),
+            "127.0.0.1".parse().unwrap(),
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_touch_session() {
+        let store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let session = session(&email);
+
+        store.add_session(session.clone()).await.unwrap();
+        store
+            .touch_session(&session.id, session.last_seen + 60)
+            .await
+            .unwrap();
+
+        let stored = store.get_session(&session.id).await.unwrap();
+        assert_eq!(stored.last_seen, session.last_seen + 60);
+        assert_eq!(
+            store.touch_session("unknown", 0).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_and_remove_sessions() {
+        let store = HashmapSessionStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let other_email = Email::parse("other@example.com").unwrap();
+        let first = session(&email);
+        let second = session(&email);
+        let other = session(&other_email);
+
+        for session in [first.clone(), second.clone(), other.clone()] {
+            store.add_session(session).await.unwrap();
+        }
+
+        assert_eq!(store.list_sessions(&email).await.unwrap().len(), 2);
+
+        store.remove_session(&first.id).await.unwrap();
+        assert_eq!(
+            store.get_session(&first.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(store.list_sessions(&email).await.unwrap(), vec![second]);
+
+        store.remove_user_sessions(&email).await.unwrap();
+        assert!(store.list_sessions(&email).await.unwrap().is_empty());
+        assert_eq!(store.get_session(&other.id).await, Ok(other));
+    }
+}
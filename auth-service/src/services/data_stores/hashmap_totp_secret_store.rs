@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{TotpEnrollment, TotpSecretStore, TotpSecretStoreError},
+    Email, TotpSecret,
+};
+
+#[derive(Default)]
+pub struct HashmapTotpSecretStore {
+    secrets: RwLock<HashMap<Email, (TotpEnrollment, Option<u64>)>>,
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for HashmapTotpSecretStore {
+    async fn add_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        let enrollment = TotpEnrollment {
+            secret,
+            confirmed: false,
+        };
+        self.secrets
+            .write()
+            .await
+            .insert(email.clone(), (enrollment, None));
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError> {
+        self.secrets
+            .read()
+            .await
+            .get(email)
+            .map(|(enrollment, _)| enrollment.clone())
+            .ok_or(TotpSecretStoreError::SecretNotFound)
+    }
+
+    async fn confirm_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        let mut secrets = self.secrets.write().await;
+        let (enrollment, _) = secrets
+            .get_mut(email)
+            .ok_or(TotpSecretStoreError::SecretNotFound)?;
+        enrollment.confirmed = true;
+        Ok(())
+    }
+
+    async fn use_step(&self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        let mut secrets = self.secrets.write().await;
+        let (_, last_used_step) = secrets
+            .get_mut(email)
+            .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        if last_used_step.is_some_and(|last_used_step| step <= last_used_step) {
+            return Err(TotpSecretStoreError::StepAlreadyUsed);
+        }
+
+        *last_used_step = Some(step);
+        Ok(())
+    }
+
+    async fn delete_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        self.secrets.write().await.remove(email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_and_confirm_secret() {
+        let store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+        let secret = TotpSecret::default();
+
+        store.add_secret(&email, secret.clone()).await.unwrap();
+
+        let enrollment = store.get_secret(&email).await.unwrap();
+        assert_eq!(enrollment.secret, secret);
+        assert!(!enrollment.confirmed);
+
+        store.confirm_secret(&email).await.unwrap();
+        assert!(store.get_secret(&email).await.unwrap().confirmed);
+    }
+
+    #[tokio::test]
+    async fn test_use_step_rejects_replay() {
+        let store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        assert_eq!(
+            store.use_step(&email, 10).await,
+            Err(TotpSecretStoreError::SecretNotFound)
+        );
+
+        store
+            .add_secret(&email, TotpSecret::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.use_step(&email, 10).await, Ok(()));
+        assert_eq!(
+            store.use_step(&email, 10).await,
+            Err(TotpSecretStoreError::StepAlreadyUsed)
+        );
+        assert_eq!(
+            store.use_step(&email, 9).await,
+            Err(TotpSecretStoreError::StepAlreadyUsed)
+        );
+        assert_eq!(store.use_step(&email, 11).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_delete_secret() {
+        let store = HashmapTotpSecretStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        store
+            .add_secret(&email, TotpSecret::default())
+            .await
+            .unwrap();
+
+        assert_eq!(store.delete_secret(&email).await, Ok(()));
+        assert_eq!(
+            store.get_secret(&email).await,
+            Err(TotpSecretStoreError::SecretNotFound)
+        );
+        assert_eq!(store.delete_secret(&email).await, Ok(()));
+    }
+}
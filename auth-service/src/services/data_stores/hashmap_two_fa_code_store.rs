@@ -1,28 +1,36 @@
 use std::collections::HashMap;
 
+use tokio::sync::RwLock;
+
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, MAX_TWO_FA_ATTEMPTS,
+    },
     Email,
 };
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    // The last element counts codes submitted for the login attempt
+    codes: RwLock<HashMap<Email, (LoginAttemptId, TwoFACode, u32)>>,
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes
+            .write()
+            .await
+            .insert(email, (login_attempt_id, code, 0));
         Ok(())
     }
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        match self.codes.remove(email) {
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        match self.codes.write().await.remove(email) {
             Some(_) => Ok(()),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -31,11 +39,30 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
+        match self.codes.read().await.get(email) {
             Some(val) => Ok((val.0.clone(), val.1.clone())),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+    async fn record_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let mut codes = self.codes.write().await;
+        let attempts = match codes.get_mut(email) {
+            Some((stored_id, _, attempts)) if stored_id == login_attempt_id => {
+                *attempts += 1;
+                *attempts
+            }
+            _ => return Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        };
+        if attempts > MAX_TWO_FA_ATTEMPTS {
+            codes.remove(email);
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -44,9 +71,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_code() {
-        let mut code_store = HashmapTwoFACodeStore {
-            codes: HashMap::new(),
-        };
+        let code_store = HashmapTwoFACodeStore::default();
 
         let email = Email::parse("joebiden@whitehouse.gov").expect("Invalid Email");
         let login_attempt_id = LoginAttemptId::default();
@@ -62,9 +87,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_remove_code() {
-        let mut code_store = HashmapTwoFACodeStore {
-            codes: HashMap::new(),
-        };
+        let code_store = HashmapTwoFACodeStore::default();
 
         let email = Email::parse("joebiden@whitehouse.gov").expect("Invalid Email");
         let login_attempt_id = LoginAttemptId::default();
@@ -91,9 +114,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_code() {
-        let mut code_store = HashmapTwoFACodeStore {
-            codes: HashMap::new(),
-        };
+        let code_store = HashmapTwoFACodeStore::default();
 
         let email = Email::parse("joebiden@whitehouse.gov").expect("Invalid Email");
         let login_attempt_id = LoginAttemptId::default();
@@ -113,4 +134,87 @@ mod tests {
 
         assert_eq!(result, (login_attempt_id, code));
     }
+
+    #[tokio::test]
+    async fn test_code_burned_after_max_attempts() {
+        let code_store = HashmapTwoFACodeStore::default();
+
+        let email = Email::parse("joebiden@whitehouse.gov").expect("Invalid Email");
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        code_store
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .await
+            .expect("Unable to add code");
+
+        for _ in 0..MAX_TWO_FA_ATTEMPTS {
+            code_store
+                .record_attempt(&email, &login_attempt_id)
+                .await
+                .expect("Unable to record attempt");
+        }
+
+        assert_eq!(
+            code_store.get_code(&email).await,
+            Ok((login_attempt_id.clone(), code))
+        );
+
+        assert_eq!(
+            code_store.record_attempt(&email, &login_attempt_id).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(
+            code_store.get_code(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_attempts_are_per_login_attempt() {
+        let code_store = HashmapTwoFACodeStore::default();
+
+        let email = Email::parse("joebiden@whitehouse.gov").expect("Invalid Email");
+        let old_login_attempt_id = LoginAttemptId::default();
+        let login_attempt_id = LoginAttemptId::default();
+
+        code_store
+            .add_code(
+                email.clone(),
+                old_login_attempt_id.clone(),
+                TwoFACode::default(),
+            )
+            .await
+            .expect("Unable to add code");
+
+        for _ in 0..MAX_TWO_FA_ATTEMPTS {
+            code_store
+                .record_attempt(&email, &old_login_attempt_id)
+                .await
+                .expect("Unable to record attempt");
+        }
+
+        code_store
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                TwoFACode::default(),
+            )
+            .await
+            .expect("Unable to add code");
+
+        assert_eq!(
+            code_store
+                .record_attempt(&email, &old_login_attempt_id)
+                .await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        code_store
+            .record_attempt(&email, &login_attempt_id)
+            .await
+            .expect("Unable to record attempt");
+
+        assert!(code_store.get_code(&email).await.is_ok());
+    }
 }
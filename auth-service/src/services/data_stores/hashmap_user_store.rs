@@ -1,23 +1,30 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    Email, Password, Role, TwoFAMethod, User, UserPage, UserStore, UserStoreError,
+};
 
 // stores a `HashMap`` of email `String`s mapped to `User` objects.
 // Derive the `Default` trait for `HashmapUserStore`.
 #[derive(Default)]
 pub struct HashmapUserStore {
-    pub users: HashMap<Email, User>,
+    users: RwLock<HashMap<Email, User>>,
+    // Any role can be assigned, there's no list of defined roles
+    roles: RwLock<HashMap<Email, BTreeSet<Role>>>,
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
     // Return `UserStoreError::UserAlreadyExists` if the user already exists,
     // otherwise insert the user into the hashmap and return `Ok(())`.
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        if users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
-        self.users.insert(user.email.clone(), user);
+        users.insert(user.email.clone(), user);
         Ok(())
     }
 
@@ -26,12 +33,8 @@ impl UserStore for HashmapUserStore {
     // `User` object or a `UserStoreError`.
     // Return `UserStoreError::UserNotFound` if the user can not be found.
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
-        if let Some(user) = self.users.get(email) {
-            return Ok(User::new(
-                user.email.clone(),
-                user.password.clone(),
-                user.requires_2fa,
-            ));
+        if let Some(user) = self.users.read().await.get(email) {
+            return Ok(user.clone());
         }
         Err(UserStoreError::UserNotFound)
     }
@@ -47,7 +50,7 @@ impl UserStore for HashmapUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        if let Some(user) = self.users.get(email) {
+        if let Some(user) = self.users.read().await.get(email) {
             if user.password == *password {
                 return Ok(());
             }
@@ -55,13 +58,188 @@ impl UserStore for HashmapUserStore {
         }
         Err(UserStoreError::UserNotFound)
     }
+
+    // Replaces the stored password of an existing user.
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.two_fa_method = two_fa_method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    // Return `UserStoreError::UserAlreadyExists` if another user has `new_email`.
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn update_email(&self, email: &Email, new_email: Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        if users.contains_key(&new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let mut user = users.remove(email).ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        user.verified = true;
+        users.insert(new_email.clone(), user);
+
+        let mut roles = self.roles.write().await;
+        if let Some(user_roles) = roles.remove(email) {
+            roles.insert(new_email, user_roles);
+        }
+        Ok(())
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                if !requires_2fa {
+                    user.two_fa_method = TwoFAMethod::None;
+                } else if user.two_fa_method == TwoFAMethod::None {
+                    user.two_fa_method = TwoFAMethod::Email;
+                }
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.users
+            .write()
+            .await
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        self.roles.write().await.remove(email);
+        Ok(())
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn set_disabled(&self, email: &Email, disabled: bool) -> Result<(), UserStoreError> {
+        match self.users.write().await.get_mut(email) {
+            Some(user) => {
+                user.disabled = disabled;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<UserPage, UserStoreError> {
+        let search = search.map(str::to_lowercase);
+
+        let mut users: Vec<User> = self
+            .users
+            .read()
+            .await
+            .values()
+            .filter(|user| match &search {
+                Some(search) => user.email.as_ref().to_lowercase().contains(search),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+
+        let total = users.len() as u64;
+        let users = users
+            .into_iter()
+            .skip(offset.try_into().unwrap_or(usize::MAX))
+            .take(limit.try_into().unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(UserPage { users, total })
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(self
+            .roles
+            .read()
+            .await
+            .get(email)
+            .map(|roles| roles.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn assign_role(&self, email: &Email, role: Role) -> Result<(), UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        self.roles
+            .write()
+            .await
+            .entry(email.clone())
+            .or_default()
+            .insert(role);
+        Ok(())
+    }
+
+    // Return `UserStoreError::UserNotFound` if the user can not be found.
+    async fn remove_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        if let Some(roles) = self.roles.write().await.get_mut(email) {
+            roles.remove(role);
+        }
+        Ok(())
+    }
 }
 
 impl HashmapUserStore {
     pub fn new() -> Self {
-        Self {
-            users: HashMap::new(),
-        }
+        Self::default()
     }
 }
 
@@ -80,11 +258,9 @@ mod tests {
         let new_password: String = FakerPassword(8..16).fake();
         let password = Password::parse(&new_password).unwrap();
 
-        let mut user_store = HashmapUserStore {
-            users: HashMap::new(),
-        };
+        let user_store = HashmapUserStore::new();
 
-        let user = User::new(email, password, true);
+        let user = User::new(email, password, TwoFAMethod::Email);
 
         assert_ne!(
             user_store.add_user(user).await,
@@ -99,11 +275,9 @@ mod tests {
         let new_password: String = FakerPassword(8..16).fake();
         let password = Password::parse(&new_password).unwrap();
 
-        let mut user_store = HashmapUserStore {
-            users: HashMap::new(),
-        };
+        let user_store = HashmapUserStore::new();
 
-        let user = User::new(email.clone(), password, true);
+        let user = User::new(email.clone(), password, TwoFAMethod::Email);
 
         user_store
             .add_user(user)
@@ -129,11 +303,9 @@ mod tests {
         let new_password: String = FakerPassword(8..16).fake();
         let password = Password::parse(&new_password).unwrap();
 
-        let mut user_store = HashmapUserStore {
-            users: HashMap::new(),
-        };
+        let user_store = HashmapUserStore::new();
 
-        let user = User::new(email.clone(), password.clone(), true);
+        let user = User::new(email.clone(), password.clone(), TwoFAMethod::Email);
 
         user_store
             .add_user(user)
@@ -155,4 +327,321 @@ mod tests {
             "validate_user fails"
         );
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+
+        let old_password = Password::parse("old_password").unwrap();
+        let new_password = Password::parse("new_password").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(
+                email.clone(),
+                old_password.clone(),
+                TwoFAMethod::None,
+            ))
+            .await
+            .expect("Failed to add account");
+
+        user_store
+            .update_password(&email, new_password.clone())
+            .await
+            .expect("Failed to update password");
+
+        assert_eq!(
+            user_store.validate_user(&email, &old_password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            user_store.validate_user(&email, &new_password).await,
+            Ok(())
+        );
+
+        let unknown_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        assert_eq!(
+            user_store
+                .update_password(&unknown_email, new_password)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mark_verified() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::None))
+            .await
+            .expect("Failed to add account");
+
+        assert!(!user_store.get_user(&email).await.unwrap().verified);
+
+        user_store
+            .mark_verified(&email)
+            .await
+            .expect("Failed to mark user as verified");
+
+        assert!(user_store.get_user(&email).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::Email))
+            .await
+            .expect("Failed to add account");
+
+        user_store
+            .set_two_fa_method(&email, TwoFAMethod::Totp)
+            .await
+            .expect("Failed to set 2FA method");
+
+        assert_eq!(
+            user_store.get_user(&email).await.unwrap().two_fa_method,
+            TwoFAMethod::Totp
+        );
+
+        let unknown_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        assert_eq!(
+            user_store
+                .set_two_fa_method(&unknown_email, TwoFAMethod::None)
+                .await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_email() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let new_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let taken_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        for email in [&email, &taken_email] {
+            user_store
+                .add_user(User::new(
+                    email.clone(),
+                    password.clone(),
+                    TwoFAMethod::None,
+                ))
+                .await
+                .expect("Failed to add account");
+        }
+
+        assert_eq!(
+            user_store.update_email(&email, taken_email).await,
+            Err(UserStoreError::UserAlreadyExists)
+        );
+
+        user_store
+            .update_email(&email, new_email.clone())
+            .await
+            .expect("Failed to update email");
+
+        assert_eq!(
+            user_store.get_user(&email).await.err(),
+            Some(UserStoreError::UserNotFound)
+        );
+
+        let user = user_store.get_user(&new_email).await.unwrap();
+        assert_eq!(user.email, new_email);
+        assert!(user.verified);
+        assert_eq!(
+            user_store.validate_user(&new_email, &password).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_requires_2fa() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::Totp))
+            .await
+            .expect("Failed to add account");
+
+        let two_fa_method = |requires_2fa| {
+            let user_store = &user_store;
+            let email = &email;
+            async move {
+                user_store
+                    .set_requires_2fa(email, requires_2fa)
+                    .await
+                    .expect("Failed to set 2FA");
+                user_store.get_user(email).await.unwrap().two_fa_method
+            }
+        };
+
+        // An authenticator app stays in use until 2FA is turned off
+        assert_eq!(two_fa_method(true).await, TwoFAMethod::Totp);
+        assert_eq!(two_fa_method(false).await, TwoFAMethod::None);
+        assert_eq!(two_fa_method(true).await, TwoFAMethod::Email);
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::None))
+            .await
+            .expect("Failed to add account");
+
+        assert_eq!(user_store.delete_user(&email).await, Ok(()));
+        assert_eq!(
+            user_store.get_user(&email).await.err(),
+            Some(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            user_store.delete_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_assign_and_remove_roles() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let new_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+        let support = Role::parse("support").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::None))
+            .await
+            .expect("Failed to add account");
+
+        assert_eq!(user_store.get_roles(&email).await, Ok(vec![]));
+
+        for role in [support.clone(), Role::admin(), support.clone()] {
+            user_store
+                .assign_role(&email, role)
+                .await
+                .expect("Failed to assign role");
+        }
+
+        assert_eq!(
+            user_store.get_roles(&email).await,
+            Ok(vec![Role::admin(), support.clone()])
+        );
+
+        // Roles follow the account to its new address
+        user_store
+            .update_email(&email, new_email.clone())
+            .await
+            .expect("Failed to update email");
+
+        user_store
+            .remove_role(&new_email, &support)
+            .await
+            .expect("Failed to remove role");
+
+        assert_eq!(
+            user_store.get_roles(&new_email).await,
+            Ok(vec![Role::admin()])
+        );
+        assert_eq!(
+            user_store.get_roles(&email).await,
+            Err(UserStoreError::UserNotFound)
+        );
+        assert_eq!(
+            user_store.assign_role(&email, support).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_disabled() {
+        let email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        user_store
+            .add_user(User::new(email.clone(), password, TwoFAMethod::None))
+            .await
+            .expect("Failed to add account");
+
+        assert!(!user_store.get_user(&email).await.unwrap().disabled);
+
+        for disabled in [true, false] {
+            user_store
+                .set_disabled(&email, disabled)
+                .await
+                .expect("Failed to set disabled");
+            assert_eq!(
+                user_store.get_user(&email).await.unwrap().disabled,
+                disabled
+            );
+        }
+
+        let unknown_email = Email::parse(&SafeEmail().fake::<String>()).unwrap();
+        assert_eq!(
+            user_store.set_disabled(&unknown_email, true).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_users() {
+        let password = Password::parse("password123").unwrap();
+
+        let user_store = HashmapUserStore::new();
+
+        for email in [
+            "carol@example.com",
+            "alice@example.com",
+            "bob@example.org",
+            "dave@example.com",
+        ] {
+            user_store
+                .add_user(User::new(
+                    Email::parse(email).unwrap(),
+                    password.clone(),
+                    TwoFAMethod::None,
+                ))
+                .await
+                .expect("Failed to add account");
+        }
+
+        let emails = |page: UserPage| -> Vec<String> {
+            page.users
+                .iter()
+                .map(|user| user.email.as_ref().to_owned())
+                .collect()
+        };
+
+        let page = user_store.list_users(None, 1, 2).await.unwrap();
+        assert_eq!(page.total, 4);
+        assert_eq!(emails(page), vec!["bob@example.org", "carol@example.com"]);
+
+        let page = user_store
+            .list_users(Some("EXAMPLE.COM"), 2, 10)
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(emails(page), vec!["dave@example.com"]);
+    }
 }
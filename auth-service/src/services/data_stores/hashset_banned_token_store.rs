@@ -1,21 +1,46 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{BannedTokenStore, BannedTokenStoreError},
+    Email,
+};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
-    tokens: HashSet<String>,
+    tokens: RwLock<HashSet<String>>,
+    user_revocations: RwLock<HashMap<Email, usize>>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token);
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError> {
+        self.tokens.write().await.insert(token);
         Ok(())
     }
 
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.contains(token))
+        Ok(self.tokens.read().await.contains(token))
+    }
+
+    async fn revoke_user_tokens(
+        &self,
+        email: &Email,
+        timestamp: usize,
+    ) -> Result<(), BannedTokenStoreError> {
+        self.user_revocations
+            .write()
+            .await
+            .insert(email.clone(), timestamp);
+        Ok(())
+    }
+
+    async fn get_user_revocation(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError> {
+        Ok(self.user_revocations.read().await.get(email).copied())
     }
 }
 
@@ -24,23 +49,35 @@ mod tests {
     use super::*;
     #[tokio::test]
     async fn test_add_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = "test_token".to_owned();
 
         let result = store.add_token(token.clone()).await;
 
         assert!(result.is_ok());
-        assert!(store.tokens.contains(&token));
+        assert!(store.tokens.read().await.contains(&token));
     }
 
     #[tokio::test]
     async fn test_contains_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = "test_token".to_owned();
-        store.tokens.insert(token.clone());
+        store.tokens.write().await.insert(token.clone());
 
         let result = store.contains_token(&token).await;
 
         assert!(result.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_revoke_user_tokens() {
+        let store = HashsetBannedTokenStore::default();
+        let email = Email::parse("test@example.com").unwrap();
+
+        assert_eq!(store.get_user_revocation(&email).await.unwrap(), None);
+
+        store.revoke_user_tokens(&email, 42).await.unwrap();
+
+        assert_eq!(store.get_user_revocation(&email).await.unwrap(), Some(42));
+    }
 }
@@ -1,13 +1,53 @@
+mod hashmap_admin_audit_store;
+mod hashmap_email_change_token_store;
+mod hashmap_email_outbox_store;
+mod hashmap_email_verification_token_store;
+mod hashmap_login_attempt_store;
+mod hashmap_password_reset_token_store;
+mod hashmap_recovery_code_store;
+mod hashmap_refresh_token_store;
+mod hashmap_session_store;
+mod hashmap_totp_secret_store;
 mod hashmap_two_fa_code_store;
 mod hashmap_user_store;
 mod hashset_banned_token_store;
+mod postgres_admin_audit_store;
+mod postgres_email_outbox_store;
+mod postgres_recovery_code_store;
+mod postgres_totp_secret_store;
 mod postgres_user_store;
 mod redis_banned_token_store;
+mod redis_email_change_token_store;
+mod redis_email_verification_token_store;
+mod redis_login_attempt_store;
+mod redis_password_reset_token_store;
+mod redis_refresh_token_store;
+mod redis_session_store;
 mod redis_two_fa_code_store;
 
+pub use hashmap_admin_audit_store::*;
+pub use hashmap_email_change_token_store::*;
+pub use hashmap_email_outbox_store::*;
+pub use hashmap_email_verification_token_store::*;
+pub use hashmap_login_attempt_store::*;
+pub use hashmap_password_reset_token_store::*;
+pub use hashmap_recovery_code_store::*;
+pub use hashmap_refresh_token_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_totp_secret_store::*;
 pub use hashmap_two_fa_code_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
+pub use postgres_admin_audit_store::*;
+pub use postgres_email_outbox_store::*;
+pub use postgres_recovery_code_store::*;
+pub use postgres_totp_secret_store::*;
 pub use postgres_user_store::*;
 pub use redis_banned_token_store::*;
+pub use redis_email_change_token_store::*;
+pub use redis_email_verification_token_store::*;
+pub use redis_login_attempt_store::*;
+pub use redis_password_reset_token_store::*;
+pub use redis_refresh_token_store::*;
+pub use redis_session_store::*;
 pub use redis_two_fa_code_store::*;
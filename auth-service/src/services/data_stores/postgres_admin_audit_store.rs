@@ -0,0 +1,99 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{AdminAuditPage, AdminAuditStore, AdminAuditStoreError},
+    AdminAction, AdminAuditEntry, Email,
+};
+
+pub struct PostgresAdminAuditStore {
+    pool: PgPool,
+}
+
+impl PostgresAdminAuditStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl AdminAuditStore for PostgresAdminAuditStore {
+    async fn record(&self, entry: AdminAuditEntry) -> Result<(), AdminAuditStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO admin_audit_log (actor, action, target, details, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            entry.actor.as_ref(),
+            entry.action.as_ref(),
+            entry.target.as_ref().map(|target| target.as_ref()),
+            entry.details,
+            entry.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| AdminAuditStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn list_entries(
+        &self,
+        target: Option<&Email>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<AdminAuditPage, AdminAuditStoreError> {
+        let target = target.map(|target| target.as_ref());
+        let offset: i64 = offset.try_into().unwrap_or(i64::MAX);
+        let limit: i64 = limit.try_into().unwrap_or(i64::MAX);
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!"
+            FROM admin_audit_log
+            WHERE $1::TEXT IS NULL OR target = $1
+            "#,
+            target
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| AdminAuditStoreError::UnexpectedError)?;
+
+        let entries = sqlx::query!(
+            r#"
+            SELECT actor, action, target, details, created_at
+            FROM admin_audit_log
+            WHERE $1::TEXT IS NULL OR target = $1
+            ORDER BY id DESC
+            OFFSET $2
+            LIMIT $3
+            "#,
+            target,
+            offset,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| AdminAuditStoreError::UnexpectedError)?
+        .into_iter()
+        .map(|row| {
+            let parse_email = |email: &str| {
+                Email::parse(email).map_err(|_| AdminAuditStoreError::UnexpectedError)
+            };
+
+            Ok(AdminAuditEntry {
+                actor: parse_email(&row.actor)?,
+                action: AdminAction::parse(&row.action)
+                    .map_err(|_| AdminAuditStoreError::UnexpectedError)?,
+                target: row.target.as_deref().map(parse_email).transpose()?,
+                details: row.details,
+                created_at: row.created_at,
+            })
+        })
+        .collect::<Result<Vec<AdminAuditEntry>, AdminAuditStoreError>>()?;
+
+        Ok(AdminAuditPage {
+            entries,
+            total: total.try_into().unwrap_or_default(),
+        })
+    }
+}
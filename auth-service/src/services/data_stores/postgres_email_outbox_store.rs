@@ -0,0 +1,205 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{EmailOutboxStore, EmailOutboxStoreError},
+    Email, EmailMessage, OutboxEmail,
+};
+
+pub struct PostgresEmailOutboxStore {
+    pool: PgPool,
+}
+
+impl PostgresEmailOutboxStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+struct OutboxEmailRow {
+    id: Uuid,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    attempts: i32,
+    last_error: Option<String>,
+    created_at: i64,
+}
+
+impl TryFrom<OutboxEmailRow> for OutboxEmail {
+    type Error = EmailOutboxStoreError;
+
+    fn try_from(row: OutboxEmailRow) -> Result<Self, Self::Error> {
+        Ok(OutboxEmail {
+            id: row.id,
+            recipient: Email::parse(&row.recipient)
+                .map_err(|_| EmailOutboxStoreError::UnexpectedError)?,
+            message: EmailMessage {
+                subject: row.subject,
+                html_body: row.html_body,
+                text_body: row.text_body,
+            },
+            attempts: row.attempts as u32,
+            last_error: row.last_error,
+            created_at: row.created_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutboxStore for PostgresEmailOutboxStore {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox (id, recipient, subject, html_body, text_body, created_at, next_attempt_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+            email.id,
+            email.recipient.as_ref(),
+            email.message.subject,
+            email.message.html_body,
+            email.message.text_body,
+            email.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn claim_next(
+        &self,
+        now: i64,
+        lease_seconds: u64,
+        skip: &[Uuid],
+    ) -> Result<Option<OutboxEmail>, EmailOutboxStoreError> {
+        // SKIP LOCKED lets several instances of the service drain the outbox side by side
+        // without claiming the same email
+        let row = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            WITH claimed AS (
+                UPDATE email_outbox
+                SET next_attempt_at = $2
+                WHERE id IN (
+                    SELECT id
+                    FROM email_outbox
+                    WHERE status = 'pending' AND next_attempt_at <= $1 AND id <> ALL($3)
+                    ORDER BY next_attempt_at, position
+                    LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING id, recipient, subject, html_body, text_body, attempts, last_error,
+                    created_at
+            )
+            SELECT
+                id AS "id!",
+                recipient AS "recipient!",
+                subject AS "subject!",
+                html_body AS "html_body!",
+                text_body AS "text_body!",
+                attempts AS "attempts!",
+                last_error,
+                created_at AS "created_at!"
+            FROM claimed
+            "#,
+            now,
+            now + lease_seconds as i64,
+            skip
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        row.map(OutboxEmail::try_from).transpose()
+    }
+
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM email_outbox
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn mark_failed(
+        &self,
+        id: Uuid,
+        error: &str,
+        retry_at: Option<i64>,
+    ) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET attempts = attempts + 1,
+                last_error = $2,
+                status = CASE WHEN $3::BIGINT IS NULL THEN 'dead' ELSE status END,
+                next_attempt_at = COALESCE($3, next_attempt_at)
+            WHERE id = $1
+            "#,
+            id,
+            error,
+            retry_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn get_dead_letters(&self) -> Result<Vec<OutboxEmail>, EmailOutboxStoreError> {
+        let rows = sqlx::query_as!(
+            OutboxEmailRow,
+            r#"
+            SELECT id, recipient, subject, html_body, text_body, attempts, last_error, created_at
+            FROM email_outbox
+            WHERE status = 'dead'
+            ORDER BY position
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        rows.into_iter().map(OutboxEmail::try_from).collect()
+    }
+
+    async fn replay_dead_letter(&self, id: Uuid, now: i64) -> Result<(), EmailOutboxStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'pending', attempts = 0, next_attempt_at = $2
+            WHERE id = $1 AND status = 'dead'
+            "#,
+            id,
+            now
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| EmailOutboxStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(EmailOutboxStoreError::EmailNotFound);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError},
+    Email,
+};
+
+use super::postgres_user_store::{
+    compute_password_hash, default_password_hash_params, verify_password_hash,
+};
+
+// Codes are only ever stored as Argon2 hashes, the same way passwords are
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let mut code_hashes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let code_hash =
+                compute_password_hash(code.as_ref().to_owned(), default_password_hash_params())
+                    .await
+                    .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash);
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        for code_hash in code_hashes {
+            sqlx::query!(
+                r#"
+                INSERT INTO recovery_codes (email, code_hash)
+                VALUES ($1, $2)
+                "#,
+                email.as_ref(),
+                &code_hash
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)
+    }
+
+    async fn consume_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, code_hash
+            FROM recovery_codes
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        for row in rows {
+            if verify_password_hash(row.code_hash, code.as_ref().to_owned())
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            // Deleting by id means only one of two concurrent requests with the same code wins
+            let result = sqlx::query!(
+                r#"
+                DELETE FROM recovery_codes
+                WHERE id = $1
+                "#,
+                row.id
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+            if result.rows_affected() == 0 {
+                break;
+            }
+
+            return Ok(());
+        }
+
+        Err(RecoveryCodeStoreError::CodeNotFound)
+    }
+}
@@ -0,0 +1,227 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{TotpEnrollment, TotpSecretStore, TotpSecretStoreError},
+    Email, TotpSecret,
+};
+
+const NONCE_LENGTH: usize = 12;
+
+// Secrets are encrypted with AES-256-GCM before they reach the database. The stored value is
+// the random nonce followed by the ciphertext, and the account's id is bound in as associated
+// data so a secret can't be moved to another account's row. The id rather than the email, so
+// the secret survives an email change.
+pub struct PostgresTotpSecretStore {
+    pool: PgPool,
+    cipher: Aes256Gcm,
+}
+
+impl PostgresTotpSecretStore {
+    pub fn new(pool: PgPool, encryption_key: [u8; 32]) -> Self {
+        Self {
+            pool,
+            cipher: Aes256Gcm::new(&encryption_key.into()),
+        }
+    }
+
+    fn encrypt(&self, aad: &[u8], secret: &TotpSecret) -> Result<Vec<u8>, TotpSecretStoreError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: secret.as_ref(),
+            aad,
+        };
+
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    fn decrypt(&self, aad: &[u8], encrypted: &[u8]) -> Result<TotpSecret, TotpSecretStoreError> {
+        if encrypted.len() < NONCE_LENGTH {
+            return Err(TotpSecretStoreError::UnexpectedError);
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+
+        let secret = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        TotpSecret::from_bytes(secret).map_err(|_| TotpSecretStoreError::UnexpectedError)
+    }
+
+    async fn get_user_id(&self, email: &Email) -> Result<Uuid, TotpSecretStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT id
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?
+        .ok_or(TotpSecretStoreError::UnexpectedError)
+    }
+
+    // Secrets stored before accounts had ids are bound to the email instead. They are moved
+    // over to the id the first time they are read, which works as long as the email hasn't
+    // changed in the meantime.
+    async fn rebind_legacy_secret(
+        &self,
+        email: &Email,
+        user_id: Uuid,
+        encrypted: &[u8],
+    ) -> Result<TotpSecret, TotpSecretStoreError> {
+        let secret = self.decrypt(email.as_ref().as_bytes(), encrypted)?;
+        let encrypted_secret = self.encrypt(user_id.as_bytes(), &secret)?;
+
+        sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET encrypted_secret = $1
+            WHERE email = $2
+            "#,
+            &encrypted_secret,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(secret)
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for PostgresTotpSecretStore {
+    async fn add_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        let user_id = self.get_user_id(email).await?;
+        let encrypted_secret = self.encrypt(user_id.as_bytes(), &secret)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, encrypted_secret, confirmed, last_used_step)
+            VALUES ($1, $2, FALSE, NULL)
+            ON CONFLICT (email) DO UPDATE
+            SET encrypted_secret = EXCLUDED.encrypted_secret,
+                confirmed = FALSE,
+                last_used_step = NULL
+            "#,
+            email.as_ref(),
+            &encrypted_secret
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpEnrollment, TotpSecretStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT totp_secrets.encrypted_secret, totp_secrets.confirmed, users.id AS user_id
+            FROM totp_secrets
+            JOIN users ON users.email = totp_secrets.email
+            WHERE totp_secrets.email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        let secret = match self.decrypt(row.user_id.as_bytes(), &row.encrypted_secret) {
+            Ok(secret) => secret,
+            Err(_) => {
+                self.rebind_legacy_secret(email, row.user_id, &row.encrypted_secret)
+                    .await?
+            }
+        };
+
+        Ok(TotpEnrollment {
+            secret,
+            confirmed: row.confirmed,
+        })
+    }
+
+    async fn confirm_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET confirmed = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::SecretNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn use_step(&self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        let step = i64::try_from(step).map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        // A single conditional update, so two requests racing with the same code can't both win
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET last_used_step = $1
+            WHERE email = $2 AND (last_used_step IS NULL OR last_used_step < $1)
+            "#,
+            step,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            self.get_secret(email).await?;
+            return Err(TotpSecretStoreError::StepAlreadyUsed);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_secret(&self, email: &Email) -> Result<(), TotpSecretStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM totp_secrets
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| TotpSecretStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -6,41 +6,122 @@ use argon2::{
 };
 
 use sqlx::PgPool;
+use tokio::sync::OnceCell;
 
-use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+use crate::{
+    domain::{
+        data_stores::{UserPage, UserStore, UserStoreError},
+        Email, Password, Role, TwoFAMethod, User,
+    },
+    utils::constants::{
+        DEFAULT_ARGON2_MEMORY_COST_KIB, DEFAULT_ARGON2_PARALLELISM, DEFAULT_ARGON2_TIME_COST,
+    },
 };
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    // New hashes are made with these, and older ones are upgraded to them on login
+    password_hash_params: Params,
+    // Checked against when there's no such user, made on first use with the params above
+    dummy_password_hash: OnceCell<String>,
 }
 
 impl PostgresUserStore {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            password_hash_params: default_password_hash_params(),
+            dummy_password_hash: OnceCell::new(),
+        }
+    }
+
+    pub fn with_password_hash_params(mut self, password_hash_params: Params) -> Self {
+        self.password_hash_params = password_hash_params;
+        self
+    }
+
+    // How many addresses had their local part lowercased by the migration that made lookups
+    // case-insensitive. Their owners can't sign in with the case-sensitive policy.
+    pub async fn count_lowercased_local_parts(&self) -> Result<i64, UserStoreError> {
+        sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM lowercased_email_local_parts"#)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    async fn dummy_password_hash(&self) -> Result<String, UserStoreError> {
+        self.dummy_password_hash
+            .get_or_try_init(|| {
+                compute_password_hash(
+                    "dummy-password".to_owned(),
+                    self.password_hash_params.clone(),
+                )
+            })
+            .await
+            .cloned()
+            .map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    // Only replaces the hash that was just verified, so a password changed in the meantime
+    // isn't overwritten
+    async fn rehash_password(
+        &self,
+        email: &Email,
+        old_password_hash: &str,
+        password: &Password,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let password_hash = compute_password_hash(
+            password.as_ref().to_owned(),
+            self.password_hash_params.clone(),
+        )
+        .await?;
+
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2 AND password_hash = $3
+            "#,
+            &password_hash,
+            email.as_ref(),
+            old_password_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
     }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = compute_password_hash(user.password.as_ref().to_owned())
-            .await
-            .map_err(|_| UserStoreError::UnexpectedError)?;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(
+            user.password.as_ref().to_owned(),
+            self.password_hash_params.clone(),
+        )
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
 
         sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, two_fa_method, verified, disabled)
+            VALUES ($1, $2, $3, $4, $5)
             "#,
             user.email.as_ref(),
             &password_hash,
-            user.requires_2fa
+            user.two_fa_method.as_ref(),
+            user.verified,
+            user.disabled
         )
         .execute(&self.pool)
         .await
-        .map_err(|_| UserStoreError::UnexpectedError)?;
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            _ => UserStoreError::UnexpectedError,
+        })?;
 
         Ok(())
     }
@@ -48,7 +129,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, two_fa_method, verified, disabled
             FROM users
             WHERE email = $1
             "#,
@@ -58,12 +139,13 @@ impl UserStore for PostgresUserStore {
         .await
         .map_err(|_| UserStoreError::UnexpectedError)?
         .map(|row| {
-            Ok(User {
-                email: Email::parse(&row.email).map_err(|_| UserStoreError::UnexpectedError)?,
-                password: Password::parse(&row.password_hash)
-                    .map_err(|_| UserStoreError::UnexpectedError)?,
-                requires_2fa: row.requires_2fa,
-            })
+            user_from_row(
+                &row.email,
+                &row.password_hash,
+                &row.two_fa_method,
+                row.verified,
+                row.disabled,
+            )
         })
         .ok_or(UserStoreError::UserNotFound)?
     }
@@ -73,18 +155,373 @@ impl UserStore for PostgresUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        let user = self.get_user(email).await?;
+        let user = match self.get_user(email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => {
+                // Costs as much as checking a real password, so that response times don't
+                // give away which emails have accounts
+                let dummy_password_hash = self.dummy_password_hash().await?;
+                let _ =
+                    verify_password_hash(dummy_password_hash, password.as_ref().to_owned()).await;
+                return Err(UserStoreError::UserNotFound);
+            }
+            Err(e) => return Err(e),
+        };
 
         verify_password_hash(
             user.password.as_ref().to_owned(),
             password.as_ref().to_owned(),
         )
         .await
-        .map_err(|_| UserStoreError::InvalidCredentials)
+        .map_err(|_| UserStoreError::InvalidCredentials)?;
+
+        // The plaintext is only at hand here, so this is where outdated hashes get upgraded.
+        // Failing to upgrade doesn't fail the login.
+        if needs_rehash(user.password.as_ref(), &self.password_hash_params) {
+            if let Err(e) = self
+                .rehash_password(email, user.password.as_ref(), password)
+                .await
+            {
+                eprintln!("Failed to rehash password: {:?}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(
+            password.as_ref().to_owned(),
+            self.password_hash_params.clone(),
+        )
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $1
+            WHERE email = $2
+            "#,
+            &password_hash,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_method = $1
+            WHERE email = $2
+            "#,
+            two_fa_method.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn update_email(&self, email: &Email, new_email: Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET email = $1, verified = TRUE
+            WHERE email = $2
+            "#,
+            new_email.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                UserStoreError::UserAlreadyExists
+            }
+            _ => UserStoreError::UnexpectedError,
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        // Turning 2FA on falls back to email codes, unless a method is already set up
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET two_fa_method = CASE
+                WHEN NOT $1 THEN $3
+                WHEN two_fa_method = $3 THEN $4
+                ELSE two_fa_method
+            END
+            WHERE email = $2
+            "#,
+            requires_2fa,
+            email.as_ref(),
+            TwoFAMethod::None.as_ref(),
+            TwoFAMethod::Email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        let roles = sqlx::query_scalar!(
+            r#"
+            SELECT ARRAY(
+                SELECT role FROM user_roles
+                WHERE user_roles.email = users.email
+                ORDER BY role
+            ) AS "roles!"
+            FROM users
+            WHERE email = $1
+            "#,
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        roles
+            .iter()
+            .map(|role| Role::parse(role).map_err(|_| UserStoreError::UnexpectedError))
+            .collect()
+    }
+
+    async fn assign_role(&self, email: &Email, role: Role) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_roles (email, role)
+            SELECT email, $2 FROM users
+            WHERE email = $1
+            ON CONFLICT DO NOTHING
+            "#,
+            email.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(e) if e.is_foreign_key_violation() => {
+                UserStoreError::RoleNotFound
+            }
+            _ => UserStoreError::UnexpectedError,
+        })?;
+
+        // Either the role was already assigned or there's no such user
+        if result.rows_affected() == 0 {
+            self.get_user(email).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn remove_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_roles
+            WHERE email = $1 AND role = $2
+            "#,
+            email.as_ref(),
+            role.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            self.get_user(email).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn set_disabled(&self, email: &Email, disabled: bool) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET disabled = $1
+            WHERE email = $2
+            "#,
+            disabled,
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<UserPage, UserStoreError> {
+        let pattern = search.map(like_pattern);
+        let offset: i64 = offset.try_into().unwrap_or(i64::MAX);
+        let limit: i64 = limit.try_into().unwrap_or(i64::MAX);
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!"
+            FROM users
+            WHERE $1::TEXT IS NULL OR lower(email) LIKE $1 ESCAPE '\'
+            "#,
+            pattern.as_deref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let users = sqlx::query!(
+            r#"
+            SELECT email, password_hash, two_fa_method, verified, disabled
+            FROM users
+            WHERE $1::TEXT IS NULL OR lower(email) LIKE $1 ESCAPE '\'
+            ORDER BY email
+            OFFSET $2
+            LIMIT $3
+            "#,
+            pattern.as_deref(),
+            offset,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .iter()
+        .map(|row| {
+            user_from_row(
+                &row.email,
+                &row.password_hash,
+                &row.two_fa_method,
+                row.verified,
+                row.disabled,
+            )
+        })
+        .collect::<Result<Vec<User>, UserStoreError>>()?;
+
+        Ok(UserPage {
+            users,
+            total: total.try_into().unwrap_or_default(),
+        })
     }
 }
 
-async fn verify_password_hash(
+fn user_from_row(
+    email: &str,
+    password_hash: &str,
+    two_fa_method: &str,
+    verified: bool,
+    disabled: bool,
+) -> Result<User, UserStoreError> {
+    Ok(User {
+        email: Email::parse(email).map_err(|_| UserStoreError::UnexpectedError)?,
+        password: Password::parse(password_hash).map_err(|_| UserStoreError::UnexpectedError)?,
+        two_fa_method: TwoFAMethod::parse(two_fa_method)
+            .map_err(|_| UserStoreError::UnexpectedError)?,
+        verified,
+        disabled,
+    })
+}
+
+// Matches `search` anywhere in a lowercased email, with LIKE wildcards in it taken literally
+fn like_pattern(search: &str) -> String {
+    let escaped = search
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+pub(super) async fn verify_password_hash(
     expected_password_hash: String,
     password_candidate: String,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
@@ -100,20 +537,92 @@ async fn verify_password_hash(
     result?
 }
 
-async fn compute_password_hash(password: String) -> Result<String, Box<dyn Error + Send + Sync>> {
+pub(super) async fn compute_password_hash(
+    password: String,
+    params: Params,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
     let result = tokio::task::spawn_blocking(move || {
         let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-        let password_hash = Argon2::new(
-            Algorithm::Argon2id,
-            Version::V0x13,
-            Params::new(15000, 2, 1, None)?,
-        )
-        .hash_password(password.as_bytes(), &salt)?
-        .to_string();
+        let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password(password.as_bytes(), &salt)?
+            .to_string();
 
         Ok(password_hash)
     })
     .await;
 
     result?
-}
\ No newline at end of file
+}
+
+pub(super) fn default_password_hash_params() -> Params {
+    Params::new(
+        DEFAULT_ARGON2_MEMORY_COST_KIB,
+        DEFAULT_ARGON2_TIME_COST,
+        DEFAULT_ARGON2_PARALLELISM,
+        None,
+    )
+    .expect("Default Argon2 parameters are valid")
+}
+
+// Whether a hash was made with another algorithm, version or cost than `params`
+fn needs_rehash(password_hash: &str, params: &Params) -> bool {
+    let Ok(password_hash) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+
+    let Ok(hash_params) = Params::try_from(&password_hash) else {
+        return true;
+    };
+
+    password_hash.algorithm != Algorithm::Argon2id.ident()
+        || password_hash.version != Some(Version::V0x13.into())
+        || hash_params.m_cost() != params.m_cost()
+        || hash_params.t_cost() != params.t_cost()
+        || hash_params.p_cost() != params.p_cost()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(m_cost: u32, t_cost: u32, p_cost: u32) -> Params {
+        Params::new(m_cost, t_cost, p_cost, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_not_rehash_hash_made_with_current_params() {
+        let password_hash = compute_password_hash("password123".to_owned(), params(8192, 2, 1))
+            .await
+            .unwrap();
+
+        assert!(!needs_rehash(&password_hash, &params(8192, 2, 1)));
+    }
+
+    #[tokio::test]
+    async fn should_rehash_hash_made_with_other_params() {
+        let password_hash = compute_password_hash("password123".to_owned(), params(8192, 2, 1))
+            .await
+            .unwrap();
+
+        assert!(needs_rehash(&password_hash, &params(16384, 2, 1)));
+        assert!(needs_rehash(&password_hash, &params(8192, 3, 1)));
+        assert!(needs_rehash(&password_hash, &params(8192, 2, 2)));
+    }
+
+    #[test]
+    fn should_escape_like_wildcards() {
+        assert_eq!(like_pattern("Bob"), "%bob%");
+        assert_eq!(like_pattern("a_b%c\\d"), "%a\\_b\\%c\\\\d%");
+    }
+
+    #[test]
+    fn should_rehash_hash_made_with_other_algorithm() {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let password_hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, params(8192, 2, 1))
+            .hash_password(b"password123", &salt)
+            .unwrap()
+            .to_string();
+
+        assert!(needs_rehash(&password_hash, &params(8192, 2, 1)));
+    }
+}
@@ -1,26 +1,26 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
-use tokio::sync::RwLock;
+use redis::{aio::ConnectionManager, AsyncCommands};
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        Email,
+    },
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError> {
         let token_key = get_key(token.as_str());
 
         let value = true;
@@ -31,9 +31,9 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         let _: () = self
             .conn
-            .write()
-            .await
+            .clone()
             .set_ex(&token_key, value, ttl)
+            .await
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(())
@@ -44,17 +44,56 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         let is_banned: bool = self
             .conn
-            .write()
-            .await
+            .clone()
             .exists(&token_key)
+            .await
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(is_banned)
     }
+
+    async fn revoke_user_tokens(
+        &self,
+        email: &Email,
+        timestamp: usize,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Any token issued before the revocation will have expired once this key does
+        let ttl: u64 = TOKEN_TTL_SECONDS
+            .try_into()
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(get_user_revocation_key(email), timestamp, ttl)
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_user_revocation(
+        &self,
+        email: &Email,
+    ) -> Result<Option<usize>, BannedTokenStoreError> {
+        let timestamp: Option<usize> = self
+            .conn
+            .clone()
+            .get(get_user_revocation_key(email))
+            .await
+            .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(timestamp)
+    }
 }
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const USER_REVOCATION_KEY_PREFIX: &str = "revoked_user_tokens:";
 
 fn get_key(token: &str) -> String {
     format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
 }
+
+fn get_user_revocation_key(email: &Email) -> String {
+    format!("{}{}", USER_REVOCATION_KEY_PREFIX, email.as_ref())
+}
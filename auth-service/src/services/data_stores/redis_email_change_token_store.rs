@@ -0,0 +1,84 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{
+    data_stores::{
+        EmailChange, EmailChangeToken, EmailChangeTokenStore, EmailChangeTokenStoreError,
+    },
+    Email,
+};
+
+pub struct RedisEmailChangeTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisEmailChangeTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailChangeTokenStore for RedisEmailChangeTokenStore {
+    async fn add_token(
+        &self,
+        change: EmailChange,
+        token: EmailChangeToken,
+    ) -> Result<(), EmailChangeTokenStoreError> {
+        let key = get_key(&token);
+        let record = serde_json::to_string(&EmailChangeRecord {
+            email: change.email.as_ref().to_owned(),
+            new_email: change.new_email.as_ref().to_owned(),
+        })
+        .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(&key, record, ONE_DAY_IN_SECONDS)
+            .await
+            .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<EmailChange, EmailChangeTokenStoreError> {
+        let key = get_key(token);
+
+        let record: Option<String> = self
+            .conn
+            .clone()
+            .get_del(&key)
+            .await
+            .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?;
+
+        let record: EmailChangeRecord = match record {
+            Some(record) => serde_json::from_str(&record)
+                .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?,
+            None => return Err(EmailChangeTokenStoreError::TokenNotFound),
+        };
+
+        Ok(EmailChange {
+            email: Email::parse(&record.email)
+                .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?,
+            new_email: Email::parse(&record.new_email)
+                .map_err(|_| EmailChangeTokenStoreError::UnexpectedError)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct EmailChangeRecord {
+    email: String,
+    new_email: String,
+}
+
+const ONE_DAY_IN_SECONDS: u64 = 86400;
+const EMAIL_CHANGE_TOKEN_PREFIX: &str = "email_change_token:";
+
+fn get_key(token: &EmailChangeToken) -> String {
+    format!("{}{}", EMAIL_CHANGE_TOKEN_PREFIX, token.as_ref())
+}
@@ -0,0 +1,91 @@
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+use crate::domain::{
+    data_stores::{
+        EmailVerificationToken, EmailVerificationTokenStore, EmailVerificationTokenStoreError,
+        EMAIL_VERIFICATION_RESEND_SECONDS,
+    },
+    Email,
+};
+
+pub struct RedisEmailVerificationTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisEmailVerificationTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailVerificationTokenStore for RedisEmailVerificationTokenStore {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: EmailVerificationToken,
+    ) -> Result<(), EmailVerificationTokenStoreError> {
+        let key = get_key(&token);
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(&key, email.as_ref(), ONE_DAY_IN_SECONDS)
+            .await
+            .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &EmailVerificationToken,
+    ) -> Result<Email, EmailVerificationTokenStoreError> {
+        let key = get_key(token);
+
+        let email: Option<String> = self
+            .conn
+            .clone()
+            .get_del(&key)
+            .await
+            .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        match email {
+            Some(email) => {
+                Email::parse(&email).map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)
+            }
+            None => Err(EmailVerificationTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn claim_resend(&self, email: &Email) -> Result<(), EmailVerificationTokenStoreError> {
+        // SET NX only succeeds for the first claim until the key expires
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(EMAIL_VERIFICATION_RESEND_SECONDS as usize));
+
+        let claimed: Option<String> = self
+            .conn
+            .clone()
+            .set_options(get_resend_key(email), true, options)
+            .await
+            .map_err(|_| EmailVerificationTokenStoreError::UnexpectedError)?;
+
+        match claimed {
+            Some(_) => Ok(()),
+            None => Err(EmailVerificationTokenStoreError::ResendTooSoon),
+        }
+    }
+}
+
+const ONE_DAY_IN_SECONDS: u64 = 86400;
+const EMAIL_VERIFICATION_TOKEN_PREFIX: &str = "email_verification_token:";
+const EMAIL_VERIFICATION_RESEND_PREFIX: &str = "email_verification_resend:";
+
+fn get_key(token: &EmailVerificationToken) -> String {
+    format!("{}{}", EMAIL_VERIFICATION_TOKEN_PREFIX, token.as_ref())
+}
+
+fn get_resend_key(email: &Email) -> String {
+    format!("{}{}", EMAIL_VERIFICATION_RESEND_PREFIX, email.as_ref())
+}
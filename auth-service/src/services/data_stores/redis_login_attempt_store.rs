@@ -0,0 +1,132 @@
+use chrono::Utc;
+use redis::{aio::ConnectionManager, AsyncCommands, Script};
+
+use crate::domain::{
+    data_stores::{LoginAttemptStore, LoginAttemptStoreError},
+    LoginAttemptKey, LoginFailures,
+};
+
+pub struct RedisLoginAttemptStore {
+    conn: ConnectionManager,
+}
+
+impl RedisLoginAttemptStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+// Each key is a hash of the failure count and the time of the last failure. The scripts run
+// atomically, so parallel attempts each see the count left by the one before.
+//
+// ARGV[1] is the current time and ARGV[2] the policy's `lockout_seconds`. Returns the count
+// and last failure from before the attempt.
+const RECORD_ATTEMPT_SCRIPT: &str = r#"
+local count = tonumber(redis.call('HGET', KEYS[1], 'count') or '0')
+local last_failure = tonumber(redis.call('HGET', KEYS[1], 'last_failure') or ARGV[1])
+redis.call('HSET', KEYS[1], 'count', count + 1, 'last_failure', last_failure)
+redis.call('EXPIREAT', KEYS[1], last_failure + tonumber(ARGV[2]))
+return {count, last_failure}
+"#;
+
+const FORGET_ATTEMPT_SCRIPT: &str = r#"
+local count = tonumber(redis.call('HGET', KEYS[1], 'count') or '0')
+if count > 0 then
+    redis.call('HSET', KEYS[1], 'count', count - 1)
+end
+return count
+"#;
+
+// Each failure pushes the expiry back, so counts only reset after a quiet period
+const RECORD_FAILURE_SCRIPT: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    redis.call('HSET', KEYS[1], 'last_failure', ARGV[1])
+    redis.call('EXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for RedisLoginAttemptStore {
+    async fn record_attempt(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<LoginFailures, LoginAttemptStoreError> {
+        let (count, last_failure): (u32, i64) = Script::new(RECORD_ATTEMPT_SCRIPT)
+            .key(get_key(key))
+            .arg(Utc::now().timestamp())
+            .arg(key.policy().lockout_seconds)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(LoginFailures {
+            count,
+            last_failure,
+        })
+    }
+
+    async fn forget_attempt(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        let _: u32 = Script::new(FORGET_ATTEMPT_SCRIPT)
+            .key(get_key(key))
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn record_failure(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        let _: u32 = Script::new(RECORD_FAILURE_SCRIPT)
+            .key(get_key(key))
+            .arg(Utc::now().timestamp())
+            .arg(key.policy().lockout_seconds)
+            .invoke_async(&mut self.conn.clone())
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_failures(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<LoginFailures>, LoginAttemptStoreError> {
+        let (count, last_failure): (Option<u32>, Option<i64>) = self
+            .conn
+            .clone()
+            .hget(get_key(key), &["count", "last_failure"])
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(count
+            .zip(last_failure)
+            .map(|(count, last_failure)| LoginFailures {
+                count,
+                last_failure,
+            }))
+    }
+
+    async fn clear_failures(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        let _: () = self
+            .conn
+            .clone()
+            .del(get_key(key))
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+// Named apart from the JSON records earlier versions stored, which the scripts can't read
+const LOGIN_ATTEMPTS_KEY_PREFIX: &str = "login_attempt_counts:";
+
+fn get_key(key: &LoginAttemptKey) -> String {
+    match key {
+        LoginAttemptKey::Email(email) => {
+            format!("{}email:{}", LOGIN_ATTEMPTS_KEY_PREFIX, email.as_ref())
+        }
+        LoginAttemptKey::Ip(ip) => format!("{}ip:{}", LOGIN_ATTEMPTS_KEY_PREFIX, ip),
+    }
+}
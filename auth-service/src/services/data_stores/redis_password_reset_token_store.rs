@@ -0,0 +1,84 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::domain::{
+    data_stores::{PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    Email,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    async fn add_token(
+        &self,
+        email: Email,
+        token: PasswordResetToken,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(&token);
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(&key, email.as_ref(), ONE_HOUR_IN_SECONDS)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_email(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let email: Option<String> = self
+            .conn
+            .clone()
+            .get(get_key(token))
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        match email {
+            Some(email) => {
+                Email::parse(&email).map_err(|_| PasswordResetTokenStoreError::UnexpectedError)
+            }
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn consume_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+
+        // GETDEL makes sure two concurrent requests can't both redeem the token
+        let email: Option<String> = self
+            .conn
+            .clone()
+            .get_del(&key)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        match email {
+            Some(email) => {
+                Email::parse(&email).map_err(|_| PasswordResetTokenStoreError::UnexpectedError)
+            }
+            None => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+}
+
+const ONE_HOUR_IN_SECONDS: u64 = 3600;
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+
+fn get_key(token: &PasswordResetToken) -> String {
+    format!("{}{}", PASSWORD_RESET_TOKEN_PREFIX, token.as_ref())
+}
@@ -0,0 +1,185 @@
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use ring::digest::{digest, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        data_stores::{
+            RefreshToken, RefreshTokenFamily, RefreshTokenStore, RefreshTokenStoreError,
+        },
+        Email,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        family: RefreshTokenFamily,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let ttl = get_ttl()?;
+
+        // Index the family under its user so that every session can be revoked at once
+        let families_key = get_user_families_key(&family.email);
+        let mut conn = self.conn.clone();
+
+        let _: () = conn
+            .sadd(&families_key, &family.id)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&families_key, ttl as i64)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let record = RefreshTokenRecord {
+            family_id: family.id,
+            email: family.email.as_ref().to_owned(),
+        };
+
+        let serialized_record =
+            serde_json::to_string(&record).map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .set_ex(get_token_key(&token), serialized_record, ttl)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn consume_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenFamily, RefreshTokenStoreError> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(get_token_key(token))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        let record: RefreshTokenRecord = match value {
+            Some(value) => {
+                serde_json::from_str(&value).map_err(|_| RefreshTokenStoreError::UnexpectedError)?
+            }
+            None => return Err(RefreshTokenStoreError::TokenNotFound),
+        };
+
+        let family = RefreshTokenFamily {
+            id: record.family_id.clone(),
+            email: Email::parse(&record.email)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+        };
+
+        if self.is_family_revoked(&family.id).await? {
+            return Err(RefreshTokenStoreError::FamilyRevoked);
+        }
+
+        // SET NX lets exactly one of several concurrent requests mark the token as used. The
+        // record itself is kept around so that a replay can still be detected.
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(get_ttl()? as usize));
+        let marked: Option<String> = self
+            .conn
+            .clone()
+            .set_options(get_used_token_key(token), true, options)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        if marked.is_none() {
+            return Err(RefreshTokenStoreError::TokenReused(family));
+        }
+
+        Ok(family)
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenStoreError> {
+        let ttl = get_ttl()?;
+
+        let _: () = self
+            .conn
+            .clone()
+            .set_ex(get_revoked_family_key(family_id), true, ttl)
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn revoke_user_families(&self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let family_ids: Vec<String> = self
+            .conn
+            .clone()
+            .smembers(get_user_families_key(email))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        for family_id in family_ids {
+            self.revoke_family(&family_id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn is_family_revoked(&self, family_id: &str) -> Result<bool, RefreshTokenStoreError> {
+        self.conn
+            .clone()
+            .exists(get_revoked_family_key(family_id))
+            .await
+            .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshTokenRecord {
+    family_id: String,
+    email: String,
+}
+
+const REFRESH_TOKEN_KEY_PREFIX: &str = "refresh_token:";
+const USED_TOKEN_KEY_PREFIX: &str = "used_refresh_token:";
+const REVOKED_FAMILY_KEY_PREFIX: &str = "revoked_refresh_family:";
+const USER_FAMILIES_KEY_PREFIX: &str = "refresh_families:";
+
+fn get_ttl() -> Result<u64, RefreshTokenStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)
+}
+
+// Keys hold a hash of the token, so that whoever can read Redis can't use the tokens in it
+fn hash_token(token: &RefreshToken) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(digest(&SHA256, token.as_ref().as_bytes()))
+}
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_KEY_PREFIX, hash_token(token))
+}
+
+fn get_used_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", USED_TOKEN_KEY_PREFIX, hash_token(token))
+}
+
+fn get_revoked_family_key(family_id: &str) -> String {
+    format!("{}{}", REVOKED_FAMILY_KEY_PREFIX, family_id)
+}
+
+fn get_user_families_key(email: &Email) -> String {
+    format!("{}{}", USER_FAMILIES_KEY_PREFIX, email.as_ref())
+}
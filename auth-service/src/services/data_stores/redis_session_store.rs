@@ -0,0 +1,202 @@
+use std::net::IpAddr;
+
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::{
+        data_stores::{SessionStore, SessionStoreError},
+        Email, Session,
+    },
+    utils::auth::REFRESH_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisSessionStore {
+    conn: ConnectionManager,
+}
+
+impl RedisSessionStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+
+    // With `XX` the write only goes through if the session is still there, so updating a
+    // session can't bring back one that was removed in the meantime
+    async fn set_session(
+        &self,
+        session: &Session,
+        existence: Option<ExistenceCheck>,
+    ) -> Result<(), SessionStoreError> {
+        let record = SessionRecord {
+            email: session.email.as_ref().to_owned(),
+            device: session.device.clone(),
+            ip: session.ip,
+            user_agent: session.user_agent.clone(),
+            created_at: session.created_at,
+            last_seen: session.last_seen,
+        };
+        let value =
+            serde_json::to_string(&record).map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let mut options = SetOptions::default().with_expiration(SetExpiry::EX(get_ttl()? as usize));
+        if let Some(existence) = existence {
+            options = options.conditional_set(existence);
+        }
+
+        let written: Option<String> = self
+            .conn
+            .clone()
+            .set_options(get_session_key(&session.id), value, options)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        written
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        // Index the session under its user so that all of them can be listed or removed
+        let sessions_key = get_user_sessions_key(&session.email);
+        let mut conn = self.conn.clone();
+
+        let _: () = conn
+            .sadd(&sessions_key, &session.id)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&sessions_key, get_ttl()? as i64)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        self.set_session(&session, None).await
+    }
+
+    async fn get_session(&self, id: &str) -> Result<Session, SessionStoreError> {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(get_session_key(id))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let record: SessionRecord = match value {
+            Some(value) => {
+                serde_json::from_str(&value).map_err(|_| SessionStoreError::UnexpectedError)?
+            }
+            None => return Err(SessionStoreError::SessionNotFound),
+        };
+
+        Ok(Session {
+            id: id.to_owned(),
+            email: Email::parse(&record.email).map_err(|_| SessionStoreError::UnexpectedError)?,
+            device: record.device,
+            ip: record.ip,
+            user_agent: record.user_agent,
+            created_at: record.created_at,
+            last_seen: record.last_seen,
+        })
+    }
+
+    async fn touch_session(&self, id: &str, last_seen: i64) -> Result<(), SessionStoreError> {
+        let mut session = self.get_session(id).await?;
+        session.last_seen = last_seen;
+
+        self.set_session(&session, Some(ExistenceCheck::XX)).await
+    }
+
+    async fn list_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let ids: Vec<String> = self
+            .conn
+            .clone()
+            .smembers(get_user_sessions_key(email))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        // Expired sessions are still in the index; they are skipped here
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.get_session(&id).await {
+                Ok(session) => sessions.push(session),
+                Err(SessionStoreError::SessionNotFound) => (),
+                Err(e) => return Err(e),
+            }
+        }
+        sessions.sort_by_key(|session| session.created_at);
+
+        Ok(sessions)
+    }
+
+    async fn remove_session(&self, id: &str) -> Result<(), SessionStoreError> {
+        let session = self.get_session(id).await?;
+        let mut conn = self.conn.clone();
+
+        let _: () = conn
+            .del(get_session_key(id))
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .srem(get_user_sessions_key(&session.email), id)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn remove_user_sessions(&self, email: &Email) -> Result<(), SessionStoreError> {
+        let sessions_key = get_user_sessions_key(email);
+        let mut conn = self.conn.clone();
+
+        let ids: Vec<String> = conn
+            .smembers(&sessions_key)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        for id in ids {
+            let _: () = conn
+                .del(get_session_key(&id))
+                .await
+                .map_err(|_| SessionStoreError::UnexpectedError)?;
+        }
+
+        let _: () = conn
+            .del(&sessions_key)
+            .await
+            .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionRecord {
+    email: String,
+    device: String,
+    ip: IpAddr,
+    user_agent: Option<String>,
+    created_at: i64,
+    last_seen: i64,
+}
+
+// A session lasts as long as its refresh tokens
+fn get_ttl() -> Result<u64, SessionStoreError> {
+    REFRESH_TOKEN_TTL_SECONDS
+        .try_into()
+        .map_err(|_| SessionStoreError::UnexpectedError)
+}
+
+const SESSION_KEY_PREFIX: &str = "session:";
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+fn get_session_key(id: &str) -> String {
+    format!("{}{}", SESSION_KEY_PREFIX, id)
+}
+
+fn get_user_sessions_key(email: &Email) -> String {
+    format!("{}{}", USER_SESSIONS_KEY_PREFIX, email.as_ref())
+}
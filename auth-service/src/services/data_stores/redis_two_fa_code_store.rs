@@ -1,20 +1,19 @@
-use std::sync::Arc;
-
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{
+        LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, MAX_TWO_FA_ATTEMPTS,
+    },
     Email,
 };
 
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
@@ -22,7 +21,7 @@ impl RedisTwoFACodeStore {
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
@@ -38,24 +37,28 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
         let _: () = self
             .conn
-            .write()
-            .await
+            .clone()
             .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
+            .await
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
         let key = get_key(email);
 
-        let _: () = self
+        let removed: u32 = self
             .conn
-            .write()
-            .await
+            .clone()
             .del(&key)
+            .await
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
+        if removed == 0 {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
         Ok(())
     }
 
@@ -65,7 +68,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         let key = get_key(email);
 
-        match self.conn.write().await.get::<_, String>(&key) {
+        match self.conn.clone().get::<_, String>(&key).await {
             Ok(value) => {
                 let data: TwoFATuple = serde_json::from_str(&value)
                     .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
@@ -81,6 +84,42 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
             Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn record_attempt(
+        &self,
+        email: &Email,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(), TwoFACodeStoreError> {
+        let (stored_login_attempt_id, _) = self.get_code(email).await?;
+        if stored_login_attempt_id != *login_attempt_id {
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        // INCR keeps the count right when several instances see guesses for the same attempt
+        let attempts_key = get_attempts_key(login_attempt_id);
+        let mut conn = self.conn.clone();
+
+        let attempts: u32 = conn
+            .incr(&attempts_key, 1)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&attempts_key, TEN_MINUTES_IN_SECONDS as i64)
+            .await
+            .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+        if attempts > MAX_TWO_FA_ATTEMPTS {
+            let _: () = conn
+                .del(get_key(email))
+                .await
+                .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
+
+            return Err(TwoFACodeStoreError::LoginAttemptIdNotFound);
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -88,7 +127,12 @@ struct TwoFATuple(pub String, pub String);
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
+const TWO_FA_ATTEMPTS_PREFIX: &str = "two_fa_attempts:";
 
 fn get_key(email: &Email) -> String {
     format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
 }
+
+fn get_attempts_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!("{}{}", TWO_FA_ATTEMPTS_PREFIX, login_attempt_id.as_ref())
+}
@@ -0,0 +1,112 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::sync::Notify;
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxStoreType},
+    domain::{
+        data_stores::EmailOutboxStoreError, Email, EmailMessage, EmailRetryPolicy, OutboxEmail,
+    },
+};
+
+// How long a claimed email is hidden from other workers while it is being sent. Emails are
+// claimed one at a time, so this only has to outlast a single send including the SMTP
+// client's own retries (43.5s with the default settings).
+pub const EMAIL_OUTBOX_LEASE_SECONDS: u64 = 60;
+// The worker is woken as soon as something is enqueued; polling only picks up retries and
+// emails enqueued by other instances
+pub const EMAIL_OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Routes hand emails to the outbox instead of sending them, so a flaky relay neither fails
+// the request nor loses the email. A background worker does the delivering.
+#[derive(Clone)]
+pub struct EmailOutbox {
+    store: EmailOutboxStoreType,
+    wake: Arc<Notify>,
+}
+
+impl EmailOutbox {
+    pub fn new(store: EmailOutboxStoreType) -> Self {
+        Self {
+            store,
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn store(&self) -> &EmailOutboxStoreType {
+        &self.store
+    }
+
+    pub async fn enqueue(
+        &self,
+        recipient: &Email,
+        message: EmailMessage,
+    ) -> Result<(), EmailOutboxStoreError> {
+        self.store
+            .enqueue(OutboxEmail::new(recipient.clone(), message))
+            .await?;
+        self.wake.notify_one();
+
+        Ok(())
+    }
+
+    // Sends every email that is due, returning how many were delivered
+    pub async fn deliver_due(
+        &self,
+        email_client: &EmailClientType,
+        policy: &EmailRetryPolicy,
+    ) -> Result<usize, EmailOutboxStoreError> {
+        let mut delivered = 0;
+        // Emails that fail are tried again on a later pass, even if they are due right away
+        let mut tried = Vec::new();
+
+        loop {
+            let now = Utc::now().timestamp();
+            let email = match self
+                .store
+                .claim_next(now, EMAIL_OUTBOX_LEASE_SECONDS, &tried)
+                .await?
+            {
+                Some(email) => email,
+                None => return Ok(delivered),
+            };
+            tried.push(email.id);
+
+            let result = email_client
+                .send_email(&email.recipient, &email.message)
+                .await;
+
+            match result {
+                Ok(()) => {
+                    self.store.mark_sent(email.id).await?;
+                    delivered += 1;
+                }
+                Err(error) => {
+                    let retry_at =
+                        policy.next_attempt_at(email.attempts + 1, Utc::now().timestamp());
+                    self.store.mark_failed(email.id, &error, retry_at).await?;
+                }
+            }
+        }
+    }
+
+    // Delivers queued emails for as long as the service runs
+    pub async fn run_worker(
+        self,
+        email_client: EmailClientType,
+        policy: EmailRetryPolicy,
+        poll_interval: Duration,
+    ) {
+        loop {
+            if let Err(e) = self.deliver_due(&email_client, &policy).await {
+                eprintln!("Failed to deliver queued emails: {:?}", e);
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(poll_interval) => {}
+            }
+        }
+    }
+}
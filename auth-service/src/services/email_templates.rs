@@ -0,0 +1,263 @@
+use askama::Template;
+
+use crate::domain::{EmailMessage, Locale};
+
+// Every kind of email the service sends has a context struct holding what its templates
+// need. Rendering picks the HTML and plain-text templates for the locale from
+// `templates/emails/<locale>/`.
+pub trait EmailTemplate {
+    fn render(&self, locale: Locale) -> Result<EmailMessage, askama::Error>;
+}
+
+pub struct TwoFACodeEmail {
+    pub code: String,
+}
+
+pub struct PasswordResetEmail {
+    pub reset_link: String,
+}
+
+pub struct EmailVerificationEmail {
+    pub verification_link: String,
+}
+
+pub struct EmailChangeEmail {
+    pub confirmation_link: String,
+}
+
+// Declares the HTML and plain-text templates of one kind of email in one locale
+macro_rules! localized_templates {
+    ($context:ty, $html:ident: $html_path:literal, $text:ident: $text_path:literal) => {
+        #[derive(Template)]
+        #[template(path = $html_path)]
+        struct $html<'a> {
+            email: &'a $context,
+            subject: &'a str,
+        }
+
+        #[derive(Template)]
+        #[template(path = $text_path)]
+        struct $text<'a> {
+            email: &'a $context,
+        }
+    };
+}
+
+localized_templates!(
+    TwoFACodeEmail,
+    TwoFACodeHtmlEn: "emails/en/two_fa_code.html",
+    TwoFACodeTextEn: "emails/en/two_fa_code.txt"
+);
+localized_templates!(
+    TwoFACodeEmail,
+    TwoFACodeHtmlEs: "emails/es/two_fa_code.html",
+    TwoFACodeTextEs: "emails/es/two_fa_code.txt"
+);
+localized_templates!(
+    PasswordResetEmail,
+    PasswordResetHtmlEn: "emails/en/password_reset.html",
+    PasswordResetTextEn: "emails/en/password_reset.txt"
+);
+localized_templates!(
+    PasswordResetEmail,
+    PasswordResetHtmlEs: "emails/es/password_reset.html",
+    PasswordResetTextEs: "emails/es/password_reset.txt"
+);
+localized_templates!(
+    EmailVerificationEmail,
+    EmailVerificationHtmlEn: "emails/en/email_verification.html",
+    EmailVerificationTextEn: "emails/en/email_verification.txt"
+);
+localized_templates!(
+    EmailVerificationEmail,
+    EmailVerificationHtmlEs: "emails/es/email_verification.html",
+    EmailVerificationTextEs: "emails/es/email_verification.txt"
+);
+localized_templates!(
+    EmailChangeEmail,
+    EmailChangeHtmlEn: "emails/en/email_change.html",
+    EmailChangeTextEn: "emails/en/email_change.txt"
+);
+localized_templates!(
+    EmailChangeEmail,
+    EmailChangeHtmlEs: "emails/es/email_change.html",
+    EmailChangeTextEs: "emails/es/email_change.txt"
+);
+
+impl EmailTemplate for TwoFACodeEmail {
+    fn render(&self, locale: Locale) -> Result<EmailMessage, askama::Error> {
+        match locale {
+            Locale::En => {
+                let subject = "Your 2FA code";
+                message(
+                    subject,
+                    TwoFACodeHtmlEn {
+                        email: self,
+                        subject,
+                    },
+                    TwoFACodeTextEn { email: self },
+                )
+            }
+            Locale::Es => {
+                let subject = "Tu código de verificación";
+                message(
+                    subject,
+                    TwoFACodeHtmlEs {
+                        email: self,
+                        subject,
+                    },
+                    TwoFACodeTextEs { email: self },
+                )
+            }
+        }
+    }
+}
+
+impl EmailTemplate for PasswordResetEmail {
+    fn render(&self, locale: Locale) -> Result<EmailMessage, askama::Error> {
+        match locale {
+            Locale::En => {
+                let subject = "Reset your password";
+                message(
+                    subject,
+                    PasswordResetHtmlEn {
+                        email: self,
+                        subject,
+                    },
+                    PasswordResetTextEn { email: self },
+                )
+            }
+            Locale::Es => {
+                let subject = "Restablece tu contraseña";
+                message(
+                    subject,
+                    PasswordResetHtmlEs {
+                        email: self,
+                        subject,
+                    },
+                    PasswordResetTextEs { email: self },
+                )
+            }
+        }
+    }
+}
+
+impl EmailTemplate for EmailVerificationEmail {
+    fn render(&self, locale: Locale) -> Result<EmailMessage, askama::Error> {
+        match locale {
+            Locale::En => {
+                let subject = "Verify your email address";
+                message(
+                    subject,
+                    EmailVerificationHtmlEn {
+                        email: self,
+                        subject,
+                    },
+                    EmailVerificationTextEn { email: self },
+                )
+            }
+            Locale::Es => {
+                let subject = "Verifica tu dirección de correo";
+                message(
+                    subject,
+                    EmailVerificationHtmlEs {
+                        email: self,
+                        subject,
+                    },
+                    EmailVerificationTextEs { email: self },
+                )
+            }
+        }
+    }
+}
+
+impl EmailTemplate for EmailChangeEmail {
+    fn render(&self, locale: Locale) -> Result<EmailMessage, askama::Error> {
+        match locale {
+            Locale::En => {
+                let subject = "Confirm your new email address";
+                message(
+                    subject,
+                    EmailChangeHtmlEn {
+                        email: self,
+                        subject,
+                    },
+                    EmailChangeTextEn { email: self },
+                )
+            }
+            Locale::Es => {
+                let subject = "Confirma tu nueva dirección de correo";
+                message(
+                    subject,
+                    EmailChangeHtmlEs {
+                        email: self,
+                        subject,
+                    },
+                    EmailChangeTextEs { email: self },
+                )
+            }
+        }
+    }
+}
+
+fn message(
+    subject: &str,
+    html: impl Template,
+    text: impl Template,
+) -> Result<EmailMessage, askama::Error> {
+    Ok(EmailMessage {
+        subject: subject.to_owned(),
+        html_body: html.render()?,
+        text_body: text.render()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_render_both_parts_with_context() {
+        let message = TwoFACodeEmail {
+            code: "123456".to_owned(),
+        }
+        .render(Locale::En)
+        .unwrap();
+
+        assert_eq!(message.subject, "Your 2FA code");
+        assert!(message.text_body.contains("123456"));
+        assert!(message.html_body.contains("123456"));
+        assert!(message.html_body.contains("<title>Your 2FA code</title>"));
+        assert!(!message.text_body.contains('<'));
+    }
+
+    #[test]
+    fn should_render_locale_variant() {
+        let link = "http://localhost:3000/?reset_token=abc".to_owned();
+        let en = PasswordResetEmail {
+            reset_link: link.clone(),
+        }
+        .render(Locale::En)
+        .unwrap();
+        let es = PasswordResetEmail { reset_link: link }
+            .render(Locale::Es)
+            .unwrap();
+
+        assert_eq!(es.subject, "Restablece tu contraseña");
+        assert!(es.html_body.contains("lang=\"es\""));
+        assert!(es.text_body.contains("reset_token=abc"));
+        assert_ne!(en.text_body, es.text_body);
+    }
+
+    #[test]
+    fn should_escape_html_but_not_plain_text() {
+        let message = EmailVerificationEmail {
+            verification_link: "http://localhost:3000/?a=1&b=2".to_owned(),
+        }
+        .render(Locale::En)
+        .unwrap();
+
+        assert!(message.html_body.contains("?a=1&amp;b=2"));
+        assert!(message.text_body.contains("?a=1&b=2"));
+    }
+}
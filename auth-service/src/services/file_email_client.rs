@@ -0,0 +1,217 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::Utc;
+use lettre::message::Mailbox;
+use serde::Serialize;
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+use uuid::Uuid;
+
+use super::smtp_email_client::build_message;
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+// JSON lines all go to this file inside the directory
+pub const FILE_EMAIL_JSON_LINES_FILE_NAME: &str = "emails.jsonl";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileEmailFormat {
+    // One `.eml` file per email, which any mail client can open
+    Eml,
+    // One JSON object per line, appended to `FILE_EMAIL_JSON_LINES_FILE_NAME`
+    JsonLines,
+}
+
+impl FromStr for FileEmailFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "eml" => Ok(FileEmailFormat::Eml),
+            "jsonl" => Ok(FileEmailFormat::JsonLines),
+            _ => Err(format!("Unknown email file format: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FileEmailClientError {
+    InvalidSender(String),
+    InvalidDirectory(String),
+}
+
+#[derive(Serialize)]
+struct EmailLine<'a> {
+    sent_at: String,
+    recipient: &'a str,
+    subject: &'a str,
+    text_body: &'a str,
+    html_body: &'a str,
+}
+
+// Writes emails to a directory instead of sending them, so codes and links can be read
+// during local development without a relay
+pub struct FileEmailClient {
+    directory: PathBuf,
+    format: FileEmailFormat,
+    sender: Mailbox,
+    // Keeps concurrent appends to the JSON lines file from interleaving
+    write_lock: Mutex<()>,
+}
+
+impl FileEmailClient {
+    pub fn new(
+        directory: impl Into<PathBuf>,
+        format: FileEmailFormat,
+        sender: &str,
+    ) -> Result<Self, FileEmailClientError> {
+        let directory = directory.into();
+        let sender = sender
+            .parse()
+            .map_err(|_| FileEmailClientError::InvalidSender(sender.to_owned()))?;
+
+        std::fs::create_dir_all(&directory)
+            .map_err(|_| FileEmailClientError::InvalidDirectory(directory.display().to_string()))?;
+
+        Ok(Self {
+            directory,
+            format,
+            sender,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    async fn write_eml(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        let email = build_message(&self.sender, recipient, message)?;
+        // Named so that the files sort in the order they were sent
+        let name = format!("{}-{}", Utc::now().timestamp_millis(), Uuid::new_v4());
+        let partial = self.directory.join(format!("{}.partial", name));
+
+        // Renamed into place once complete, so nobody watching the directory reads half a file
+        fs::write(&partial, email.formatted())
+            .await
+            .map_err(|e| e.to_string())?;
+        fs::rename(&partial, self.directory.join(format!("{}.eml", name)))
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    async fn append_json_line(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> Result<(), String> {
+        let mut line = serde_json::to_string(&EmailLine {
+            sent_at: Utc::now().to_rfc3339(),
+            recipient: recipient.as_ref(),
+            subject: &message.subject,
+            text_body: &message.text_body,
+            html_body: &message.html_body,
+        })
+        .map_err(|e| e.to_string())?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().await;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.directory.join(FILE_EMAIL_JSON_LINES_FILE_NAME))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+        // Tokio writes in the background, so dropping the file unflushed can lose the line
+        file.flush().await.map_err(|e| e.to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for FileEmailClient {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        match self.format {
+            FileEmailFormat::Eml => self.write_eml(recipient, message).await,
+            FileEmailFormat::JsonLines => self.append_json_line(recipient, message).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SENDER: &str = "Auth Service <no-reply@localhost>";
+
+    fn temp_directory() -> PathBuf {
+        std::env::temp_dir().join(format!("auth-service-emails-{}", Uuid::new_v4()))
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage {
+            subject: "Your 2FA code".to_owned(),
+            html_body: "<p>Your code is 123456</p>".to_owned(),
+            text_body: "Your code is 123456".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_write_one_eml_file_per_email() {
+        let directory = temp_directory();
+        let client = FileEmailClient::new(&directory, FileEmailFormat::Eml, SENDER).unwrap();
+        let recipient = Email::parse("test@example.com").unwrap();
+
+        client.send_email(&recipient, &message()).await.unwrap();
+        client.send_email(&recipient, &message()).await.unwrap();
+
+        let files: Vec<PathBuf> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().all(|file| file.extension().unwrap() == "eml"));
+
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("To: test@example.com"));
+        assert!(content.contains("Subject: Your 2FA code"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_append_one_json_line_per_email() {
+        let directory = temp_directory();
+        let client = FileEmailClient::new(&directory, FileEmailFormat::JsonLines, SENDER).unwrap();
+        let recipient = Email::parse("test@example.com").unwrap();
+
+        client.send_email(&recipient, &message()).await.unwrap();
+        client.send_email(&recipient, &message()).await.unwrap();
+
+        let content =
+            std::fs::read_to_string(directory.join(FILE_EMAIL_JSON_LINES_FILE_NAME)).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["recipient"], "test@example.com");
+        assert_eq!(lines[1]["text_body"], "Your code is 123456");
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn should_reject_invalid_sender() {
+        assert_eq!(
+            FileEmailClient::new(temp_directory(), FileEmailFormat::Eml, "not a mailbox").err(),
+            Some(FileEmailClientError::InvalidSender(
+                "not a mailbox".to_owned()
+            ))
+        );
+    }
+}
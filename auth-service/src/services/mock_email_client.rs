@@ -1,21 +1,16 @@
-use crate::domain::{Email, EmailClient};
+use crate::domain::{Email, EmailClient, EmailMessage};
 
 pub struct MockEmailClient;
 
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
-    async fn send_email(
-        &self,
-        recipient: &Email,
-        subject: &str,
-        content: &str,
-    ) -> Result<(), String> {
-        // Our mock email client will simply log the recipient, subject, and content to standard output
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        // Our mock email client will simply log the recipient, subject, and plain-text content to standard output
         println!(
             "Sending email to {} with subject: {} and content: {}",
             recipient.as_ref(),
-            subject,
-            content
+            message.subject,
+            message.text_body
         );
 
         Ok(())
@@ -1,2 +1,7 @@
+pub mod capturing_email_client;
 pub mod data_stores;
+pub mod email_outbox;
+pub mod email_templates;
+pub mod file_email_client;
 pub mod mock_email_client;
+pub mod smtp_email_client;
@@ -0,0 +1,265 @@
+use std::{error::Error as StdError, io, str::FromStr, time::Duration};
+
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Certificate, Tls, TlsParameters},
+        Error as SmtpError,
+    },
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::domain::{Email, EmailClient, EmailMessage};
+
+// How the connection to the relay is secured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpSecurity {
+    // Starts in plaintext and upgrades with STARTTLS before anything else is sent. Fails if
+    // the relay doesn't offer STARTTLS rather than falling back to plaintext.
+    StartTls,
+    // TLS from the first byte, usually on port 465
+    Tls,
+    // No encryption at all. Only meant for relays on a trusted local network.
+    None,
+}
+
+impl SmtpSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            _ => Err(format!("Unknown SMTP security mode: {}", value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpSettings {
+    pub host: String,
+    // Defaults to the usual port for `security`
+    pub port: Option<u16>,
+    pub security: SmtpSecurity,
+    // Without a username the relay is used unauthenticated
+    pub username: Option<String>,
+    pub password: String,
+    pub sender: String,
+    // Applies to each attempt as a whole, not just to connecting
+    pub timeout: Duration,
+    pub max_retries: u32,
+    // Doubled after every failed attempt, up to `max_retry_delay`
+    pub retry_delay: Duration,
+    pub max_retry_delay: Duration,
+    // PEM encoded root certificate trusted on top of the public roots, for relays behind a
+    // private CA
+    pub ca_certificate: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum SmtpEmailClientError {
+    InvalidSender(String),
+    InvalidCertificate,
+}
+
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+    timeout: Duration,
+    max_retries: u32,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl SmtpEmailClient {
+    pub fn new(settings: SmtpSettings) -> Result<Self, SmtpEmailClientError> {
+        let sender = settings
+            .sender
+            .parse()
+            .map_err(|_| SmtpEmailClientError::InvalidSender(settings.sender.clone()))?;
+
+        let tls = match settings.security {
+            SmtpSecurity::None => Tls::None,
+            security => {
+                let mut parameters = TlsParameters::builder(settings.host.clone());
+                if let Some(pem) = &settings.ca_certificate {
+                    let certificate = Certificate::from_pem(pem)
+                        .map_err(|_| SmtpEmailClientError::InvalidCertificate)?;
+                    parameters = parameters.add_root_certificate(certificate);
+                }
+                let parameters = parameters
+                    .build()
+                    .map_err(|_| SmtpEmailClientError::InvalidCertificate)?;
+
+                match security {
+                    SmtpSecurity::Tls => Tls::Wrapper(parameters),
+                    _ => Tls::Required(parameters),
+                }
+            }
+        };
+
+        let mut transport = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host)
+            .port(
+                settings
+                    .port
+                    .unwrap_or_else(|| settings.security.default_port()),
+            )
+            .tls(tls)
+            .timeout(Some(settings.timeout));
+        if let Some(username) = settings.username {
+            transport = transport.credentials(Credentials::new(username, settings.password));
+        }
+
+        Ok(Self {
+            transport: transport.build(),
+            sender,
+            timeout: settings.timeout,
+            max_retries: settings.max_retries,
+            retry_delay: settings.retry_delay,
+            max_retry_delay: settings.max_retry_delay,
+        })
+    }
+
+    // Saturates rather than overflowing however many retries are configured
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        self.retry_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_retry_delay)
+    }
+}
+
+// The MIME message as it goes over the wire, with both the plain-text and the HTML body
+pub(crate) fn build_message(
+    sender: &Mailbox,
+    recipient: &Email,
+    message: &EmailMessage,
+) -> Result<Message, String> {
+    let recipient: Mailbox = recipient
+        .as_ref()
+        .parse()
+        .map_err(|e: lettre::address::AddressError| e.to_string())?;
+
+    Message::builder()
+        .from(sender.clone())
+        .to(recipient)
+        .subject(&message.subject)
+        .multipart(MultiPart::alternative_plain_html(
+            message.text_body.clone(),
+            message.html_body.clone(),
+        ))
+        .map_err(|e| e.to_string())
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<(), String> {
+        let email = build_message(&self.sender, recipient, message)?;
+
+        let mut attempt = 0;
+        loop {
+            let error = match tokio::time::timeout(self.timeout, self.transport.send(email.clone()))
+                .await
+            {
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(e)) if !is_retryable(&e) => return Err(e.to_string()),
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => "Timed out sending email".to_owned(),
+            };
+
+            if attempt >= self.max_retries {
+                return Err(error);
+            }
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+}
+
+// 4xx replies, timeouts and dropped connections may succeed on another try. Permanent
+// rejections, failed TLS handshakes and malformed messages won't.
+fn is_retryable(error: &SmtpError) -> bool {
+    if error.is_permanent() || error.is_client() || error.is_response() || error.is_tls() {
+        return false;
+    }
+
+    // Handshake failures such as an untrusted certificate surface as invalid data
+    let mut source = error.source();
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<io::Error>() {
+            return e.kind() != io::ErrorKind::InvalidData;
+        }
+        source = e.source();
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_security_modes() {
+        assert_eq!("starttls".parse(), Ok(SmtpSecurity::StartTls));
+        assert_eq!("TLS".parse(), Ok(SmtpSecurity::Tls));
+        assert_eq!("none".parse(), Ok(SmtpSecurity::None));
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+
+    #[test]
+    fn should_reject_invalid_sender() {
+        let settings = SmtpSettings {
+            host: "localhost".to_owned(),
+            port: None,
+            security: SmtpSecurity::None,
+            username: None,
+            password: String::new(),
+            sender: "not an address".to_owned(),
+            timeout: Duration::from_secs(1),
+            max_retries: 0,
+            retry_delay: Duration::from_millis(10),
+            max_retry_delay: Duration::from_millis(100),
+            ca_certificate: None,
+        };
+
+        assert!(matches!(
+            SmtpEmailClient::new(settings),
+            Err(SmtpEmailClientError::InvalidSender(_))
+        ));
+    }
+
+    #[test]
+    fn should_cap_retry_delay() {
+        let client = SmtpEmailClient::new(SmtpSettings {
+            host: "localhost".to_owned(),
+            port: None,
+            security: SmtpSecurity::None,
+            username: None,
+            password: String::new(),
+            sender: "no-reply@example.com".to_owned(),
+            timeout: Duration::from_secs(1),
+            max_retries: 40,
+            retry_delay: Duration::from_millis(10),
+            max_retry_delay: Duration::from_millis(100),
+            ca_certificate: None,
+        })
+        .unwrap();
+
+        assert_eq!(client.retry_delay(0), Duration::from_millis(10));
+        assert_eq!(client.retry_delay(3), Duration::from_millis(80));
+        assert_eq!(client.retry_delay(4), Duration::from_millis(100));
+        assert_eq!(client.retry_delay(39), Duration::from_millis(100));
+    }
+}
@@ -1,18 +1,344 @@
+use std::time::Duration;
+
 use axum_extra::extract::cookie::{Cookie, SameSite};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::{
+    digest::{digest, SHA256},
+    signature::{Ed25519KeyPair, KeyPair},
+};
 use serde::{Deserialize, Serialize};
+use spki::{ObjectIdentifier, SubjectPublicKeyInfoRef};
+
+use crate::{
+    app_state::{BannedTokenStoreType, KeyringType, RefreshTokenStoreType, SessionStoreType},
+    domain::{Email, RefreshToken, RefreshTokenFamily, Role},
+};
+
+use super::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME};
+
+// The key access tokens are signed with. Asymmetric keys are published on
+// /.well-known/jwks.json so other services can verify tokens without calling this one.
+// The `kid` is the RFC 7638 thumbprint of the key.
+#[derive(Clone)]
+pub struct SigningKey {
+    kid: String,
+    algorithm: Algorithm,
+    // `None` for public keys, which can only verify tokens
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    jwk: Option<Jwk>,
+}
+
+const ED25519_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+const RSA_ENCRYPTION_OID: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+
+impl SigningKey {
+    // Accepts an Ed25519 (PKCS#8) or RSA (PKCS#1 or PKCS#8) private key
+    pub fn from_pem(pem: &[u8]) -> Result<Self, SigningKeyError> {
+        let der = pem::parse(pem).map_err(|_| SigningKeyError::InvalidPem)?;
+        let der = der.contents();
+
+        if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+            let encoding_key =
+                EncodingKey::from_ed_pem(pem).map_err(|_| SigningKeyError::UnsupportedKey)?;
+
+            return Self::ed25519(key_pair.public_key().as_ref(), Some(encoding_key));
+        }
+
+        let key_pair = ring::rsa::KeyPair::from_pkcs8(der)
+            .or_else(|_| ring::rsa::KeyPair::from_der(der))
+            .map_err(|_| SigningKeyError::UnsupportedKey)?;
+        let public_key = ring::rsa::PublicKeyComponents::<Vec<u8>>::from(key_pair.public());
+        let encoding_key =
+            EncodingKey::from_rsa_pem(pem).map_err(|_| SigningKeyError::UnsupportedKey)?;
+
+        Self::rsa(&public_key.n, &public_key.e, Some(encoding_key))
+    }
+
+    // Accepts an Ed25519 or RSA public key (SubjectPublicKeyInfo), so the private half of a
+    // retired key doesn't have to be kept around to verify its tokens
+    pub fn from_public_pem(pem: &[u8]) -> Result<Self, SigningKeyError> {
+        let der = pem::parse(pem).map_err(|_| SigningKeyError::InvalidPem)?;
+        let info = SubjectPublicKeyInfoRef::try_from(der.contents())
+            .map_err(|_| SigningKeyError::UnsupportedKey)?;
+        let public_key = info
+            .subject_public_key
+            .as_bytes()
+            .ok_or(SigningKeyError::UnsupportedKey)?;
+
+        match info.algorithm.oid {
+            ED25519_OID if public_key.len() == 32 => Self::ed25519(public_key, None),
+            RSA_ENCRYPTION_OID => {
+                let public_key = pkcs1::RsaPublicKey::try_from(public_key)
+                    .map_err(|_| SigningKeyError::UnsupportedKey)?;
+
+                Self::rsa(
+                    public_key.modulus.as_bytes(),
+                    public_key.public_exponent.as_bytes(),
+                    None,
+                )
+            }
+            _ => Err(SigningKeyError::UnsupportedKey),
+        }
+    }
+
+    fn ed25519(
+        public_key: &[u8],
+        encoding_key: Option<EncodingKey>,
+    ) -> Result<Self, SigningKeyError> {
+        let x = BASE64_URL_SAFE_NO_PAD.encode(public_key);
+        let thumbprint = format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x);
+        let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x,
+        });
+
+        Self::asymmetric(Algorithm::EdDSA, encoding_key, parameters, &thumbprint)
+    }
+
+    fn rsa(n: &[u8], e: &[u8], encoding_key: Option<EncodingKey>) -> Result<Self, SigningKeyError> {
+        let n = BASE64_URL_SAFE_NO_PAD.encode(n);
+        let e = BASE64_URL_SAFE_NO_PAD.encode(e);
+        let thumbprint = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+        let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n,
+            e,
+        });
+
+        Self::asymmetric(Algorithm::RS256, encoding_key, parameters, &thumbprint)
+    }
+
+    // HS256 with a shared secret. There is nothing to publish, so other services can only
+    // check these tokens through /verify-token.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        let thumbprint = format!(
+            r#"{{"k":"{}","kty":"oct"}}"#,
+            BASE64_URL_SAFE_NO_PAD.encode(secret)
+        );
+
+        Self {
+            kid: compute_thumbprint(&thumbprint),
+            algorithm: Algorithm::HS256,
+            encoding_key: Some(EncodingKey::from_secret(secret)),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    fn asymmetric(
+        algorithm: Algorithm,
+        encoding_key: Option<EncodingKey>,
+        parameters: AlgorithmParameters,
+        thumbprint: &str,
+    ) -> Result<Self, SigningKeyError> {
+        let kid = compute_thumbprint(thumbprint);
+        let key_algorithm = match algorithm {
+            Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+            _ => KeyAlgorithm::RS256,
+        };
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        };
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).map_err(|_| SigningKeyError::UnsupportedKey)?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk: Some(jwk),
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    // The public half of the key, or `None` for shared secrets
+    pub fn jwk(&self) -> Option<&Jwk> {
+        self.jwk.as_ref()
+    }
+}
+
+fn compute_thumbprint(canonical_jwk: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(digest(&SHA256, canonical_jwk.as_bytes()))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SigningKeyError {
+    InvalidPem,
+    UnsupportedKey,
+}
+
+// The key new tokens are signed with, followed by retired keys whose tokens are still
+// accepted until they expire. Tokens are matched to a key by the `kid` in their header.
+pub struct Keyring {
+    keys: Vec<SigningKey>,
+}
+
+impl Keyring {
+    pub fn new(active: SigningKey) -> Self {
+        Self { keys: vec![active] }
+    }
+
+    pub fn with_verification_key(mut self, key: SigningKey) -> Self {
+        if self.get(key.kid()).is_none() {
+            self.keys.push(key);
+        }
+        self
+    }
+
+    pub fn load(source: &KeyringSource) -> Result<Self, KeyringError> {
+        let active = match &source.signing_key_path {
+            Some(path) => read_signing_key(path)?,
+            None => SigningKey::from_secret(source.secret.as_bytes()),
+        };
 
-use crate::{app_state::BannedTokenStoreType, domain::Email};
+        let keyring = source
+            .previous_secrets
+            .iter()
+            .fold(Self::new(active), |keyring, secret| {
+                keyring.with_verification_key(SigningKey::from_secret(secret.as_bytes()))
+            });
+
+        source
+            .verification_key_paths
+            .iter()
+            .try_fold(keyring, |keyring, path| {
+                Ok(keyring.with_verification_key(read_verification_key(path)?))
+            })
+    }
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+    pub fn active(&self) -> &SigningKey {
+        &self.keys[0]
+    }
+
+    pub fn get(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys.iter().find(|key| key.kid() == kid)
+    }
 
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+    // Public keys of every accepted key, so tokens signed before a rotation can still be
+    // verified by other services
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter_map(|key| key.jwk().cloned())
+                .collect(),
+        }
+    }
+}
+
+fn read_signing_key(path: &str) -> Result<SigningKey, KeyringError> {
+    let pem = std::fs::read(path).map_err(|_| KeyringError::UnreadableFile(path.to_owned()))?;
+    SigningKey::from_pem(&pem).map_err(|e| KeyringError::InvalidKey(path.to_owned(), e))
+}
+
+// Retired keys only verify tokens, so their public half is enough
+fn read_verification_key(path: &str) -> Result<SigningKey, KeyringError> {
+    let pem = std::fs::read(path).map_err(|_| KeyringError::UnreadableFile(path.to_owned()))?;
+    SigningKey::from_pem(&pem)
+        .or_else(|_| SigningKey::from_public_pem(&pem))
+        .map_err(|e| KeyringError::InvalidKey(path.to_owned(), e))
+}
+
+// Where the keyring is loaded from. Verification keys are the key files of retired signing
+// keys and previous secrets are retired HS256 secrets; drop them once tokens signed with
+// them have expired.
+#[derive(Clone, Debug, Default)]
+pub struct KeyringSource {
+    pub signing_key_path: Option<String>,
+    pub verification_key_paths: Vec<String>,
+    pub secret: String,
+    pub previous_secrets: Vec<String>,
+}
+
+impl KeyringSource {
+    fn read_files(&self) -> Vec<Option<Vec<u8>>> {
+        self.signing_key_path
+            .iter()
+            .chain(&self.verification_key_paths)
+            .map(|path| std::fs::read(path).ok())
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyringError {
+    UnreadableFile(String),
+    InvalidKey(String, SigningKeyError),
+}
+
+pub const KEYRING_RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+// Reloads the keyring whenever one of its files changes, so keys can be rotated without a
+// restart. If the new files don't load, the current keyring stays in use. Secrets come from
+// the environment, so rotating `JWT_SECRET` takes a restart with the old secret moved to
+// `JWT_PREVIOUS_SECRETS`.
+pub async fn watch_keyring(keyring: KeyringType, source: KeyringSource, interval: Duration) {
+    let mut files = source.read_files();
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+
+        let current_files = source.read_files();
+        if current_files == files {
+            continue;
+        }
+        files = current_files;
+
+        match Keyring::load(&source) {
+            Ok(reloaded) => *keyring.write().await = reloaded,
+            Err(e) => eprintln!("Failed to reload keyring: {:?}", e),
+        }
+    }
+}
+
+pub async fn generate_auth_cookie(
+    email: &Email,
+    session_id: &str,
+    roles: &[Role],
+    keyring: KeyringType,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_access_token(email, session_id, roles, keyring).await?;
     Ok(create_auth_cookie(token))
 }
 
-fn create_auth_cookie(token: String) -> Cookie<'static> {
+// Signs an access token with the active key, for clients that take it in the response body
+pub async fn generate_access_token(
+    email: &Email,
+    session_id: &str,
+    roles: &[Role],
+    keyring: KeyringType,
+) -> Result<String, GenerateTokenError> {
+    generate_auth_token(email, session_id, roles, keyring.read().await.active())
+}
+
+pub fn create_auth_cookie(token: String) -> Cookie<'static> {
     let cookie = Cookie::build((JWT_COOKIE_NAME, token))
         .path("/")
         .http_only(true)
@@ -22,6 +348,39 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Issues a new refresh token as the next member of `family` and wraps it in a cookie.
+pub async fn generate_refresh_cookie(
+    family: RefreshTokenFamily,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_refresh_token(family, refresh_token_store).await?;
+    Ok(create_refresh_cookie(token))
+}
+
+pub async fn generate_refresh_token(
+    family: RefreshTokenFamily,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<RefreshToken, GenerateTokenError> {
+    let token = RefreshToken::default();
+
+    refresh_token_store
+        .add_token(token.clone(), family)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    Ok(token)
+}
+
+pub fn create_refresh_cookie(token: RefreshToken) -> Cookie<'static> {
+    let cookie = Cookie::build((REFRESH_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .build();
+
+    cookie
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -30,11 +389,21 @@ pub enum GenerateTokenError {
 
 pub const TOKEN_TTL_SECONDS: i64 = 600;
 
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+// Refresh tokens outlive access tokens by design: 14 days
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 60 * 60 * 24 * 14;
+
+fn generate_auth_token(
+    email: &Email,
+    session_id: &str,
+    roles: &[Role],
+    signing_key: &SigningKey,
+) -> Result<String, GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
-    let exp = Utc::now()
+    let now = Utc::now();
+
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
@@ -43,18 +412,35 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
     let sub = email.as_ref().to_owned();
 
-    let claims = Claims { sub, exp };
+    let claims = Claims {
+        sub,
+        exp,
+        iat,
+        jti: session_id.to_owned(),
+        roles: roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+    };
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    create_token(&claims, signing_key).map_err(GenerateTokenError::TokenError)
 }
 
+// A session's last-seen time is only written when it is at least this stale, so that
+// validating a token doesn't always cost a write
+pub const SESSION_LAST_SEEN_RESOLUTION_SECONDS: i64 = 60;
+
 pub async fn validate_token(
     token: &str,
     banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+    keyring: KeyringType,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    match banned_token_store.read().await.contains_token(token).await {
+    match banned_token_store.contains_token(token).await {
         Ok(value) => {
             if value {
                 return Err(jsonwebtoken::errors::Error::from(
@@ -69,26 +455,100 @@ pub async fn validate_token(
         }
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
+    // Tokens signed with a key that isn't on the keyring are rejected before their signature
+    // is checked
+    let kid = decode_header(token)?.kid.ok_or_else(|| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+    })?;
+
+    let claims = {
+        let keyring = keyring.read().await;
+        let signing_key = keyring.get(&kid).ok_or_else(|| {
+            jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+        })?;
+
+        decode::<Claims>(
+            token,
+            &signing_key.decoding_key,
+            &Validation::new(signing_key.algorithm),
+        )
+        .map(|data| data.claims)?
+    };
+
+    // Tokens issued before the user's sessions were revoked (e.g. by a password reset) are
+    // no longer accepted. Timestamps have one second resolution, so a token issued in the
+    // same second as the revocation is rejected as well.
+    let email = Email::parse(&claims.sub).map_err(|_| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+    })?;
+
+    match banned_token_store.get_user_revocation(&email).await {
+        Ok(Some(revoked_at)) if claims.iat <= revoked_at => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ))
+        }
+        Ok(_) => (),
+        Err(_) => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ))
+        }
+    }
+
+    // The session has to still be registered, so revoking it takes effect immediately
+    let session = match session_store.get_session(&claims.jti).await {
+        Ok(session) if session.email == email => session,
+        _ => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ))
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    if now - session.last_seen >= SESSION_LAST_SEEN_RESOLUTION_SECONDS
+        && session_store.touch_session(&session.id, now).await.is_err()
+    {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        ));
+    }
+
+    Ok(claims)
 }
 
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+fn create_token(
+    claims: &Claims,
+    signing_key: &SigningKey,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let encoding_key = signing_key.encoding_key.as_ref().ok_or_else(|| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)
+    })?;
+
+    let mut header = Header::new(signing_key.algorithm);
+    header.kid = Some(signing_key.kid.clone());
+
+    encode(&header, &claims, encoding_key)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    // The id of the session the token was issued to
+    pub jti: String,
+    // Roles the user had when the token was issued. Changes to them show up in the tokens
+    // issued at the next refresh.
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Claims {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|claimed| claimed == role)
+    }
 }
 
 #[cfg(test)]
@@ -96,14 +556,68 @@ mod tests {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::{domain::BannedTokenStore, services::data_stores::HashsetBannedTokenStore};
+    use crate::{
+        domain::{BannedTokenStore, RefreshTokenStore, Session, SessionStore},
+        services::data_stores::{
+            HashmapRefreshTokenStore, HashmapSessionStore, HashsetBannedTokenStore,
+        },
+    };
 
     use super::*;
 
+    const SESSION_ID: &str = "test-session";
+
+    // Registers the session every test token is issued to
+    async fn test_session_store() -> SessionStoreType {
+        let store = HashmapSessionStore::default();
+        store
+            .add_session(Session::new(
+                SESSION_ID.to_owned(),
+                Email::parse("test@example.com").unwrap(),
+                "127.0.0.1".parse().unwrap(),
+                None,
+            ))
+            .await
+            .unwrap();
+        Arc::new(store)
+    }
+
+    fn test_signing_key() -> SigningKey {
+        SigningKey::from_secret(b"secret")
+    }
+
+    fn keyring(active: SigningKey) -> KeyringType {
+        Arc::new(RwLock::new(Keyring::new(active)))
+    }
+
+    fn write_temp_file(contents: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("{}.pem", uuid::Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn ed25519_pem() -> Vec<u8> {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref())).into_bytes()
+    }
+
+    // The SubjectPublicKeyInfo of an Ed25519 key is a fixed prefix followed by the key
+    fn ed25519_public_pem(private_pem: &[u8]) -> Vec<u8> {
+        let der = pem::parse(private_pem).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der.contents()).unwrap();
+        let mut spki = vec![
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+        ];
+        spki.extend_from_slice(key_pair.public_key().as_ref());
+        pem::encode(&pem::Pem::new("PUBLIC KEY", spki)).into_bytes()
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse(&"test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, SESSION_ID, &[], keyring(test_signing_key()))
+            .await
+            .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -122,19 +636,44 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_generate_refresh_cookie() {
+        let email = Email::parse("test@example.com").unwrap();
+        let family = RefreshTokenFamily::new(email);
+        let refresh_token_store = Arc::new(HashmapRefreshTokenStore::default());
+
+        let cookie = generate_refresh_cookie(family.clone(), refresh_token_store.clone())
+            .await
+            .unwrap();
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+
+        let token = RefreshToken::parse(cookie.value().to_owned()).unwrap();
+        let result = refresh_token_store.consume_token(&token).await;
+        assert_eq!(result, Ok(family));
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse(&"test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse(&"test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await
+        .unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -143,24 +682,412 @@ mod tests {
             .timestamp();
 
         assert!(result.exp > exp as usize);
+        assert!(result.roles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_carries_roles() {
+        let email = Email::parse("test@example.com").unwrap();
+        let roles = [Role::admin(), Role::parse("support").unwrap()];
+        let token = generate_auth_token(&email, SESSION_ID, &roles, &test_signing_key()).unwrap();
+
+        let result = validate_token(
+            &token,
+            Arc::new(HashsetBannedTokenStore::default()),
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.roles, vec!["admin".to_owned(), "support".to_owned()]);
+        assert!(result.has_role("admin"));
+        assert!(!result.has_role("billing"));
     }
 
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse(&"test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let mut hs = HashsetBannedTokenStore::default();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let hs = HashsetBannedTokenStore::default();
         hs.add_token(token.clone()).await.unwrap();
-        let banned_token_store = Arc::new(RwLock::new(hs));
-        let result = validate_token(&token, banned_token_store).await;
+        let banned_token_store = Arc::new(hs);
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_before_user_revocation() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let hs = HashsetBannedTokenStore::default();
+        hs.revoke_user_tokens(&email, Utc::now().timestamp() as usize)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(hs);
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_after_user_revocation() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let hs = HashsetBannedTokenStore::default();
+        hs.revoke_user_tokens(&email, Utc::now().timestamp() as usize - 60)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(hs);
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(test_signing_key()),
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_ed25519_key() {
+        let signing_key = SigningKey::from_pem(&ed25519_pem()).unwrap();
+        assert_eq!(signing_key.algorithm(), Algorithm::EdDSA);
+
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &signing_key).unwrap();
+        assert_eq!(
+            decode_header(&token).unwrap().kid.as_deref(),
+            Some(signing_key.kid())
+        );
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(signing_key),
+        )
+        .await;
+        assert_eq!(result.unwrap().sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_rsa_key() {
+        let signing_key = SigningKey::from_pem(include_bytes!(
+            "../../tests/fixtures/jwt_rsa_private_key.pem"
+        ))
+        .unwrap();
+        assert_eq!(signing_key.algorithm(), Algorithm::RS256);
+
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &signing_key).unwrap();
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(signing_key),
+        )
+        .await;
+        assert_eq!(result.unwrap().sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_other_key() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+
+        let other_key = SigningKey::from_pem(&ed25519_pem()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring(other_key),
+        )
+        .await;
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_signing_key_jwk() {
+        let signing_key = SigningKey::from_pem(&ed25519_pem()).unwrap();
+        let jwk = signing_key.jwk().unwrap();
+        assert_eq!(jwk.common.key_id.as_deref(), Some(signing_key.kid()));
+        assert!(matches!(
+            jwk.algorithm,
+            AlgorithmParameters::OctetKeyPair(_)
+        ));
+
+        assert!(test_signing_key().jwk().is_none());
+    }
+
+    #[test]
+    fn test_signing_key_from_invalid_pem() {
+        assert_eq!(
+            SigningKey::from_pem(b"not a key").err(),
+            Some(SigningKeyError::InvalidPem)
+        );
+        let public_key_only = pem::encode(&pem::Pem::new("PUBLIC KEY", vec![0u8; 32]));
+        assert_eq!(
+            SigningKey::from_pem(public_key_only.as_bytes()).err(),
+            Some(SigningKeyError::UnsupportedKey)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_public_verification_key() {
+        let email = Email::parse("test@example.com").unwrap();
+        let ed25519_private_pem = ed25519_pem();
+        let retired_keys = [
+            (
+                SigningKey::from_pem(&ed25519_private_pem).unwrap(),
+                ed25519_public_pem(&ed25519_private_pem),
+            ),
+            (
+                SigningKey::from_pem(include_bytes!(
+                    "../../tests/fixtures/jwt_rsa_private_key.pem"
+                ))
+                .unwrap(),
+                include_bytes!("../../tests/fixtures/jwt_rsa_public_key.pem").to_vec(),
+            ),
+        ];
+
+        for (retired_key, public_pem) in retired_keys {
+            let token = generate_auth_token(&email, SESSION_ID, &[], &retired_key).unwrap();
+            let public_key = SigningKey::from_public_pem(&public_pem).unwrap();
+            assert_eq!(public_key.kid(), retired_key.kid());
+            assert_eq!(public_key.algorithm(), retired_key.algorithm());
+
+            // A public key can't sign anything itself
+            assert!(generate_auth_token(&email, SESSION_ID, &[], &public_key).is_err());
+
+            let keyring = Arc::new(RwLock::new(
+                Keyring::new(test_signing_key()).with_verification_key(public_key),
+            ));
+            let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+            let result = validate_token(
+                &token,
+                banned_token_store,
+                test_session_store().await,
+                keyring,
+            )
+            .await;
+            assert_eq!(result.unwrap().sub, "test@example.com");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_previous_secret() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token =
+            generate_auth_token(&email, SESSION_ID, &[], &SigningKey::from_secret(b"old")).unwrap();
+
+        let source = KeyringSource {
+            secret: "new".to_owned(),
+            previous_secrets: vec!["old".to_owned()],
+            ..Default::default()
+        };
+        let keyring = Keyring::load(&source).unwrap();
+        assert_eq!(
+            keyring.active().kid(),
+            SigningKey::from_secret(b"new").kid()
+        );
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            Arc::new(RwLock::new(keyring)),
+        )
+        .await;
+        assert_eq!(result.unwrap().sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key() {
+        let retired_key = SigningKey::from_pem(&ed25519_pem()).unwrap();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &retired_key).unwrap();
+
+        let keyring = Arc::new(RwLock::new(
+            Keyring::new(SigningKey::from_pem(&ed25519_pem()).unwrap())
+                .with_verification_key(retired_key),
+        ));
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            test_session_store().await,
+            keyring.clone(),
+        )
+        .await;
+        assert_eq!(result.unwrap().sub, "test@example.com");
+
+        // New tokens are only ever signed with the active key
+        let cookie = generate_auth_cookie(&email, SESSION_ID, &[], keyring.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            decode_header(cookie.value()).unwrap().kid.as_deref(),
+            Some(keyring.read().await.active().kid())
+        );
+        assert_eq!(keyring.read().await.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_keyring_load() {
+        let active_path = write_temp_file(&ed25519_pem());
+        let retired_path = write_temp_file(&ed25519_pem());
+        let source = KeyringSource {
+            signing_key_path: Some(active_path.clone()),
+            verification_key_paths: vec![retired_path.clone()],
+            secret: "secret".to_owned(),
+            ..Default::default()
+        };
+
+        let keyring = Keyring::load(&source).unwrap();
+        assert_eq!(keyring.active().algorithm(), Algorithm::EdDSA);
+        assert_eq!(keyring.jwks().keys.len(), 2);
+
+        // Retired keys can be given as just their public key
+        let public_path = write_temp_file(include_bytes!(
+            "../../tests/fixtures/jwt_rsa_public_key.pem"
+        ));
+        let keyring = Keyring::load(&KeyringSource {
+            verification_key_paths: vec![public_path.clone()],
+            ..source.clone()
+        })
+        .unwrap();
+        assert_eq!(keyring.jwks().keys.len(), 2);
+        std::fs::remove_file(&public_path).unwrap();
+
+        // Without a key file tokens are signed with the shared secret
+        let keyring = Keyring::load(&KeyringSource {
+            secret: "secret".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(keyring.active().kid(), test_signing_key().kid());
+
+        std::fs::write(&retired_path, b"not a key").unwrap();
+        assert_eq!(
+            Keyring::load(&source).err(),
+            Some(KeyringError::InvalidKey(
+                retired_path.clone(),
+                SigningKeyError::InvalidPem
+            ))
+        );
+
+        std::fs::remove_file(&active_path).unwrap();
+        std::fs::remove_file(&retired_path).unwrap();
+        assert_eq!(
+            Keyring::load(&source).err(),
+            Some(KeyringError::UnreadableFile(active_path))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_keyring_reloads_changed_files() {
+        let path = write_temp_file(&ed25519_pem());
+        let source = KeyringSource {
+            signing_key_path: Some(path.clone()),
+            ..Default::default()
+        };
+        let keyring = Arc::new(RwLock::new(Keyring::load(&source).unwrap()));
+        let old_kid = keyring.read().await.active().kid().to_owned();
+
+        let watcher = tokio::spawn(watch_keyring(
+            keyring.clone(),
+            source,
+            Duration::from_millis(10),
+        ));
+
+        // A broken file keeps the current keyring in use
+        std::fs::write(&path, b"not a key").unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(keyring.read().await.active().kid(), old_kid);
+
+        let new_key = ed25519_pem();
+        std::fs::write(&path, &new_key).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            keyring.read().await.active().kid(),
+            SigningKey::from_pem(&new_key).unwrap().kid()
+        );
+
+        watcher.abort();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_for_removed_session() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let session_store = test_session_store().await;
+        session_store.remove_session(SESSION_ID).await.unwrap();
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            session_store,
+            keyring(test_signing_key()),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_updates_stale_last_seen() {
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_auth_token(&email, SESSION_ID, &[], &test_signing_key()).unwrap();
+        let session_store = test_session_store().await;
+        let stale = Utc::now().timestamp() - SESSION_LAST_SEEN_RESOLUTION_SECONDS;
+        session_store
+            .touch_session(SESSION_ID, stale)
+            .await
+            .unwrap();
+
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            banned_token_store,
+            session_store.clone(),
+            keyring(test_signing_key()),
+        )
+        .await;
+        assert_eq!(result.unwrap().jti, SESSION_ID);
+
+        let session = session_store.get_session(SESSION_ID).await.unwrap();
+        assert!(session.last_seen > stale);
+    }
 }
@@ -0,0 +1,135 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+};
+
+use super::{
+    auth::{validate_token, Claims},
+    constants::JWT_COOKIE_NAME,
+};
+
+// The user behind a request's access token. Routes that need a logged in user take this as
+// an argument; requests without a valid token are turned away before the handler runs.
+//
+// Browsers send the token in the `jwt` cookie, other clients in an `Authorization: Bearer`
+// header. The header wins when both are present.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub email: Email,
+    pub claims: Claims,
+    // The access token as sent, e.g. for logout to ban it
+    pub token: String,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = get_access_token(&parts.headers)?;
+
+        let claims = validate_token(
+            &token,
+            state.banned_token_store.clone(),
+            state.session_store.clone(),
+            state.keyring.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(Self {
+            email,
+            claims,
+            token,
+        })
+    }
+}
+
+// An `Authorization` header with any other scheme than Bearer is rejected rather than
+// falling back to the cookie
+pub(crate) fn get_access_token(headers: &HeaderMap) -> Result<String, AuthAPIError> {
+    if let Some(value) = headers.get(AUTHORIZATION) {
+        return value
+            .to_str()
+            .ok()
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, token)| token.trim().to_owned())
+            .filter(|token| !token.is_empty())
+            .ok_or(AuthAPIError::InvalidToken);
+    }
+
+    CookieJar::from_headers(headers)
+        .get(JWT_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .ok_or(AuthAPIError::MissingToken)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header::COOKIE, HeaderValue};
+
+    use super::*;
+
+    fn headers(entries: &[(axum::http::HeaderName, &str)]) -> HeaderMap {
+        entries
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn should_read_token_from_cookie() {
+        let headers = headers(&[(COOKIE, "other=1; jwt=cookie-token")]);
+
+        assert_eq!(
+            get_access_token(&headers).ok(),
+            Some("cookie-token".to_owned())
+        );
+    }
+
+    #[test]
+    fn should_prefer_bearer_header_over_cookie() {
+        let headers = headers(&[
+            (AUTHORIZATION, "bearer header-token"),
+            (COOKIE, "jwt=cookie-token"),
+        ]);
+
+        assert_eq!(
+            get_access_token(&headers).ok(),
+            Some("header-token".to_owned())
+        );
+    }
+
+    #[test]
+    fn should_reject_other_authorization_schemes() {
+        for value in ["Basic dXNlcjpwYXNz", "Bearer", "Bearer   ", "header-token"] {
+            let headers = headers(&[(AUTHORIZATION, value), (COOKIE, "jwt=cookie-token")]);
+
+            assert!(matches!(
+                get_access_token(&headers),
+                Err(AuthAPIError::InvalidToken)
+            ));
+        }
+    }
+
+    #[test]
+    fn should_report_missing_token() {
+        assert!(matches!(
+            get_access_token(&HeaderMap::new()),
+            Err(AuthAPIError::MissingToken)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{
+        header::{ACCEPT_LANGUAGE, USER_AGENT},
+        request::Parts,
+        HeaderMap, HeaderName,
+    },
+};
+
+use crate::{app_state::AppState, domain::Locale};
+
+// Who is on the other end of a request, as recorded for login throttling and sessions, and
+// which language emails sent on their behalf should be in
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+    pub locale: Locale,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = <ConnectInfo<SocketAddr> as FromRequestParts<AppState>>::Rejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(address) =
+            ConnectInfo::<SocketAddr>::from_request_parts(parts, state).await?;
+        let ip = state
+            .trusted_proxy_header
+            .as_ref()
+            .and_then(|header| forwarded_ip(&parts.headers, header))
+            .unwrap_or(address.ip());
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let locale = parts
+            .headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(Locale::from_accept_language)
+            .unwrap_or_default();
+
+        Ok(Self {
+            ip,
+            user_agent,
+            locale,
+        })
+    }
+}
+
+// The proxy appends the address it saw to whatever the client sent, so only the last entry
+// can be trusted
+fn forwarded_ip(headers: &HeaderMap, header: &HeaderName) -> Option<IpAddr> {
+    headers
+        .get_all(header)
+        .iter()
+        .next_back()?
+        .to_str()
+        .ok()?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    const HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+    #[test]
+    fn test_forwarded_ip_is_last_entry() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HEADER, HeaderValue::from_static("10.0.0.1, 203.0.113.7"));
+
+        assert_eq!(
+            forwarded_ip(&headers, &HEADER),
+            Some("203.0.113.7".parse().unwrap())
+        );
+
+        headers.append(HEADER, HeaderValue::from_static("2001:db8::1"));
+
+        assert_eq!(
+            forwarded_ip(&headers, &HEADER),
+            Some("2001:db8::1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_forwarded_ip_is_missing_or_invalid() {
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(forwarded_ip(&headers, &HEADER), None);
+
+        headers.insert(HEADER, HeaderValue::from_static("203.0.113.7, unknown"));
+
+        assert_eq!(forwarded_ip(&headers, &HEADER), None);
+    }
+}
@@ -1,11 +1,59 @@
+use axum::http::HeaderName;
+use base64::{prelude::BASE64_STANDARD, Engine};
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
-use std::env as std_env;
+use std::{env as std_env, time::Duration};
+
+use crate::domain::{LocalPartPolicy, DEFAULT_PASSWORD_MIN_ENTROPY_BITS};
 
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
     pub static ref DATABASE_URL: String = set_database_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
+    pub static ref REQUIRE_EMAIL_VERIFICATION: bool = set_require_email_verification();
+    pub static ref TOTP_ENCRYPTION_KEY: [u8; 32] = set_totp_encryption_key();
+    pub static ref JWT_SIGNING_KEY_PATH: Option<String> = set_jwt_signing_key_path();
+    pub static ref JWT_VERIFICATION_KEY_PATHS: Vec<String> = set_jwt_verification_key_paths();
+    pub static ref JWT_PREVIOUS_SECRETS: Vec<String> = set_jwt_previous_secrets();
+    pub static ref SMTP_HOST: Option<String> = set_optional(env::SMTP_HOST_ENV_VAR);
+    pub static ref SMTP_PORT: Option<u16> = set_smtp_port();
+    pub static ref SMTP_SECURITY: String = set_smtp_security();
+    pub static ref SMTP_USERNAME: Option<String> = set_optional(env::SMTP_USERNAME_ENV_VAR);
+    pub static ref SMTP_PASSWORD: String =
+        set_optional(env::SMTP_PASSWORD_ENV_VAR).unwrap_or_default();
+    pub static ref SMTP_SENDER: String =
+        set_optional(env::SMTP_SENDER_ENV_VAR).unwrap_or(DEFAULT_SMTP_SENDER.to_owned());
+    pub static ref SMTP_TIMEOUT_SECONDS: u64 = set_number(
+        env::SMTP_TIMEOUT_SECONDS_ENV_VAR,
+        DEFAULT_SMTP_TIMEOUT_SECONDS
+    );
+    pub static ref SMTP_MAX_RETRIES: u32 =
+        set_number(env::SMTP_MAX_RETRIES_ENV_VAR, DEFAULT_SMTP_MAX_RETRIES);
+    pub static ref SMTP_CA_CERT_PATH: Option<String> = set_optional(env::SMTP_CA_CERT_PATH_ENV_VAR);
+    pub static ref EMAIL_BACKEND: Option<String> = set_optional(env::EMAIL_BACKEND_ENV_VAR);
+    pub static ref EMAIL_FILE_DIRECTORY: String = set_optional(env::EMAIL_FILE_DIRECTORY_ENV_VAR)
+        .unwrap_or(DEFAULT_EMAIL_FILE_DIRECTORY.to_owned());
+    pub static ref EMAIL_FILE_FORMAT: String = set_optional(env::EMAIL_FILE_FORMAT_ENV_VAR)
+        .unwrap_or(DEFAULT_EMAIL_FILE_FORMAT.to_owned());
+    pub static ref ARGON2_MEMORY_COST_KIB: u32 = set_number(
+        env::ARGON2_MEMORY_COST_KIB_ENV_VAR,
+        DEFAULT_ARGON2_MEMORY_COST_KIB
+    );
+    pub static ref ARGON2_TIME_COST: u32 =
+        set_number(env::ARGON2_TIME_COST_ENV_VAR, DEFAULT_ARGON2_TIME_COST);
+    pub static ref ARGON2_PARALLELISM: u32 =
+        set_number(env::ARGON2_PARALLELISM_ENV_VAR, DEFAULT_ARGON2_PARALLELISM);
+    pub static ref PASSWORD_MIN_ENTROPY_BITS: f64 = set_number(
+        env::PASSWORD_MIN_ENTROPY_BITS_ENV_VAR,
+        DEFAULT_PASSWORD_MIN_ENTROPY_BITS
+    );
+    // Replaces the bundled list of breached passwords with a Pwned Passwords download ordered
+    // by hash, which is searched on disk
+    pub static ref BREACHED_PASSWORDS_PATH: Option<String> =
+        set_optional(env::BREACHED_PASSWORDS_PATH_ENV_VAR);
+    pub static ref EMAIL_LOCAL_PART_POLICY: LocalPartPolicy = set_email_local_part_policy();
+    pub static ref TRUSTED_PROXY_HEADER: Option<HeaderName> = set_trusted_proxy_header();
 }
 
 fn set_token() -> String {
@@ -31,10 +79,136 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+fn set_auth_service_url() -> String {
+    dotenv().ok();
+    std_env::var(env::AUTH_SERVICE_URL_ENV_VAR).unwrap_or(DEFAULT_AUTH_SERVICE_URL.to_owned())
+}
+
+fn set_require_email_verification() -> bool {
+    dotenv().ok();
+    std_env::var(env::REQUIRE_EMAIL_VERIFICATION_ENV_VAR)
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(false)
+}
+
+// PEM file with the private key tokens are signed with. Without it tokens are signed with
+// `JWT_SECRET` instead.
+fn set_jwt_signing_key_path() -> Option<String> {
+    dotenv().ok();
+    std_env::var(env::JWT_SIGNING_KEY_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+// Comma separated PEM files of retired signing keys, either the private key or just its
+// public key. Tokens they signed stay valid until they expire.
+fn set_jwt_verification_key_paths() -> Vec<String> {
+    set_list(env::JWT_VERIFICATION_KEY_PATHS_ENV_VAR)
+}
+
+// Comma separated secrets `JWT_SECRET` was rotated away from. Tokens they signed stay valid
+// until they expire.
+fn set_jwt_previous_secrets() -> Vec<String> {
+    set_list(env::JWT_PREVIOUS_SECRETS_ENV_VAR)
+}
+
+fn set_list(name: &str) -> Vec<String> {
+    dotenv().ok();
+    std_env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|value| value.trim().to_owned())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+fn set_optional(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn set_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    set_optional(name)
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be a number.", name))
+        })
+        .unwrap_or(default)
+}
+
+// Defaults to the standard port for `SMTP_SECURITY`
+fn set_smtp_port() -> Option<u16> {
+    set_optional(env::SMTP_PORT_ENV_VAR)
+        .map(|port| port.parse().expect("SMTP_PORT must be a port number."))
+}
+
+// One of `starttls`, `tls` (implicit TLS) or `none`
+fn set_smtp_security() -> String {
+    set_optional(env::SMTP_SECURITY_ENV_VAR).unwrap_or(DEFAULT_SMTP_SECURITY.to_owned())
+}
+
+// `case-insensitive` (the default) or `case-sensitive`. Changing it on a running deployment
+// doesn't rewrite addresses that are already stored. Databases whose addresses were
+// lowercased when lookups became case-insensitive refuse `case-sensitive` until those
+// addresses are restored.
+fn set_email_local_part_policy() -> LocalPartPolicy {
+    set_optional(env::EMAIL_LOCAL_PART_POLICY_ENV_VAR)
+        .map(|policy| policy.parse().expect("Invalid EMAIL_LOCAL_PART_POLICY"))
+        .unwrap_or_default()
+}
+
+// e.g. `X-Forwarded-For`. Only set it when every request comes through a proxy that writes
+// the header, otherwise clients can pick the address they are throttled by.
+fn set_trusted_proxy_header() -> Option<HeaderName> {
+    set_optional(env::TRUSTED_PROXY_HEADER_ENV_VAR).map(|header| {
+        header
+            .parse()
+            .expect("TRUSTED_PROXY_HEADER must be a header name.")
+    })
+}
+
+// Base64 encoded 32 byte key, e.g. the output of `openssl rand -base64 32`
+fn set_totp_encryption_key() -> [u8; 32] {
+    dotenv().ok();
+    let key =
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    BASE64_STANDARD
+        .decode(key.trim())
+        .ok()
+        .and_then(|key| key.try_into().ok())
+        .expect("TOTP_ENCRYPTION_KEY must be a base64 encoded 32 byte key.")
+}
+
 pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const REQUIRE_EMAIL_VERIFICATION_ENV_VAR: &str = "REQUIRE_EMAIL_VERIFICATION";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const JWT_SIGNING_KEY_PATH_ENV_VAR: &str = "JWT_SIGNING_KEY_PATH";
+    pub const JWT_VERIFICATION_KEY_PATHS_ENV_VAR: &str = "JWT_VERIFICATION_KEY_PATHS";
+    pub const JWT_PREVIOUS_SECRETS_ENV_VAR: &str = "JWT_PREVIOUS_SECRETS";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_SECURITY_ENV_VAR: &str = "SMTP_SECURITY";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_SENDER_ENV_VAR: &str = "SMTP_SENDER";
+    pub const SMTP_TIMEOUT_SECONDS_ENV_VAR: &str = "SMTP_TIMEOUT_SECONDS";
+    pub const SMTP_MAX_RETRIES_ENV_VAR: &str = "SMTP_MAX_RETRIES";
+    pub const SMTP_CA_CERT_PATH_ENV_VAR: &str = "SMTP_CA_CERT_PATH";
+    pub const EMAIL_BACKEND_ENV_VAR: &str = "EMAIL_BACKEND";
+    pub const EMAIL_FILE_DIRECTORY_ENV_VAR: &str = "EMAIL_FILE_DIRECTORY";
+    pub const EMAIL_FILE_FORMAT_ENV_VAR: &str = "EMAIL_FILE_FORMAT";
+    pub const ARGON2_MEMORY_COST_KIB_ENV_VAR: &str = "ARGON2_MEMORY_COST_KIB";
+    pub const ARGON2_TIME_COST_ENV_VAR: &str = "ARGON2_TIME_COST";
+    pub const ARGON2_PARALLELISM_ENV_VAR: &str = "ARGON2_PARALLELISM";
+    pub const PASSWORD_MIN_ENTROPY_BITS_ENV_VAR: &str = "PASSWORD_MIN_ENTROPY_BITS";
+    pub const BREACHED_PASSWORDS_PATH_ENV_VAR: &str = "BREACHED_PASSWORDS_PATH";
+    pub const EMAIL_LOCAL_PART_POLICY_ENV_VAR: &str = "EMAIL_LOCAL_PART_POLICY";
+    pub const TRUSTED_PROXY_HEADER_ENV_VAR: &str = "TRUSTED_PROXY_HEADER";
 }
 
 pub mod prod {
@@ -46,4 +220,27 @@ pub mod test {
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+// Clients that can't keep cookies ask for tokens in the response body with either of these
+pub const RESPONSE_MODE_QUERY_PARAM: &str = "response_mode";
+pub const BEARER_RESPONSE_MODE: &str = "bearer";
+pub const BEARER_MEDIA_TYPE: &str = "application/vnd.auth-service.bearer+json";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+// Reconnects wait up to 100ms, 200ms, 400ms... with jitter
+pub const REDIS_RECONNECT_BACKOFF_MILLISECONDS: u64 = 50;
+pub const REDIS_RECONNECT_ATTEMPTS: usize = 6;
+pub const REDIS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+pub const REDIS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+pub const DEFAULT_AUTH_SERVICE_URL: &str = "http://localhost:3000";
+pub const DEFAULT_SMTP_SECURITY: &str = "starttls";
+pub const DEFAULT_SMTP_SENDER: &str = "Auth Service <no-reply@localhost>";
+pub const DEFAULT_SMTP_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_SMTP_MAX_RETRIES: u32 = 3;
+pub const SMTP_RETRY_DELAY_MILLISECONDS: u64 = 500;
+pub const SMTP_MAX_RETRY_DELAY_MILLISECONDS: u64 = 8_000;
+pub const DEFAULT_EMAIL_FILE_DIRECTORY: &str = "emails";
+pub const DEFAULT_EMAIL_FILE_FORMAT: &str = "eml";
+// Raising these upgrades stored password hashes as their users next log in
+pub const DEFAULT_ARGON2_MEMORY_COST_KIB: u32 = 15000;
+pub const DEFAULT_ARGON2_TIME_COST: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
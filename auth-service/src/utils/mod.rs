@@ -1,2 +1,6 @@
 pub mod auth;
+pub mod authenticated_user;
+pub mod client_info;
 pub mod constants;
+pub mod require_role;
+pub mod token_delivery;
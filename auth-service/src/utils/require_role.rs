@@ -0,0 +1,32 @@
+use std::{future::Future, pin::Pin};
+
+use axum::{extract::Request, middleware::Next, response::Response};
+
+use crate::domain::AuthAPIError;
+
+use super::authenticated_user::AuthenticatedUser;
+
+type GuardFuture = Pin<Box<dyn Future<Output = Result<Response, AuthAPIError>> + Send>>;
+
+// Middleware for routes only users with `role` may use, e.g.
+//
+//     router.route_layer(middleware::from_fn_with_state(state, require_role(Role::ADMIN)))
+//
+// The role is read from the access token, so no user lookup is needed. Requests without a
+// valid token are rejected as they would be by any authenticated route, those whose token
+// lacks the role with 403. Handlers behind the guard can take the caller as an
+// `Extension<AuthenticatedUser>` rather than validating the token again.
+pub fn require_role(
+    role: &'static str,
+) -> impl Fn(AuthenticatedUser, Request, Next) -> GuardFuture + Clone + Send + Sync + 'static {
+    move |user: AuthenticatedUser, mut request: Request, next: Next| {
+        Box::pin(async move {
+            if !user.claims.has_role(role) {
+                return Err(AuthAPIError::Forbidden);
+            }
+
+            request.extensions_mut().insert(user);
+            Ok(next.run(request).await)
+        })
+    }
+}
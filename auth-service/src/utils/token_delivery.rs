@@ -0,0 +1,105 @@
+use std::convert::Infallible;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header::ACCEPT, request::Parts, HeaderMap, Uri},
+};
+
+use super::constants::{BEARER_MEDIA_TYPE, BEARER_RESPONSE_MODE, RESPONSE_MODE_QUERY_PARAM};
+
+// Where the tokens of a new or refreshed session go. Browsers get them as cookies. Clients
+// that can't keep cookies, like CLI tools and mobile apps, ask for them in the JSON body with
+// `?response_mode=bearer` or by accepting `application/vnd.auth-service.bearer+json`, and
+// send the access token back in an `Authorization: Bearer` header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TokenDelivery {
+    #[default]
+    Cookies,
+    Body,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for TokenDelivery
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if asks_for_bearer_mode(&parts.uri) || accepts_bearer_media_type(&parts.headers) {
+            Ok(TokenDelivery::Body)
+        } else {
+            Ok(TokenDelivery::Cookies)
+        }
+    }
+}
+
+fn asks_for_bearer_mode(uri: &Uri) -> bool {
+    uri.query()
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .any(|(name, value)| {
+            name == RESPONSE_MODE_QUERY_PARAM && value.eq_ignore_ascii_case(BEARER_RESPONSE_MODE)
+        })
+}
+
+// Media type parameters such as `q` are ignored
+fn accepts_bearer_media_type(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|media_range| media_range.split(';').next())
+        .any(|media_type| media_type.trim().eq_ignore_ascii_case(BEARER_MEDIA_TYPE))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+
+    use super::*;
+
+    async fn delivery(uri: &str, accept: Option<&str>) -> TokenDelivery {
+        let mut request = Request::builder().uri(uri);
+        if let Some(accept) = accept {
+            request = request.header(ACCEPT, accept);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+
+        TokenDelivery::from_request_parts(&mut parts, &())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_default_to_cookies() {
+        assert_eq!(delivery("/login", None).await, TokenDelivery::Cookies);
+        assert_eq!(
+            delivery("/login?response_mode=cookie", Some("application/json")).await,
+            TokenDelivery::Cookies
+        );
+    }
+
+    #[tokio::test]
+    async fn should_deliver_in_body_when_asked_by_query() {
+        assert_eq!(
+            delivery("/login?a=1&response_mode=bearer", None).await,
+            TokenDelivery::Body
+        );
+    }
+
+    #[tokio::test]
+    async fn should_deliver_in_body_when_asked_by_accept_header() {
+        assert_eq!(
+            delivery(
+                "/login",
+                Some("application/json, application/vnd.auth-service.bearer+json; q=0.9")
+            )
+            .await,
+            TokenDelivery::Body
+        );
+    }
+}
@@ -0,0 +1,381 @@
+use auth_service::{
+    domain::{Email, LoginAttemptKey, UserStoreError},
+    routes::AccountResponse,
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up `email` without 2FA
+async fn signup(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+}
+
+async fn login_status(app: &TestApp, email: &str, password: &str) -> u16 {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": password,
+    });
+
+    app.post_login(&login_body).await.status().as_u16()
+}
+
+// Logs in and returns the access token
+async fn login(app: &TestApp, email: &str) -> String {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    token
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[api_test]
+async fn should_change_password_and_sign_out_other_sessions() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    let other_token = login(&app, &random_email).await;
+    let current_token = login(&app, &random_email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<AccountResponse>()
+            .await
+            .expect("Could not deserialize response body to AccountResponse")
+            .message,
+        "Password updated successfully!".to_owned()
+    );
+
+    assert_eq!(verify_token_status(&app, &other_token).await, 401);
+    assert_eq!(verify_token_status(&app, &current_token).await, 200);
+
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        401
+    );
+    assert_eq!(
+        login_status(&app, &random_email, "new_password123").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_current_password_is_incorrect() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let wrong_password = "Wr0ng-S3cret-pw";
+    let attempt_key = LoginAttemptKey::Email(Email::parse(&random_email).unwrap());
+
+    for path in ["password", "email", "2fa", "delete"] {
+        let body = serde_json::json!({
+            "currentPassword": wrong_password,
+            "newPassword": "new_password123",
+            "newEmail": get_random_email(),
+            "requires2FA": true
+        });
+        let response = match path {
+            "password" => app.post_change_password(&body).await,
+            "email" => app.post_change_email(&body).await,
+            "2fa" => app.post_set_2fa(&body).await,
+            _ => app.post_delete_account(&body).await,
+        };
+
+        assert_eq!(response.status().as_u16(), 401, "/account/{}", path);
+
+        // Wrong passwords count as failed logins
+        let failures = app
+            .login_attempt_store
+            .get_failures(&attempt_key)
+            .await
+            .unwrap()
+            .expect("Failure was not recorded");
+        assert_eq!(failures.count, 1);
+
+        app.login_attempt_store
+            .clear_failures(&attempt_key)
+            .await
+            .unwrap();
+    }
+
+    assert!(app
+        .user_store
+        .get_user(&Email::parse(&random_email).unwrap())
+        .await
+        .is_ok());
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_400_with_reasons_if_new_password_breaks_policy() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newPassword": "password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .reasons
+        .contains(&"breached".to_owned()));
+
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_change_email_once_confirmed_from_new_address() {
+    let random_email = get_random_email();
+    let new_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    let token = login(&app, &random_email).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newEmail": new_email
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Nothing changes until the link is followed
+    assert_eq!(login_status(&app, &new_email, "Sup3r-S3cret-pw").await, 401);
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+
+    let email_change_token = app
+        .get_token_from_email(&new_email, "email_change_token")
+        .await;
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": email_change_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        401
+    );
+    assert_eq!(login_status(&app, &new_email, "Sup3r-S3cret-pw").await, 200);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(&new_email).unwrap())
+        .await
+        .expect("User not found under the new email");
+    assert!(user.verified);
+
+    // Links work only once
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": email_change_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_409_if_new_email_is_taken() {
+    let random_email = get_random_email();
+    let taken_email = get_random_email();
+
+    signup(&app, &taken_email).await;
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newEmail": taken_email.to_uppercase()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+    // Only the verification email from signing up went out
+    assert_eq!(
+        app.last_email_to(&taken_email)
+            .await
+            .expect("No email was sent")
+            .subject,
+        "Verify your email address"
+    );
+}
+
+#[api_test]
+async fn should_keep_authenticator_app_when_email_changes() {
+    let random_email = get_random_email();
+    let new_email = get_random_email();
+
+    let (secret, confirmed_step, _) = app.enable_totp(&random_email).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "newEmail": new_email
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_change_token = app
+        .get_token_from_email(&new_email, "email_change_token")
+        .await;
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": email_change_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let secrets: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM totp_secrets WHERE email = $1")
+        .bind(&new_email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to count TOTP secrets");
+    assert_eq!(secrets, 1);
+
+    let login_attempt_id = app.login_with_2fa(&new_email).await;
+
+    // The confirmation code's step is already used up, so log in with the next one
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": new_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": secret.code_at_step(confirmed_step + 1).as_ref()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_turn_2fa_on_and_off() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    for (requires_2fa, login_status_code) in [(true, 206), (false, 200)] {
+        let response = app
+            .post_set_2fa(&serde_json::json!({
+                "currentPassword": "Sup3r-S3cret-pw",
+                "requires2FA": requires_2fa
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+            login_status_code
+        );
+    }
+}
+
+#[api_test]
+async fn should_delete_account_and_end_sessions() {
+    let random_email = get_random_email();
+
+    signup(&app, &random_email).await;
+    let token = login(&app, &random_email).await;
+
+    let response = app
+        .post_delete_account(&serde_json::json!({ "currentPassword": "Sup3r-S3cret-pw" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(
+        app.user_store
+            .get_user(&Email::parse(&random_email).unwrap())
+            .await
+            .err(),
+        Some(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        401
+    );
+
+    // The address is free to sign up with again
+    signup(&app, &random_email).await;
+}
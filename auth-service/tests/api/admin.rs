@@ -0,0 +1,527 @@
+use auth_service::{
+    domain::{Email, TwoFAMethod},
+    routes::{
+        AuditLogResponse, RolesResponse, SignupResponse, TokensResponse, UserDetailsResponse,
+        UserSummary, UsersResponse,
+    },
+    utils::auth::Claims,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn error_message(response: reqwest::Response) -> String {
+    response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse")
+        .error
+}
+
+#[api_test]
+async fn should_return_403_if_user_is_not_admin() {
+    let random_email = get_random_email();
+
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+
+    let response = app
+        .post_grant_role(
+            &tokens.access_token,
+            &serde_json::json!({ "email": random_email, "role": "admin" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        error_message(response).await,
+        "Insufficient permissions".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_token_is_invalid() {
+    let response = app
+        .post_grant_role(
+            "invalid",
+            &serde_json::json!({ "email": get_random_email(), "role": "admin" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_grant_and_revoke_roles() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+    let role_body = serde_json::json!({ "email": random_email, "role": "admin" });
+
+    let response = app.post_grant_role(&admin_token, &role_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<RolesResponse>()
+            .await
+            .expect("Could not deserialize response body to RolesResponse")
+            .roles,
+        vec!["admin".to_owned()]
+    );
+
+    // The current token keeps the roles it was issued with until it is refreshed
+    let claims_for = |access_token: String| {
+        let app = &app;
+        async move {
+            app.post_verify_token_with_claims(&serde_json::json!({ "token": access_token }))
+                .await
+                .json::<Claims>()
+                .await
+                .expect("Could not deserialize response body to Claims")
+        }
+    };
+
+    assert!(claims_for(tokens.access_token).await.roles.is_empty());
+
+    let response = app.post_refresh_bearer(&tokens.refresh_token).await;
+    let tokens = response
+        .json::<TokensResponse>()
+        .await
+        .expect("Could not deserialize response body to TokensResponse");
+
+    assert_eq!(
+        claims_for(tokens.access_token.clone()).await.roles,
+        vec!["admin".to_owned()]
+    );
+
+    let response = app.post_revoke_role(&admin_token, &role_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .json::<RolesResponse>()
+        .await
+        .expect("Could not deserialize response body to RolesResponse")
+        .roles
+        .is_empty());
+
+    // Tokens still carrying the role stop working right away
+    let response = app.get_admin_users(&tokens.access_token, &[]).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        app.post_refresh_bearer(&tokens.refresh_token)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_role_is_unknown() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    app.signup_and_login_bearer(&random_email).await;
+
+    for role in ["support", "Not A Role"] {
+        let response = app
+            .post_grant_role(
+                &admin_token,
+                &serde_json::json!({ "email": random_email, "role": role }),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(error_message(response).await, "Unknown role".to_owned());
+    }
+}
+
+#[api_test]
+async fn should_return_404_if_user_does_not_exist() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let response = app
+        .post_grant_role(
+            &admin_token,
+            &serde_json::json!({ "email": get_random_email(), "role": "admin" }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(error_message(response).await, "User not found".to_owned());
+}
+
+async fn login_status(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await
+        .status()
+        .as_u16()
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[api_test]
+async fn should_return_403_for_every_admin_route_if_user_is_not_admin() {
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+    let token = &tokens.access_token;
+
+    let responses = [
+        app.get_admin_users(token, &[]).await,
+        app.get_admin_user(token, &random_email).await,
+        app.post_admin_user_action(token, "disable", &random_email)
+            .await,
+        app.get_admin_audit_log(token, &[]).await,
+    ];
+
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 403);
+    }
+}
+
+#[api_test]
+async fn should_list_and_search_users_page_by_page() {
+    let admin_email = get_random_email();
+    let admin_token = app.signup_admin(&admin_email).await;
+
+    let mut emails = vec![admin_email];
+    for _ in 0..4 {
+        let email = get_random_email();
+        app.signup_and_login_bearer(&email).await;
+        emails.push(email);
+    }
+    emails.sort();
+
+    let users = |query: Vec<(&'static str, String)>| {
+        let app = &app;
+        let admin_token = &admin_token;
+        async move {
+            let query: Vec<(&str, &str)> = query
+                .iter()
+                .map(|(name, value)| (*name, value.as_str()))
+                .collect();
+            let response = app.get_admin_users(admin_token, &query).await;
+            assert_eq!(response.status().as_u16(), 200);
+            response
+                .json::<UsersResponse>()
+                .await
+                .expect("Could not deserialize response body to UsersResponse")
+        }
+    };
+
+    let page = users(vec![("page", "2".to_owned()), ("per_page", "2".to_owned())]).await;
+    assert_eq!(page.total, 5);
+    assert_eq!((page.page, page.per_page), (2, 2));
+    assert_eq!(
+        page.users
+            .iter()
+            .map(|user| user.email.clone())
+            .collect::<Vec<_>>(),
+        emails[2..4].to_vec()
+    );
+
+    // Any part of the address matches, regardless of case
+    let search = emails[3][..8].to_uppercase();
+    let page = users(vec![("search", search)]).await;
+    assert_eq!(page.total, 1);
+    assert_eq!(
+        page.users[0],
+        UserSummary {
+            email: emails[3].clone(),
+            verified: false,
+            disabled: false,
+            two_fa_method: "none".to_owned(),
+        }
+    );
+}
+
+#[api_test]
+async fn should_show_user_with_roles_and_sessions() {
+    let admin_email = get_random_email();
+    let admin_token = app.signup_admin(&admin_email).await;
+
+    let response = app.get_admin_user(&admin_token, &admin_email).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user = response
+        .json::<UserDetailsResponse>()
+        .await
+        .expect("Could not deserialize response body to UserDetailsResponse");
+    assert_eq!(user.user.email, admin_email);
+    assert_eq!(user.roles, vec!["admin".to_owned()]);
+    assert_eq!(user.active_sessions, 1);
+
+    let response = app.get_admin_user(&admin_token, &get_random_email()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[api_test]
+async fn should_disable_and_enable_user() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+
+    let response = app
+        .post_admin_user_action(&admin_token, "disable", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Signed out everywhere and kept from logging in
+    assert_eq!(verify_token_status(&app, &tokens.access_token).await, 401);
+    assert_eq!(
+        app.post_refresh_bearer(&tokens.refresh_token)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "Sup3r-S3cret-pw"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(error_message(response).await, "Account disabled".to_owned());
+
+    let response = app
+        .post_admin_user_action(&admin_token, "enable", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_force_password_reset() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+
+    let response = app
+        .post_admin_user_action(&admin_token, "password-reset", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &tokens.access_token).await, 401);
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        401
+    );
+
+    let reset_token = app.get_token_from_email(&random_email, "reset_token").await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        login_status(&app, &random_email, "new_password123").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_reset_authenticator_app_to_email_codes() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let response = app
+        .post_admin_user_action(&admin_token, "reset-2fa", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+
+    // Recovery codes from the old app are gone too
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_revoke_all_tokens() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+
+    let response = app
+        .post_admin_user_action(&admin_token, "revoke-tokens", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify_token_status(&app, &tokens.access_token).await, 401);
+    assert_eq!(
+        app.post_refresh_bearer(&tokens.refresh_token)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+
+    // Unlike disabling, the user can log in again right away
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        200
+    );
+}
+
+#[api_test]
+async fn should_record_admin_actions_in_audit_log() {
+    let admin_email = get_random_email();
+    let admin_token = app.signup_admin(&admin_email).await;
+
+    let random_email = get_random_email();
+    app.signup_and_login_bearer(&random_email).await;
+
+    app.get_admin_users(&admin_token, &[("search", "example")])
+        .await;
+    for action in ["disable", "enable", "revoke-tokens"] {
+        let response = app
+            .post_admin_user_action(&admin_token, action, &random_email)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    app.post_grant_role(
+        &admin_token,
+        &serde_json::json!({ "email": random_email, "role": "admin" }),
+    )
+    .await;
+
+    let response = app
+        .get_admin_audit_log(&admin_token, &[("target", &random_email)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let log = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+
+    assert_eq!(log.total, 4);
+    assert_eq!(
+        log.entries
+            .iter()
+            .map(|entry| (entry.action.as_str(), entry.details.as_deref()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("grant_role", Some("admin")),
+            ("revoke_tokens", None),
+            ("enable_user", None),
+            ("disable_user", None),
+        ]
+    );
+    assert!(log.entries.iter().all(|entry| entry.actor == admin_email
+        && entry.target.as_deref() == Some(random_email.as_str())));
+
+    // Searches are recorded without a target
+    let response = app
+        .get_admin_audit_log(&admin_token, &[("per_page", "1"), ("page", "5")])
+        .await;
+    let log = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+
+    assert_eq!(log.total, 5);
+    assert_eq!(log.entries[0].action, "list_users");
+    assert_eq!(log.entries[0].details.as_deref(), Some("example"));
+}
+
+#[api_test]
+async fn should_leave_email_2fa_users_as_they_are_on_2fa_reset() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    let recovery_codes = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "Sup3r-S3cret-pw",
+            "requires2FA": true
+        }))
+        .await
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes;
+
+    let response = app
+        .post_admin_user_action(&admin_token, "reset-2fa", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_act_if_audit_trail_cannot_be_written() {
+    let admin_token = app.signup_admin(&get_random_email()).await;
+
+    let random_email = get_random_email();
+    app.signup_and_login_bearer(&random_email).await;
+
+    sqlx::query("DROP TABLE admin_audit_log")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_admin_user_action(&admin_token, "disable", &random_email)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    assert_eq!(
+        login_status(&app, &random_email, "Sup3r-S3cret-pw").await,
+        200
+    );
+}
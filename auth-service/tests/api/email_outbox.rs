@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use auth_service::domain::Email;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp, TEST_EMAIL_RETRY_POLICY};
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": requires_2fa
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+}
+
+#[api_test]
+async fn should_log_in_while_email_relay_is_down() {
+    let random_email = get_random_email();
+    signup(&app, &random_email, true).await;
+    // Gets the verification email out of the way
+    assert_eq!(app.deliver_emails().await, 1);
+
+    app.emails.set_failing(true);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 206);
+    assert_eq!(app.deliver_emails().await, 0);
+
+    app.emails.set_failing(false);
+
+    let email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("2FA code was never delivered");
+
+    assert_eq!(email.subject, "Your 2FA code");
+}
+
+#[api_test]
+async fn should_dead_letter_email_after_max_attempts_and_replay_it() {
+    let random_email = get_random_email();
+
+    app.emails.set_failing(true);
+    signup(&app, &random_email, false).await;
+
+    for _ in 0..TEST_EMAIL_RETRY_POLICY.max_attempts {
+        app.deliver_emails().await;
+    }
+
+    let dead_letters = app.email_outbox.store().get_dead_letters().await.unwrap();
+
+    assert_eq!(dead_letters.len(), 1);
+    let dead_letter = &dead_letters[0];
+    assert_eq!(dead_letter.recipient.as_ref(), random_email);
+    assert_eq!(dead_letter.attempts, TEST_EMAIL_RETRY_POLICY.max_attempts);
+    assert_eq!(dead_letter.last_error.as_deref(), Some("Relay unavailable"));
+
+    app.emails.set_failing(false);
+    // Dead letters stay put until they are replayed
+    assert!(app.last_email_to(&random_email).await.is_none());
+
+    app.email_outbox
+        .store()
+        .replay_dead_letter(dead_letter.id, chrono::Utc::now().timestamp())
+        .await
+        .unwrap();
+
+    let email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("Replayed email was never delivered");
+
+    assert_eq!(email.subject, "Verify your email address");
+}
+
+#[api_test]
+async fn should_deliver_emails_in_background_as_soon_as_they_are_queued() {
+    let random_email = get_random_email();
+    // Polling alone would take a minute, so a quick delivery means the worker was woken
+    tokio::spawn(app.email_outbox.clone().run_worker(
+        app.email_client.clone(),
+        TEST_EMAIL_RETRY_POLICY,
+        Duration::from_secs(60),
+    ));
+
+    signup(&app, &random_email, false).await;
+
+    let email = Email::parse(&random_email).unwrap();
+    let started = Instant::now();
+    while app.emails.last_email_to(&email).is_none() {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Email was not delivered in the background"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
@@ -1,5 +1,8 @@
+use axum::http::HeaderName;
 use core::panic;
+use redis::aio::ConnectionManager;
 use reqwest::cookie::Jar;
+use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
@@ -8,24 +11,55 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    get_postgres_pool, get_redis_client,
+    app_state::{
+        AppState, BannedTokenStoreType, EmailClientType, EmailVerificationPolicy, KeyringType,
+        LoginAttemptStoreType, PasswordResetTokenStoreType, RefreshTokenStoreType,
+        TwoFACodeStoreType, UserStoreType,
+    },
+    domain::{Email, EmailRetryPolicy, Role, TotpSecret, TOTP_STEP_SECONDS},
+    get_postgres_pool, get_redis_connection,
+    routes::{ConfirmTotpResponse, EnrollTotpResponse, TokensResponse, TwoFactorAuthResponse},
     services::{
-        data_stores::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
-        mock_email_client::MockEmailClient,
+        capturing_email_client::{CapturedEmails, CapturingEmailClient, SentEmail},
+        data_stores::{
+            HashmapLoginAttemptStore, PostgresAdminAuditStore, PostgresEmailOutboxStore,
+            PostgresRecoveryCodeStore, PostgresTotpSecretStore, PostgresUserStore,
+            RedisBannedTokenStore, RedisEmailChangeTokenStore, RedisEmailVerificationTokenStore,
+            RedisPasswordResetTokenStore, RedisRefreshTokenStore, RedisSessionStore,
+            RedisTwoFACodeStore,
+        },
+        email_outbox::EmailOutbox,
+    },
+    utils::{
+        auth::{Keyring, SigningKey},
+        constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
     },
-    utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
     Application,
 };
 
 use std::str::FromStr;
 use uuid::Uuid;
 
+pub const TEST_USER_AGENT: &str =
+    "Mozilla/5.0 (X11; Linux x86_64; rv:121.0) Gecko/20100101 Firefox/121.0";
+
+// Set like a reverse proxy would. Requests without it are throttled by their peer address.
+pub const TRUSTED_PROXY_HEADER: &str = "x-forwarded-for";
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
+    pub pg_pool: PgPool,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub login_attempt_store: LoginAttemptStoreType,
+    pub email_client: EmailClientType,
+    pub emails: CapturedEmails,
+    pub email_outbox: EmailOutbox,
+    pub keyring: KeyringType,
     pub http_client: reqwest::Client,
     pub db_name: String,
     pub clean_up_called: bool,
@@ -33,24 +67,80 @@ pub struct TestApp {
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_email_verification_policy(EmailVerificationPolicy::Optional).await
+    }
+
+    pub async fn with_email_verification_policy(
+        email_verification_policy: EmailVerificationPolicy,
+    ) -> Self {
+        Self::build(email_verification_policy, None).await
+    }
+
+    // Runs the app against another user store than Postgres, e.g. one that is slower
+    pub async fn with_user_store(user_store: UserStoreType) -> Self {
+        Self::build(EmailVerificationPolicy::Optional, Some(user_store)).await
+    }
+
+    async fn build(
+        email_verification_policy: EmailVerificationPolicy,
+        user_store: Option<UserStoreType>,
+    ) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(&db_name).await;
-        let redis_connection = Arc::new(RwLock::new(configure_redis()));
-
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
-        let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
+        let redis_connection = configure_redis().await;
+
+        let user_store =
+            user_store.unwrap_or_else(|| Arc::new(PostgresUserStore::new(pg_pool.clone())));
+        let banned_token_store = Arc::new(RedisBannedTokenStore::new(redis_connection.clone()));
+        let two_fa_code_store = Arc::new(RedisTwoFACodeStore::new(redis_connection.clone()));
+        let refresh_token_store = Arc::new(RedisRefreshTokenStore::new(redis_connection.clone()));
+        let password_reset_token_store =
+            Arc::new(RedisPasswordResetTokenStore::new(redis_connection.clone()));
+        let email_verification_token_store = Arc::new(RedisEmailVerificationTokenStore::new(
             redis_connection.clone(),
-        )));
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_connection)));
-
-        let email_client = Arc::new(RwLock::new(MockEmailClient));
+        ));
+        let email_change_token_store =
+            Arc::new(RedisEmailChangeTokenStore::new(redis_connection.clone()));
+        let session_store = Arc::new(RedisSessionStore::new(redis_connection));
+
+        let totp_secret_store = Arc::new(PostgresTotpSecretStore::new(
+            pg_pool.clone(),
+            rand::random(),
+        ));
+        let recovery_code_store = Arc::new(PostgresRecoveryCodeStore::new(pg_pool.clone()));
+        let admin_audit_store = Arc::new(PostgresAdminAuditStore::new(pg_pool.clone()));
+        // No worker runs in tests. Helpers that read emails deliver the outbox first, so
+        // tests see emails as soon as the request that queued them returns.
+        let email_outbox =
+            EmailOutbox::new(Arc::new(PostgresEmailOutboxStore::new(pg_pool.clone())));
+
+        // Kept in memory so failed logins in one test can't throttle another
+        let login_attempt_store = Arc::new(HashmapLoginAttemptStore::default());
+
+        let email_client = CapturingEmailClient::default();
+        let emails = email_client.handle();
+        let email_client: EmailClientType = Arc::new(email_client);
+
+        let keyring = Arc::new(RwLock::new(Keyring::new(generate_signing_key())));
 
         let app_state = AppState::new(
-            user_store,
+            user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
-            email_client,
-        );
+            refresh_token_store.clone(),
+            password_reset_token_store.clone(),
+            email_verification_token_store,
+            email_change_token_store,
+            totp_secret_store,
+            recovery_code_store,
+            login_attempt_store.clone(),
+            session_store,
+            admin_audit_store,
+            email_outbox.clone(),
+            keyring.clone(),
+        )
+        .with_email_verification_policy(email_verification_policy)
+        .with_trusted_proxy_header(Some(HeaderName::from_static(TRUSTED_PROXY_HEADER)));
 
         let app = Application::build(app_state, test::APP_ADDRESS)
             .await
@@ -64,14 +154,24 @@ impl TestApp {
         let cookie_jar = Arc::new(Jar::default());
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .user_agent(TEST_USER_AGENT)
             .build()
             .unwrap();
 
         Self {
             address,
             cookie_jar,
+            pg_pool,
+            user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            password_reset_token_store,
+            login_attempt_store,
+            email_client,
+            emails,
+            email_outbox,
+            keyring,
             http_client,
             db_name,
             clean_up_called: false,
@@ -86,6 +186,14 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -110,6 +218,41 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    // Logs in like a client that keeps its own tokens instead of cookies
+    pub async fn post_login_bearer<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login?response_mode=bearer", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Signs up a user without 2FA and logs in in bearer mode, returning the issued tokens
+    pub async fn signup_and_login_bearer(&self, email: &str) -> TokensResponse {
+        let signup_body = serde_json::json!({
+            "email": email,
+            "password": "Sup3r-S3cret-pw",
+            "requires2FA": false
+        });
+        assert_eq!(self.post_signup(&signup_body).await.status().as_u16(), 201);
+
+        let login_body = serde_json::json!({
+            "email": email,
+            "password": "Sup3r-S3cret-pw"
+        });
+        let response = self.post_login_bearer(&login_body).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+            .json::<TokensResponse>()
+            .await
+            .expect("Could not deserialize response body to TokensResponse")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
             .post(format!("{}/logout", &self.address))
@@ -130,6 +273,18 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_verify_recovery_code<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-2fa/recovery", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -142,6 +297,400 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_verify_token_with_claims<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!(
+                "{}/verify-token?include_claims=true",
+                &self.address
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh_bearer(&self, refresh_token: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh?response_mode=bearer", &self.address))
+            .json(&serde_json::json!({ "refreshToken": refresh_token }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_session<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/sessions/revoke", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_all_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/sessions/revoke-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/email/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_set_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/2fa", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/account/delete", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Admin routes are called with the admin's token rather than the cookie jar, so one test
+    // can act as the admin and as the user being managed
+    pub async fn post_grant_role<Body>(&self, access_token: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/roles/grant", &self.address))
+            .bearer_auth(access_token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_revoke_role<Body>(&self, access_token: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/roles/revoke", &self.address))
+            .bearer_auth(access_token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_users(
+        &self,
+        access_token: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users", &self.address))
+            .bearer_auth(access_token)
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_user(&self, access_token: &str, email: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/{}", &self.address, email))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // `action` is the last segment of one of the /admin/users/<action> routes
+    pub async fn post_admin_user_action(
+        &self,
+        access_token: &str,
+        action: &str,
+        email: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/users/{}", &self.address, action))
+            .bearer_auth(access_token)
+            .json(&serde_json::json!({ "email": email }))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin_audit_log(
+        &self,
+        access_token: &str,
+        query: &[(&str, &str)],
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-log", &self.address))
+            .bearer_auth(access_token)
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Signs up a user, makes them an admin and returns an access token carrying the role
+    pub async fn signup_admin(&self, email: &str) -> String {
+        let tokens = self.signup_and_login_bearer(email).await;
+
+        self.user_store
+            .assign_role(&Email::parse(email).unwrap(), Role::admin())
+            .await
+            .expect("Failed to assign admin role");
+
+        // The role shows up in tokens issued from now on
+        let response = self.post_refresh_bearer(&tokens.refresh_token).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+            .json::<TokensResponse>()
+            .await
+            .expect("Could not deserialize response body to TokensResponse")
+            .access_token
+    }
+
+    // Signs up and logs in a user without 2FA, then enrolls and confirms an authenticator app.
+    // Returns the enrolled secret, the time step used for confirmation and the recovery codes.
+    pub async fn enable_totp(&self, email: &str) -> (TotpSecret, u64, Vec<String>) {
+        let signup_body = serde_json::json!({
+            "email": email,
+            "password": "Sup3r-S3cret-pw",
+            "requires2FA": false
+        });
+        assert_eq!(self.post_signup(&signup_body).await.status().as_u16(), 201);
+
+        let login_body = serde_json::json!({
+            "email": email,
+            "password": "Sup3r-S3cret-pw"
+        });
+        assert_eq!(self.post_login(&login_body).await.status().as_u16(), 200);
+
+        let response = self.post_totp_enroll().await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let enrollment = response
+            .json::<EnrollTotpResponse>()
+            .await
+            .expect("Could not deserialize response body to EnrollTotpResponse");
+        let secret = TotpSecret::parse_base32(&enrollment.secret).expect("Invalid TOTP secret");
+
+        let step = current_totp_step();
+        let response = self
+            .post_totp_confirm(
+                &serde_json::json!({ "2FACode": secret.code_at_step(step).as_ref() }),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let recovery_codes = response
+            .json::<ConfirmTotpResponse>()
+            .await
+            .expect("Could not deserialize response body to ConfirmTotpResponse")
+            .recovery_codes;
+
+        (secret, step, recovery_codes)
+    }
+
+    // Logs in a user that has 2FA enabled and returns the login attempt id
+    pub async fn login_with_2fa(&self, email: &str) -> String {
+        let login_body = serde_json::json!({
+            "email": email,
+            "password": "Sup3r-S3cret-pw"
+        });
+
+        let response = self.post_login(&login_body).await;
+        assert_eq!(response.status().as_u16(), 206);
+
+        response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_regenerate_recovery_codes<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/recovery-codes/regenerate", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Sends whatever is in the outbox, retrying failed emails straight away
+    pub async fn deliver_emails(&self) -> usize {
+        self.email_outbox
+            .deliver_due(&self.email_client, &TEST_EMAIL_RETRY_POLICY)
+            .await
+            .expect("Failed to deliver emails")
+    }
+
+    pub async fn last_email_to(&self, email: &str) -> Option<SentEmail> {
+        self.deliver_emails().await;
+
+        let email = Email::parse(email).expect("Invalid email");
+        self.emails.last_email_to(&email)
+    }
+
+    // Pulls the value of `query_param` out of the link in the last email sent to `email`
+    pub async fn get_token_from_email(&self, email: &str, query_param: &str) -> String {
+        let content = self
+            .last_email_to(email)
+            .await
+            .expect("No email was sent")
+            .text_body;
+
+        let (_, token) = content
+            .split_once(&format!("{}=", query_param))
+            .expect("Email does not contain the token");
+
+        token
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_owned()
+    }
+
     pub async fn clean_up(&mut self) {
         if self.clean_up_called {
             return;
@@ -161,6 +710,25 @@ impl Drop for TestApp {
     }
 }
 
+pub const TEST_EMAIL_RETRY_POLICY: EmailRetryPolicy = EmailRetryPolicy {
+    base_delay_seconds: 0,
+    max_delay_seconds: 0,
+    max_attempts: 3,
+};
+
+// Tests sign tokens with a fresh Ed25519 key, like a deployment with a PEM key configured
+pub fn generate_signing_key() -> SigningKey {
+    let pkcs8 =
+        Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("Failed to generate key");
+    let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref()));
+
+    SigningKey::from_pem(pem.as_bytes()).expect("Failed to parse signing key")
+}
+
+pub fn current_totp_step() -> u64 {
+    chrono::Utc::now().timestamp() as u64 / TOTP_STEP_SECONDS
+}
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
@@ -237,11 +805,10 @@ async fn configure_database(db_conn_string: &str, db_name: &str) {
         .expect("Failed to migrate the database");
 }
 
-fn configure_redis() -> redis::Connection {
+async fn configure_redis() -> ConnectionManager {
     let redis_hostname = DEFAULT_REDIS_HOSTNAME.to_owned();
 
-    get_redis_client(redis_hostname)
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+    get_redis_connection(redis_hostname)
+        .await
+        .expect("Failed to connect to Redis")
 }
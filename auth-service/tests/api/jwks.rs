@@ -0,0 +1,128 @@
+use auth_service::utils::{auth::Keyring, constants::JWT_COOKIE_NAME};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use test_helpers::api_test;
+
+use crate::helpers::{generate_signing_key, get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_public_key_for_token_kid() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let kid = decode_header(&token)
+        .expect("Failed to decode token header")
+        .kid
+        .expect("Token has no kid");
+
+    let jwk = jwks.find(&kid).expect("No key found for token kid");
+
+    // A relying service only needs the published key to verify the token
+    let decoding_key = DecodingKey::from_jwk(jwk).expect("Failed to build decoding key");
+
+    let claims =
+        decode::<serde_json::Value>(&token, &decoding_key, &Validation::new(Algorithm::EdDSA))
+            .expect("Failed to verify token with published key")
+            .claims;
+
+    assert_eq!(claims["sub"], random_email);
+}
+
+#[api_test]
+async fn should_accept_tokens_signed_before_key_rotation() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    // Rotate: a new key signs from now on, the old one is kept for verification
+    {
+        let mut keyring = app.keyring.write().await;
+        let retired_key = keyring.active().clone();
+        *keyring = Keyring::new(generate_signing_key()).with_verification_key(retired_key);
+    }
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let old_kid = decode_header(&old_token).unwrap().kid;
+    let new_kid = decode_header(&new_token).unwrap().kid;
+
+    assert_ne!(old_kid, new_kid);
+
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert!(jwks.find(&old_kid.unwrap()).is_some());
+    assert!(jwks.find(&new_kid.unwrap()).is_some());
+}
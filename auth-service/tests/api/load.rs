@@ -0,0 +1,178 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use auth_service::{
+    domain::{Email, Password, Role, TwoFAMethod, User, UserPage, UserStore, UserStoreError},
+    services::data_stores::HashmapUserStore,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const CONCURRENT_SIGNUPS: u32 = 20;
+const ADD_USER_DELAY: Duration = Duration::from_millis(200);
+
+// Stands in for a store that is slow to add users, e.g. because of password hashing or a
+// busy database
+struct SlowUserStore {
+    inner: HashmapUserStore,
+}
+
+#[async_trait::async_trait]
+impl UserStore for SlowUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        tokio::time::sleep(ADD_USER_DELAY).await;
+        self.inner.add_user(user).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.inner.get_user(email).await
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        self.inner.validate_user(email, password).await
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        self.inner.update_password(email, password).await
+    }
+
+    async fn mark_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.mark_verified(email).await
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        two_fa_method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_two_fa_method(email, two_fa_method).await
+    }
+
+    async fn update_email(&self, email: &Email, new_email: Email) -> Result<(), UserStoreError> {
+        self.inner.update_email(email, new_email).await
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_requires_2fa(email, requires_2fa).await
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.delete_user(email).await
+    }
+
+    async fn get_roles(&self, email: &Email) -> Result<Vec<Role>, UserStoreError> {
+        self.inner.get_roles(email).await
+    }
+
+    async fn assign_role(&self, email: &Email, role: Role) -> Result<(), UserStoreError> {
+        self.inner.assign_role(email, role).await
+    }
+
+    async fn remove_role(&self, email: &Email, role: &Role) -> Result<(), UserStoreError> {
+        self.inner.remove_role(email, role).await
+    }
+
+    async fn set_disabled(&self, email: &Email, disabled: bool) -> Result<(), UserStoreError> {
+        self.inner.set_disabled(email, disabled).await
+    }
+
+    async fn list_users(
+        &self,
+        search: Option<&str>,
+        offset: u64,
+        limit: u64,
+    ) -> Result<UserPage, UserStoreError> {
+        self.inner.list_users(search, offset, limit).await
+    }
+}
+
+#[tokio::test]
+async fn concurrent_signups_should_not_wait_for_each_other() {
+    let mut app = TestApp::with_user_store(Arc::new(SlowUserStore {
+        inner: HashmapUserStore::default(),
+    }))
+    .await;
+
+    let started = Instant::now();
+    let signups: Vec<_> = (0..CONCURRENT_SIGNUPS)
+        .map(|_| {
+            let request = app
+                .http_client
+                .post(format!("{}/signup", app.address))
+                .json(&serde_json::json!({
+                    "email": get_random_email(),
+                    "password": "Sup3r-S3cret-pw",
+                    "requires2FA": false
+                }))
+                .send();
+
+            tokio::spawn(async move { request.await.expect("Failed to execute request.") })
+        })
+        .collect();
+
+    for signup in signups {
+        assert_eq!(signup.await.unwrap().status().as_u16(), 201);
+    }
+
+    // One after the other they would take CONCURRENT_SIGNUPS * ADD_USER_DELAY
+    let elapsed = started.elapsed();
+    assert!(
+        elapsed < ADD_USER_DELAY * 5,
+        "{} signups took {:?}",
+        CONCURRENT_SIGNUPS,
+        elapsed
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn concurrent_signups_for_same_email_should_create_one_user() {
+    let mut app = TestApp::with_user_store(Arc::new(SlowUserStore {
+        inner: HashmapUserStore::default(),
+    }))
+    .await;
+    let email = get_random_email();
+
+    let signups: Vec<_> = (0..CONCURRENT_SIGNUPS)
+        .map(|_| {
+            let request = app
+                .http_client
+                .post(format!("{}/signup", app.address))
+                .json(&serde_json::json!({
+                    "email": email,
+                    "password": "Sup3r-S3cret-pw",
+                    "requires2FA": false
+                }))
+                .send();
+
+            tokio::spawn(async move { request.await.expect("Failed to execute request.") })
+        })
+        .collect();
+
+    let mut statuses: Vec<u16> = Vec::new();
+    for signup in signups {
+        statuses.push(signup.await.unwrap().status().as_u16());
+    }
+
+    assert_eq!(statuses.iter().filter(|&&status| status == 201).count(), 1);
+    assert!(statuses
+        .iter()
+        .all(|&status| status == 201 || status == 409));
+
+    app.clean_up().await;
+}
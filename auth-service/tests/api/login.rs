@@ -1,6 +1,11 @@
-use crate::helpers::{get_random_email, TestApp};
-use auth_service::domain::Email;
-use auth_service::routes::TwoFactorAuthResponse;
+use crate::helpers::{get_random_email, TestApp, TRUSTED_PROXY_HEADER};
+use argon2::Params;
+use auth_service::app_state::EmailVerificationPolicy;
+use auth_service::domain::{
+    Email, LoginAttemptKey, Password, TwoFAMethod, User, UserStore, EMAIL_LOGIN_THROTTLE,
+};
+use auth_service::routes::{TokensResponse, TwoFactorAuthResponse};
+use auth_service::services::data_stores::PostgresUserStore;
 use auth_service::utils::constants::JWT_COOKIE_NAME;
 use auth_service::ErrorResponse;
 use test_helpers::api_test;
@@ -11,7 +16,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -21,7 +26,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
     });
 
     let response = app.post_login(&login_body).await;
@@ -36,13 +41,60 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     assert!(!auth_cookie.value().is_empty());
 }
 
+#[api_test]
+async fn should_return_tokens_in_body_if_bearer_mode_negotiated() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let by_query = app.post_login_bearer(&login_body).await;
+    let by_accept_header = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("Accept", "application/vnd.auth-service.bearer+json")
+        .json(&login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    for response in [by_query, by_accept_header] {
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.cookies().count(), 0);
+
+        let tokens = response
+            .json::<TokensResponse>()
+            .await
+            .expect("Could not deserialize response body to TokensResponse");
+
+        assert_eq!(tokens.token_type, "Bearer");
+        assert!(!tokens.refresh_token.is_empty());
+
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
 #[api_test]
 async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let random_email = get_random_email();
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -52,7 +104,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "Sup3r-S3cret-pw"
     });
 
     let response = app.post_login(&login_body).await;
@@ -66,9 +118,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let two_fa_code_store = app.two_fa_code_store.read().await;
-
-    let code_tuple = two_fa_code_store
+    let code_tuple = app
+        .two_fa_code_store
         .get_code(&Email::parse(&random_email).unwrap())
         .await
         .expect("Failed to get 2FA code");
@@ -82,7 +133,7 @@ async fn should_return_400_if_invalid_input() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -91,9 +142,9 @@ async fn should_return_400_if_invalid_input() {
     assert_eq!(response.status().as_u16(), 201);
 
     let test_cases = vec![
-        ("invalid_email", "password123"),
+        ("invalid_email", "Sup3r-S3cret-pw"),
         (random_email.as_str(), "invalid"),
-        ("", "password123"),
+        ("", "Sup3r-S3cret-pw"),
         (random_email.as_str(), ""),
         ("", ""),
     ];
@@ -129,7 +180,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -139,7 +190,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let test_cases = vec![
         (random_email.as_str(), "wrong-password"),
-        ("wrong@email.com", "password123"),
+        ("wrong@email.com", "Sup3r-S3cret-pw"),
         ("wrong@email.com", "wrong-password"),
     ];
 
@@ -174,7 +225,7 @@ async fn should_return_422_if_malformed_credentials() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -184,7 +235,7 @@ async fn should_return_422_if_malformed_credentials() {
 
     let test_cases = [
         serde_json::json!({
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
         }),
         serde_json::json!({
             "email": random_email,
@@ -202,4 +253,358 @@ async fn should_return_422_if_malformed_credentials() {
             test_case
         );
     }
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn should_return_403_if_email_not_verified_and_verification_required() {
+    let mut app = TestApp::with_email_verification_policy(EmailVerificationPolicy::Required).await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+
+    assert!(response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .is_none());
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Email address not verified".to_owned()
+    );
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[api_test]
+async fn should_return_429_if_account_locked_out() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let key = LoginAttemptKey::Email(Email::parse(&random_email).unwrap());
+    for _ in 0..EMAIL_LOGIN_THROTTLE.lockout_threshold {
+        app.login_attempt_store.record_attempt(&key).await.unwrap();
+    }
+    app.login_attempt_store.record_failure(&key).await.unwrap();
+
+    // Even the correct password is refused while the account is locked
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    assert!(retry_after > 0 && retry_after <= EMAIL_LOGIN_THROTTLE.lockout_seconds);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many login attempts".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_throttle_wrong_passwords_sent_in_parallel() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+
+    let responses = tokio::join!(
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+        app.post_login(&wrong_login_body),
+    );
+    let statuses = [
+        responses.0.status().as_u16(),
+        responses.1.status().as_u16(),
+        responses.2.status().as_u16(),
+        responses.3.status().as_u16(),
+        responses.4.status().as_u16(),
+        responses.5.status().as_u16(),
+        responses.6.status().as_u16(),
+        responses.7.status().as_u16(),
+    ];
+
+    // Each attempt is counted before its password is checked, so only the free attempts get
+    // one. The first delayed attempt may also get through if a second has gone by.
+    let checked = statuses.iter().filter(|status| **status == 401).count();
+    let throttled = statuses.iter().filter(|status| **status == 429).count();
+
+    assert!(checked <= EMAIL_LOGIN_THROTTLE.free_attempts as usize + 1);
+    assert_eq!(checked + throttled, statuses.len());
+}
+
+#[api_test]
+async fn should_throttle_by_address_from_trusted_proxy_header() {
+    let wrong_login_body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "wrong-password",
+    });
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header(TRUSTED_PROXY_HEADER, "198.51.100.1, 203.0.113.7")
+        .json(&wrong_login_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let forwarded_key = LoginAttemptKey::Ip("203.0.113.7".parse().unwrap());
+    let spoofed_key = LoginAttemptKey::Ip("198.51.100.1".parse().unwrap());
+
+    let failures = app
+        .login_attempt_store
+        .get_failures(&forwarded_key)
+        .await
+        .unwrap();
+
+    assert_eq!(failures.map(|f| f.count), Some(1));
+    assert_eq!(
+        app.login_attempt_store
+            .get_failures(&spoofed_key)
+            .await
+            .unwrap(),
+        None
+    );
+}
+
+#[api_test]
+async fn should_reset_account_failures_after_successful_login() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+
+    for _ in 0..2 {
+        let response = app.post_login(&wrong_login_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let email_key = LoginAttemptKey::Email(Email::parse(&random_email).unwrap());
+    let ip_key = LoginAttemptKey::Ip("127.0.0.1".parse().unwrap());
+
+    let failures = app
+        .login_attempt_store
+        .get_failures(&email_key)
+        .await
+        .unwrap();
+
+    assert_eq!(failures.map(|f| f.count), Some(2));
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        app.login_attempt_store
+            .get_failures(&email_key)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // The client's address keeps its count
+    let failures = app.login_attempt_store.get_failures(&ip_key).await.unwrap();
+
+    assert_eq!(failures.map(|f| f.count), Some(2));
+}
+
+#[api_test]
+async fn should_rehash_password_made_with_outdated_params_on_login() {
+    let random_email = get_random_email();
+    let email = Email::parse(&random_email).unwrap();
+
+    // Stands in for a user who signed up before the cost was raised
+    let outdated_user_store = PostgresUserStore::new(app.pg_pool.clone())
+        .with_password_hash_params(Params::new(8192, 1, 1, None).unwrap());
+    outdated_user_store
+        .add_user(User::new(
+            email.clone(),
+            Password::parse("Sup3r-S3cret-pw").unwrap(),
+            TwoFAMethod::None,
+        ))
+        .await
+        .unwrap();
+
+    let outdated_hash = app.user_store.get_user(&email).await.unwrap().password;
+    assert!(outdated_hash.as_ref().contains("m=8192,t=1,p=1"));
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    let current_hash = app.user_store.get_user(&email).await.unwrap().password;
+    assert!(current_hash.as_ref().contains("m=15000,t=2,p=1"));
+
+    // The upgraded hash still verifies the same password
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_take_similar_time_for_unknown_and_known_emails() {
+    const SAMPLES: usize = 15;
+    // Without a dummy hash an unknown email answers hundreds of times faster
+    const MAX_RELATIVE_DIFFERENCE: f64 = 0.5;
+
+    let known_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": known_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let ip_key = LoginAttemptKey::Ip("127.0.0.1".parse().unwrap());
+    let known_email_key = LoginAttemptKey::Email(Email::parse(&known_email).unwrap());
+
+    let mut known_durations = Vec::with_capacity(SAMPLES);
+    let mut unknown_durations = Vec::with_capacity(SAMPLES);
+
+    // One extra round to warm up, and interleaved so that load on the machine hits both alike
+    for round in 0..=SAMPLES {
+        for (email, durations) in [
+            (known_email.clone(), &mut known_durations),
+            (get_random_email(), &mut unknown_durations),
+        ] {
+            let login_body = serde_json::json!({
+                "email": email,
+                "password": "wrong-password",
+            });
+
+            let started = std::time::Instant::now();
+            let response = app.post_login(&login_body).await;
+            let elapsed = started.elapsed().as_secs_f64();
+
+            assert_eq!(response.status().as_u16(), 401);
+
+            if round > 0 {
+                durations.push(elapsed);
+            }
+        }
+
+        // Keeps throttling from cutting attempts short
+        for key in [&ip_key, &known_email_key] {
+            app.login_attempt_store.clear_failures(key).await.unwrap();
+        }
+    }
+
+    // Other tests running alongside only ever add time, so the fastest attempts are the
+    // closest to what each kind of login costs
+    let known_fastest = fastest(&known_durations);
+    let unknown_fastest = fastest(&unknown_durations);
+    let relative_difference =
+        (known_fastest - unknown_fastest).abs() / known_fastest.max(unknown_fastest);
+
+    assert!(
+        relative_difference < MAX_RELATIVE_DIFFERENCE,
+        "known email took {:.1}ms, unknown email took {:.1}ms",
+        known_fastest * 1000.0,
+        unknown_fastest * 1000.0
+    );
+}
+
+fn fastest(durations: &[f64]) -> f64 {
+    durations.iter().copied().fold(f64::INFINITY, f64::min)
+}
@@ -10,7 +10,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -20,7 +20,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
     });
 
     let response = app.post_login(&login_body).await;
@@ -47,8 +47,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert!(auth_cookie.value().is_empty());
 
-    let banned_token_store = app.banned_token_store.read().await;
-    let contains_token = banned_token_store
+    let contains_token = app
+        .banned_token_store
         .contains_token(token)
         .await
         .expect("Failed to check if token is banned");
@@ -88,7 +88,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -98,7 +98,7 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
     });
 
     let response = app.post_login(&login_body).await;
@@ -165,3 +165,28 @@ async fn should_return_401_if_invalid_token() {
         "Invalid auth token".to_owned()
     );
 }
+
+#[api_test]
+async fn should_logout_with_bearer_token_and_revoke_refresh_token() {
+    let tokens = app.signup_and_login_bearer(&get_random_email()).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/logout", &app.address))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_refresh_bearer(&tokens.refresh_token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -1,7 +1,21 @@
+mod account;
+mod admin;
+mod email_outbox;
 mod helpers;
+mod jwks;
+mod load;
 mod login;
 mod logout;
+mod password_reset;
+mod recovery_codes;
+mod redis_connection;
+mod refresh;
 mod root;
+mod sessions;
 mod signup;
+mod smtp_email_client;
+mod smtp_server;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
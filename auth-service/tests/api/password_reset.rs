@@ -0,0 +1,330 @@
+use auth_service::{
+    domain::{Email, PasswordResetToken},
+    get_redis_connection,
+    routes::PasswordResetResponse,
+    utils::constants::{DEFAULT_REDIS_HOSTNAME, JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use redis::AsyncCommands;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn add_reset_token(app: &TestApp, email: &str) -> String {
+    let token = PasswordResetToken::default();
+
+    app.password_reset_token_store
+        .add_token(Email::parse(email).unwrap(), token.clone())
+        .await
+        .expect("Failed to add password reset token");
+
+    token.as_ref().to_owned()
+}
+
+#[api_test]
+async fn should_return_200_for_known_and_unknown_emails() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    for email in [random_email, get_random_email()] {
+        let response = app
+            .post_password_reset_request(&serde_json::json!({ "email": email }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        assert_eq!(
+            response
+                .json::<PasswordResetResponse>()
+                .await
+                .expect("Could not deserialize response body to PasswordResetResponse")
+                .message,
+            "If the account exists, a password reset link has been sent".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_email() {
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "invalid_email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_reset_password_and_revoke_sessions() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    assert!(response
+        .cookies()
+        .any(|cookie| cookie.name() == REFRESH_COOKIE_NAME));
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app.get_token_from_email(&random_email, "reset_token").await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The old access token and refresh token are no longer accepted
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Only the new password works from now on
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "new_password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_401_if_token_used_twice() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = add_reset_token(&app, &random_email).await;
+
+    let confirm_body = serde_json::json!({
+        "token": token,
+        "newPassword": "new_password123"
+    });
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&confirm_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_unknown_token() {
+    let test_cases = [
+        PasswordResetToken::default().as_ref().to_owned(),
+        "invalid_token".to_owned(),
+    ];
+
+    for token in test_cases {
+        let response = app
+            .post_password_reset_confirm(&serde_json::json!({
+                "token": token,
+                "newPassword": "new_password123"
+            }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for token: {}",
+            token
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_new_password() {
+    let random_email = get_random_email();
+    let token = add_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "short"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid credentials".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_422_if_malformed_input() {
+    let response = app
+        .post_password_reset_request(&serde_json::json!({}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 422);
+
+    let test_cases = [
+        serde_json::json!({ "token": "token" }),
+        serde_json::json!({ "newPassword": "new_password123" }),
+        serde_json::json!({}),
+    ];
+
+    for test_case in test_cases {
+        let response = app.post_password_reset_confirm(&test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[api_test]
+async fn should_keep_token_if_new_password_breaks_policy() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let token = add_reset_token(&app, &random_email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "password1"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .reasons,
+        vec!["breached"]
+    );
+
+    // The same link still works with a better password
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "new_password123"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_keep_token_expiry_if_new_password_breaks_policy() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let token = add_reset_token(&app, &random_email).await;
+
+    // Stands in for a link that was sent a while ago
+    let mut conn = get_redis_connection(DEFAULT_REDIS_HOSTNAME.to_owned())
+        .await
+        .expect("Failed to connect to Redis");
+    let key = format!("password_reset_token:{}", token);
+    let _: () = conn.expire(&key, 60).await.unwrap();
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "newPassword": "password1"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let ttl: i64 = conn.ttl(&key).await.unwrap();
+
+    assert!(ttl > 0 && ttl <= 60, "TTL was {}", ttl);
+}
@@ -0,0 +1,333 @@
+use auth_service::{
+    domain::RECOVERY_CODE_BATCH_SIZE,
+    routes::{RecoveryCodesResponse, SetTwoFAResponse, SignupResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[api_test]
+async fn should_issue_recovery_codes_when_totp_is_enabled() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_BATCH_SIZE);
+}
+
+#[api_test]
+async fn should_issue_recovery_codes_to_email_2fa_users() {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "Sup3r-S3cret-pw",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let recovery_codes = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes;
+
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_BATCH_SIZE);
+
+    // They stand in for an email code the user can't get at
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_issue_recovery_codes_when_2fa_is_turned_on() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw"
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    let response = app
+        .post_set_2fa(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let recovery_codes = response
+        .json::<SetTwoFAResponse>()
+        .await
+        .expect("Could not deserialize response body to SetTwoFAResponse")
+        .recovery_codes;
+
+    assert_eq!(recovery_codes.len(), RECOVERY_CODE_BATCH_SIZE);
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let response = app
+        .post_verify_recovery_code(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_200_if_valid_recovery_code() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    // Codes are accepted however the user chooses to type them
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0].to_uppercase()
+    });
+
+    let response = app.post_verify_recovery_code(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_return_401_if_recovery_code_used_twice() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0]
+    });
+
+    assert_eq!(
+        app.post_verify_recovery_code(&request_body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0]
+    });
+
+    let response = app.post_verify_recovery_code(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Incorrect credentials".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_login_attempt_id_does_not_match() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let old_login_attempt_id = app.login_with_2fa(&random_email).await;
+    app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": old_login_attempt_id,
+        "recoveryCode": recovery_codes[0]
+    });
+
+    let response = app.post_verify_recovery_code(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_invalid_input() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let test_cases = [
+        (
+            "invalid_email",
+            login_attempt_id.as_str(),
+            recovery_codes[0].as_str(),
+        ),
+        (
+            random_email.as_str(),
+            "invalid_login_attempt_id",
+            recovery_codes[0].as_str(),
+        ),
+        (random_email.as_str(), login_attempt_id.as_str(), "short"),
+        (
+            random_email.as_str(),
+            login_attempt_id.as_str(),
+            "not!a!code",
+        ),
+    ];
+
+    for (email, login_attempt_id, recovery_code) in test_cases {
+        let request_body = serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_code
+        });
+
+        let response = app.post_verify_recovery_code(&request_body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            request_body
+        );
+    }
+}
+
+#[api_test]
+async fn should_invalidate_old_codes_when_regenerated() {
+    let random_email = get_random_email();
+
+    let (_, _, old_recovery_codes) = app.enable_totp(&random_email).await;
+
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_recovery_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_recovery_codes.len(), RECOVERY_CODE_BATCH_SIZE);
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": old_recovery_codes[0]
+    });
+
+    assert_eq!(
+        app.post_verify_recovery_code(&request_body)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": new_recovery_codes[0]
+    });
+
+    assert_eq!(
+        app.post_verify_recovery_code(&request_body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_regenerating_with_incorrect_password() {
+    let random_email = get_random_email();
+
+    let (_, _, recovery_codes) = app.enable_totp(&random_email).await;
+
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({
+            "currentPassword": "Wr0ng-S3cret-pw"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The old codes keep working
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": recovery_codes[0]
+    });
+
+    assert_eq!(
+        app.post_verify_recovery_code(&request_body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[api_test]
+async fn should_return_400_if_regenerating_without_jwt_cookie() {
+    let response = app
+        .post_regenerate_recovery_codes(&serde_json::json!({
+            "currentPassword": "Sup3r-S3cret-pw"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,86 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use auth_service::{
+    domain::data_stores::BannedTokenStore, get_redis_connection,
+    services::data_stores::RedisBannedTokenStore, utils::constants::DEFAULT_REDIS_HOSTNAME,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+// Sits between the service and Redis so a test can cut every open connection, like a
+// Redis restart or a network blip would
+struct RedisProxy {
+    address: SocketAddr,
+    connections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl RedisProxy {
+    async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::default();
+
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            while let Ok((mut inbound, _)) = listener.accept().await {
+                let connection = tokio::spawn(async move {
+                    let mut outbound = TcpStream::connect((DEFAULT_REDIS_HOSTNAME, 6379))
+                        .await
+                        .unwrap();
+                    let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                });
+                accepted.lock().unwrap().push(connection);
+            }
+        });
+
+        Self {
+            address,
+            connections,
+        }
+    }
+
+    fn drop_connections(&self) {
+        for connection in self.connections.lock().unwrap().iter() {
+            connection.abort();
+        }
+    }
+
+    fn connection_count(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+}
+
+#[tokio::test]
+async fn should_reconnect_after_connection_is_dropped() {
+    let proxy = RedisProxy::start().await;
+    let connection = get_redis_connection(proxy.address.to_string())
+        .await
+        .expect("Failed to connect to Redis");
+    let store = RedisBannedTokenStore::new(connection);
+    let token = Uuid::new_v4().to_string();
+
+    store.add_token(token.clone()).await.unwrap();
+    assert!(store.contains_token(&token).await.unwrap());
+
+    proxy.drop_connections();
+
+    // The command that notices the dropped connection may fail, but the ones after it
+    // go over a fresh connection
+    let started = Instant::now();
+    while !matches!(store.contains_token(&token).await, Ok(true)) {
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "Store never reconnected to Redis"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(proxy.connection_count(), 2);
+}
@@ -0,0 +1,210 @@
+use auth_service::{
+    domain::{RefreshToken, RefreshTokenStoreError},
+    routes::TokensResponse,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    refresh_token
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_COOKIE_NAME, token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[api_test]
+async fn should_return_200_and_rotate_if_valid_refresh_cookie() {
+    let refresh_token = signup_and_login(&app).await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let refresh_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found");
+
+    assert_ne!(refresh_cookie.value(), refresh_token);
+    assert!(refresh_cookie.http_only());
+
+    // The rotated token can be used in turn
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_401_if_invalid_refresh_token() {
+    set_refresh_cookie(&app, "invalid");
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_revoke_family_if_refresh_token_reused() {
+    let first_token = signup_and_login(&app).await;
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let second_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned();
+
+    // Replaying the rotated token is rejected...
+    set_refresh_cookie(&app, &first_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // ...and takes the rest of the family down with it
+    set_refresh_cookie(&app, &second_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_refresh_token_used_after_logout() {
+    let refresh_token = signup_and_login(&app).await;
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let result = app
+        .refresh_token_store
+        .consume_token(&RefreshToken::parse(refresh_token.clone()).unwrap())
+        .await;
+
+    assert_eq!(result, Err(RefreshTokenStoreError::FamilyRevoked));
+
+    set_refresh_cookie(&app, &refresh_token);
+
+    let response = app.post_refresh().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_rotate_refresh_token_sent_in_body() {
+    let tokens = app.signup_and_login_bearer(&get_random_email()).await;
+
+    let response = app.post_refresh_bearer(&tokens.refresh_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.cookies().count(), 0);
+
+    let rotated = response
+        .json::<TokensResponse>()
+        .await
+        .expect("Could not deserialize response body to TokensResponse");
+
+    assert_ne!(rotated.refresh_token, tokens.refresh_token);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": rotated.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The old token was rotated out
+    let response = app.post_refresh_bearer(&tokens.refresh_token).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_rotate_refresh_token_only_once_if_used_concurrently() {
+    let tokens = app.signup_and_login_bearer(&get_random_email()).await;
+
+    let (first, second) = tokio::join!(
+        app.post_refresh_bearer(&tokens.refresh_token),
+        app.post_refresh_bearer(&tokens.refresh_token)
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+
+    assert_eq!(statuses, [200, 401]);
+}
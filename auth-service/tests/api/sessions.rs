@@ -0,0 +1,239 @@
+use auth_service::{routes::SessionsResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp, TEST_USER_AGENT};
+
+// Signs up and logs in `email`, returning the access token
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    login(app, email).await
+}
+
+async fn login(app: &TestApp, email: &str) -> String {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    token
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+async fn get_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+#[api_test]
+async fn should_list_sessions() {
+    let random_email = get_random_email();
+
+    signup_and_login(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    let sessions = get_sessions(&app).await.sessions;
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+
+    let session = &sessions[0];
+    assert_eq!(session.device, "Firefox on Linux");
+    assert_eq!(session.ip, "127.0.0.1");
+    assert_eq!(session.user_agent.as_deref(), Some(TEST_USER_AGENT));
+    assert!(session.last_seen >= session.created_at);
+}
+
+#[api_test]
+async fn should_revoke_other_session_immediately() {
+    let random_email = get_random_email();
+
+    let old_token = signup_and_login(&app, &random_email).await;
+    let current_token = login(&app, &random_email).await;
+
+    let old_session = get_sessions(&app)
+        .await
+        .sessions
+        .into_iter()
+        .find(|session| !session.current)
+        .expect("No other session found");
+
+    let response = app
+        .post_revoke_session(&serde_json::json!({ "sessionId": old_session.id }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &old_token).await, 401);
+    assert_eq!(verify_token_status(&app, &current_token).await, 200);
+    assert_eq!(get_sessions(&app).await.sessions.len(), 1);
+}
+
+#[api_test]
+async fn should_return_404_if_revoking_unknown_session() {
+    let random_email = get_random_email();
+
+    signup_and_login(&app, &random_email).await;
+
+    // Someone else's session can't be revoked either
+    let other_email = get_random_email();
+    signup_and_login(&app, &other_email).await;
+    let other_session_id = get_sessions(&app).await.sessions[0].id.clone();
+    login(&app, &random_email).await;
+
+    for session_id in ["unknown", other_session_id.as_str()] {
+        let response = app
+            .post_revoke_session(&serde_json::json!({ "sessionId": session_id }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 404);
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Session not found".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_revoke_all_sessions() {
+    let random_email = get_random_email();
+
+    let old_token = signup_and_login(&app, &random_email).await;
+    let current_token = login(&app, &random_email).await;
+
+    let response = app.post_revoke_all_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &old_token).await, 401);
+    assert_eq!(verify_token_status(&app, &current_token).await, 401);
+}
+
+#[api_test]
+async fn should_not_bring_back_session_refreshed_while_revoking() {
+    let random_email = get_random_email();
+
+    signup_and_login(&app, &random_email).await;
+
+    let (refresh, revoke) = tokio::join!(app.post_refresh(), app.post_revoke_all_sessions());
+
+    assert_eq!(revoke.status().as_u16(), 200);
+    assert!([200, 401].contains(&refresh.status().as_u16()));
+
+    // Whichever ran first, only the session started now is left
+    login(&app, &random_email).await;
+
+    assert_eq!(get_sessions(&app).await.sessions.len(), 1);
+}
+
+#[api_test]
+async fn should_end_session_on_logout() {
+    let random_email = get_random_email();
+
+    let token = signup_and_login(&app, &random_email).await;
+    login(&app, &random_email).await;
+
+    assert_eq!(get_sessions(&app).await.sessions.len(), 2);
+
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    // Log back in to look at what is left
+    login(&app, &random_email).await;
+
+    assert_eq!(get_sessions(&app).await.sessions.len(), 2);
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+}
+
+#[api_test]
+async fn should_return_400_if_listing_sessions_without_jwt_cookie() {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_accept_bearer_token_instead_of_cookie() {
+    let random_email = get_random_email();
+
+    let token = signup_and_login(&app, &random_email).await;
+
+    // A client without the cookie jar
+    let response = reqwest::Client::new()
+        .get(format!("{}/sessions", &app.address))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let sessions = response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+        .sessions;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
+
+#[api_test]
+async fn should_return_401_if_authorization_header_is_not_bearer() {
+    let random_email = get_random_email();
+
+    signup_and_login(&app, &random_email).await;
+
+    // The valid cookie is ignored once an Authorization header is sent
+    let response = app
+        .http_client
+        .get(format!("{}/sessions", &app.address))
+        .basic_auth(&random_email, Some("Sup3r-S3cret-pw"))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
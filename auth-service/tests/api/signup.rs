@@ -1,4 +1,11 @@
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use auth_service::{
+    domain::{
+        Email, LocalPartPolicy, Password, TwoFAMethod, User, UserStoreError,
+        RECOVERY_CODE_BATCH_SIZE,
+    },
+    routes::{SignupResponse, VerifyEmailResponse},
+    ErrorResponse,
+};
 use test_helpers::api_test;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -9,7 +16,7 @@ async fn should_return_201_if_valid_input() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -17,17 +24,14 @@ async fn should_return_201_if_valid_input() {
 
     assert_eq!(response.status().as_u16(), 201);
 
-    let expected_response = SignupResponse {
-        message: "User created successfully!".to_owned(),
-    };
+    let response = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
 
-    assert_eq!(
-        response
-            .json::<SignupResponse>()
-            .await
-            .expect("Could not deserialize response body to UserBody"),
-        expected_response
-    );
+    assert_eq!(response.message, "User created successfully!".to_owned());
+    // Signing up with 2FA hands out recovery codes
+    assert_eq!(response.recovery_codes.len(), RECOVERY_CODE_BATCH_SIZE);
 }
 
 #[api_test]
@@ -37,7 +41,7 @@ async fn should_return_400_if_invalid_input() {
     let input = [
         serde_json::json!({
             "email": "",
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
             "requires2FA": true
         }),
         serde_json::json!({
@@ -52,7 +56,7 @@ async fn should_return_400_if_invalid_input() {
         }),
         serde_json::json!({
             "email": "invalid_email",
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
             "requires2FA": true
         }),
         serde_json::json!({
@@ -77,13 +81,56 @@ async fn should_return_400_if_invalid_input() {
     }
 }
 
+#[api_test]
+async fn should_return_400_with_reasons_if_password_breaks_policy() {
+    let test_cases = [
+        ("password", vec!["too_weak", "breached"]),
+        ("12345678", vec!["too_weak", "breached"]),
+        ("P@ssw0rd", vec!["breached"]),
+        ("{local_part}-Xq9!", vec!["contains_email"]),
+    ];
+
+    for (password, expected_reasons) in test_cases {
+        let random_email = get_random_email();
+        let local_part = random_email.split('@').next().unwrap();
+        let password = password.replace("{local_part}", local_part);
+
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": random_email,
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for password: {}",
+            password
+        );
+
+        let body = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+
+        assert_eq!(body.error, "Password does not meet the password policy");
+        assert_eq!(
+            body.reasons, expected_reasons,
+            "Failed for password: {}",
+            password
+        );
+    }
+}
+
 #[api_test]
 async fn should_return_409_if_email_already_exists() {
     let random_email = get_random_email();
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -111,7 +158,7 @@ async fn should_return_422_if_malformed_input() {
 
     let test_cases = [
         serde_json::json!({
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
             "requires2FA": true
         }),
         serde_json::json!({
@@ -120,11 +167,11 @@ async fn should_return_422_if_malformed_input() {
         }),
         serde_json::json!({
             "email": random_email,
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
         }),
         serde_json::json!({
             "email": random_email,
-            "password": "password123",
+            "password": "Sup3r-S3cret-pw",
             "requires2FA": "true"
         }),
         serde_json::json!({}),
@@ -139,4 +186,164 @@ async fn should_return_422_if_malformed_input() {
             test_case
         );
     }
-}
\ No newline at end of file
+}
+
+#[api_test]
+async fn should_send_verification_email() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No verification email was sent");
+
+    assert_eq!(email.subject, "Verify your email address");
+    assert!(email.html_body.contains("verify_email_token="));
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    assert!(!token.is_empty());
+}
+
+#[api_test]
+async fn should_send_verification_email_in_preferred_language() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .header("Accept-Language", "fr-FR, es;q=0.8, en;q=0.5")
+        .json(&signup_body)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email = app
+        .last_email_to(&random_email)
+        .await
+        .expect("No verification email was sent");
+
+    assert_eq!(email.subject, "Verifica tu dirección de correo");
+    assert!(email.html_body.contains("lang=\"es\""));
+    assert!(email.text_body.contains("verify_email_token="));
+}
+
+#[api_test]
+async fn should_verify_email_with_emailed_token() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        response
+            .json::<VerifyEmailResponse>()
+            .await
+            .expect("Could not deserialize response body to VerifyEmailResponse")
+            .message,
+        "Email verified successfully!".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_treat_differently_cased_emails_as_one_account() {
+    let local_part = uuid::Uuid::new_v4().to_string();
+
+    let signup_body = serde_json::json!({
+        "email": format!("{}@Example.COM", local_part.to_uppercase()),
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let signup_body = serde_json::json!({
+        "email": format!("{}@example.com", local_part),
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 409);
+
+    let login_body = serde_json::json!({
+        "email": format!("  {}@EXAMPLE.com", local_part),
+        "password": "Sup3r-S3cret-pw",
+    });
+
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_not_store_emails_differing_only_by_case() {
+    let local_part = uuid::Uuid::new_v4().to_string().to_uppercase();
+    let parse =
+        |email: String| Email::parse_with_policy(&email, LocalPartPolicy::CaseSensitive).unwrap();
+    let email = parse(format!("{}@example.com", local_part));
+    let differently_cased_email = parse(format!("{}@example.com", local_part.to_lowercase()));
+
+    assert_ne!(email, differently_cased_email);
+
+    app.user_store
+        .add_user(User::new(
+            email.clone(),
+            Password::parse("Sup3r-S3cret-pw").unwrap(),
+            TwoFAMethod::None,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        app.user_store
+            .add_user(User::new(
+                differently_cased_email.clone(),
+                Password::parse("Sup3r-S3cret-pw").unwrap(),
+                TwoFAMethod::None,
+            ))
+            .await,
+        Err(UserStoreError::UserAlreadyExists)
+    );
+
+    // Lookups match the canonical form exactly
+    assert!(app.user_store.get_user(&email).await.is_ok());
+    assert!(matches!(
+        app.user_store.get_user(&differently_cased_email).await,
+        Err(UserStoreError::UserNotFound)
+    ));
+}
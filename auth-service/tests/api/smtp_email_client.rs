@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use auth_service::{
+    domain::{Email, EmailClient, EmailMessage},
+    services::smtp_email_client::{SmtpEmailClient, SmtpSecurity},
+};
+
+use crate::smtp_server::{TestSmtpServer, TestSmtpServerOptions};
+
+fn recipient() -> Email {
+    Email::parse("recipient@example.com").unwrap()
+}
+
+fn message() -> EmailMessage {
+    EmailMessage {
+        subject: "Your 2FA code".to_owned(),
+        html_body: "<p>123456</p>".to_owned(),
+        text_body: "123456".to_owned(),
+    }
+}
+
+#[tokio::test]
+async fn should_send_email_over_starttls_with_credentials() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions::default()).await;
+    let mut settings = server.settings();
+    settings.username = Some("mailer".to_owned());
+    settings.password = "hunter2".to_owned();
+    let client = SmtpEmailClient::new(settings).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert_eq!(result, Ok(()));
+    let received = server.received();
+    assert_eq!(received.len(), 1);
+    let email = &received[0];
+    assert!(email.tls);
+    assert_eq!(
+        email.credentials,
+        Some(("mailer".to_owned(), "hunter2".to_owned()))
+    );
+    assert_eq!(email.from, "no-reply@example.com");
+    assert_eq!(email.recipients, vec!["recipient@example.com".to_owned()]);
+    assert!(email.data.contains("Subject: Your 2FA code"));
+    assert!(email.data.contains("multipart/alternative"));
+    assert!(email.data.contains("text/plain"));
+    assert!(email.data.contains("text/html"));
+    assert!(email.data.contains("123456"));
+}
+
+#[tokio::test]
+async fn should_send_email_over_implicit_tls() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions {
+        security: SmtpSecurity::Tls,
+        ..Default::default()
+    })
+    .await;
+    let client = SmtpEmailClient::new(server.settings()).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert_eq!(result, Ok(()));
+    let received = server.received();
+    assert_eq!(received.len(), 1);
+    assert!(received[0].tls);
+    assert_eq!(received[0].credentials, None);
+}
+
+#[tokio::test]
+async fn should_reject_relay_with_untrusted_certificate() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions::default()).await;
+    let mut settings = server.settings();
+    settings.ca_certificate = None;
+    let client = SmtpEmailClient::new(settings).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert!(result.is_err());
+    assert!(server.received().is_empty());
+    // Certificate errors aren't retried
+    assert_eq!(server.connections(), 1);
+}
+
+#[tokio::test]
+async fn should_retry_when_relay_is_temporarily_unavailable() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions {
+        unavailable_connections: 2,
+        ..Default::default()
+    })
+    .await;
+    let client = SmtpEmailClient::new(server.settings()).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert_eq!(result, Ok(()));
+    assert_eq!(server.connections(), 3);
+    assert_eq!(server.received().len(), 1);
+}
+
+#[tokio::test]
+async fn should_give_up_after_max_retries() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions {
+        unavailable_connections: 10,
+        ..Default::default()
+    })
+    .await;
+    let client = SmtpEmailClient::new(server.settings()).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert!(result.is_err());
+    assert_eq!(server.connections(), 3);
+}
+
+#[tokio::test]
+async fn should_not_retry_permanent_rejection() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions {
+        reject_recipients: true,
+        ..Default::default()
+    })
+    .await;
+    let client = SmtpEmailClient::new(server.settings()).unwrap();
+
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert!(result.is_err());
+    assert_eq!(server.connections(), 1);
+    assert!(server.received().is_empty());
+}
+
+#[tokio::test]
+async fn should_time_out_when_relay_does_not_respond() {
+    let server = TestSmtpServer::start(TestSmtpServerOptions {
+        unresponsive: true,
+        ..Default::default()
+    })
+    .await;
+    let mut settings = server.settings();
+    settings.timeout = Duration::from_millis(200);
+    settings.max_retries = 0;
+    let client = SmtpEmailClient::new(settings).unwrap();
+
+    let started = Instant::now();
+    let result = client.send_email(&recipient(), &message()).await;
+
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,293 @@
+// A minimal in-process SMTP relay, so the SMTP email client can be tested offline
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use auth_service::services::smtp_email_client::{SmtpSecurity, SmtpSettings};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{
+    rustls::{pki_types::PrivateKeyDer, ServerConfig},
+    TlsAcceptor,
+};
+
+pub const SMTP_CA_CERTIFICATE: &[u8] = include_bytes!("../fixtures/smtp_ca.pem");
+const SMTP_SERVER_CERTIFICATE: &[u8] = include_bytes!("../fixtures/smtp_server_cert.pem");
+const SMTP_SERVER_KEY: &[u8] = include_bytes!("../fixtures/smtp_server_key.pem");
+
+#[derive(Clone, Debug, Default)]
+pub struct ReceivedEmail {
+    pub from: String,
+    pub recipients: Vec<String>,
+    pub data: String,
+    pub credentials: Option<(String, String)>,
+    pub tls: bool,
+}
+
+#[derive(Clone)]
+pub struct TestSmtpServerOptions {
+    pub security: SmtpSecurity,
+    // Connections turned away with a 421 before the relay starts accepting mail
+    pub unavailable_connections: usize,
+    pub reject_recipients: bool,
+    // Accepts connections but never sends a greeting
+    pub unresponsive: bool,
+}
+
+impl Default for TestSmtpServerOptions {
+    fn default() -> Self {
+        Self {
+            security: SmtpSecurity::StartTls,
+            unavailable_connections: 0,
+            reject_recipients: false,
+            unresponsive: false,
+        }
+    }
+}
+
+pub struct TestSmtpServer {
+    pub port: u16,
+    security: SmtpSecurity,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestSmtpServer {
+    pub async fn start(options: TestSmtpServerOptions) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind SMTP server");
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let server = Self {
+            port,
+            security: options.security,
+            received: received.clone(),
+            connections: connections.clone(),
+        };
+
+        let acceptor = tls_acceptor();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let connection = connections.fetch_add(1, Ordering::SeqCst);
+                let options = options.clone();
+                let acceptor = acceptor.clone();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let _ =
+                        handle_connection(stream, connection, options, acceptor, received).await;
+                });
+            }
+        });
+
+        server
+    }
+
+    // Client settings pointing at this server, trusting its test CA
+    pub fn settings(&self) -> SmtpSettings {
+        SmtpSettings {
+            host: "localhost".to_owned(),
+            port: Some(self.port),
+            security: self.security,
+            username: None,
+            password: String::new(),
+            sender: "Auth Service <no-reply@example.com>".to_owned(),
+            timeout: Duration::from_secs(2),
+            max_retries: 2,
+            retry_delay: Duration::from_millis(10),
+            max_retry_delay: Duration::from_millis(100),
+            ca_certificate: Some(SMTP_CA_CERTIFICATE.to_vec()),
+        }
+    }
+
+    pub fn received(&self) -> Vec<ReceivedEmail> {
+        self.received.lock().unwrap().clone()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+fn tls_acceptor() -> TlsAcceptor {
+    let certificates = rustls_pemfile::certs(&mut &SMTP_SERVER_CERTIFICATE[..])
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Invalid test certificate");
+    let key: PrivateKeyDer = rustls_pemfile::private_key(&mut &SMTP_SERVER_KEY[..])
+        .expect("Invalid test key")
+        .expect("Missing test key");
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certificates, key)
+        .expect("Invalid test certificate");
+
+    TlsAcceptor::from(Arc::new(config))
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    connection: usize,
+    options: TestSmtpServerOptions,
+    acceptor: TlsAcceptor,
+    received: Arc<Mutex<Vec<ReceivedEmail>>>,
+) -> io::Result<()> {
+    if options.unresponsive {
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        return Ok(());
+    }
+    if connection < options.unavailable_connections {
+        stream
+            .write_all(b"421 4.3.2 Service not available\r\n")
+            .await?;
+        return Ok(());
+    }
+
+    match options.security {
+        SmtpSecurity::Tls => {
+            let stream = acceptor.accept(stream).await?;
+            serve(stream, true, &options, &received).await?;
+        }
+        SmtpSecurity::StartTls => {
+            if let Some(stream) = serve(stream, false, &options, &received).await? {
+                let stream = acceptor.accept(stream).await?;
+                serve(stream, true, &options, &received).await?;
+            }
+        }
+        SmtpSecurity::None => {
+            serve(stream, false, &options, &received).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Runs an SMTP session. Returns the underlying stream when the client asks for STARTTLS.
+async fn serve<S>(
+    stream: S,
+    tls: bool,
+    options: &TestSmtpServerOptions,
+    received: &Mutex<Vec<ReceivedEmail>>,
+) -> io::Result<Option<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    let offer_starttls = options.security == SmtpSecurity::StartTls && !tls;
+    let mut email = ReceivedEmail {
+        tls,
+        ..Default::default()
+    };
+
+    if !tls || options.security == SmtpSecurity::Tls {
+        reply(&mut stream, "220 localhost ESMTP test server").await?;
+    }
+
+    loop {
+        let Some(line) = read_line(&mut stream).await? else {
+            return Ok(None);
+        };
+        let command = line.to_ascii_uppercase();
+
+        if command.starts_with("EHLO") || command.starts_with("HELO") {
+            let extensions = if offer_starttls {
+                "250-localhost\r\n250-STARTTLS\r\n250 AUTH PLAIN LOGIN"
+            } else {
+                "250-localhost\r\n250 AUTH PLAIN LOGIN"
+            };
+            reply(&mut stream, extensions).await?;
+        } else if command == "STARTTLS" && offer_starttls {
+            reply(&mut stream, "220 2.0.0 Ready to start TLS").await?;
+            return Ok(Some(stream.into_inner()));
+        } else if command.starts_with("AUTH PLAIN") {
+            let response = match line.split_whitespace().nth(2) {
+                Some(response) => response.to_owned(),
+                None => {
+                    reply(&mut stream, "334 ").await?;
+                    read_line(&mut stream).await?.unwrap_or_default()
+                }
+            };
+            let decoded = BASE64_STANDARD.decode(response).unwrap_or_default();
+            let mut parts = decoded
+                .split(|byte| *byte == 0)
+                .map(|part| String::from_utf8_lossy(part).into_owned())
+                .skip(1);
+            email.credentials = Some((
+                parts.next().unwrap_or_default(),
+                parts.next().unwrap_or_default(),
+            ));
+            reply(&mut stream, "235 2.7.0 Authentication successful").await?;
+        } else if command.starts_with("MAIL FROM:") {
+            email.from = address(&line);
+            reply(&mut stream, "250 2.1.0 OK").await?;
+        } else if command.starts_with("RCPT TO:") {
+            if options.reject_recipients {
+                reply(&mut stream, "550 5.1.1 Mailbox unavailable").await?;
+            } else {
+                email.recipients.push(address(&line));
+                reply(&mut stream, "250 2.1.5 OK").await?;
+            }
+        } else if command == "DATA" {
+            reply(&mut stream, "354 End data with <CR><LF>.<CR><LF>").await?;
+            while let Some(line) = read_line(&mut stream).await? {
+                if line == "." {
+                    break;
+                }
+                email.data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                email.data.push_str("\r\n");
+            }
+            received.lock().unwrap().push(email.clone());
+            email = ReceivedEmail {
+                tls,
+                credentials: email.credentials,
+                ..Default::default()
+            };
+            reply(&mut stream, "250 2.0.0 Queued").await?;
+        } else if command == "RSET" || command == "NOOP" {
+            reply(&mut stream, "250 2.0.0 OK").await?;
+        } else if command == "QUIT" {
+            reply(&mut stream, "221 2.0.0 Bye").await?;
+            return Ok(None);
+        } else {
+            reply(&mut stream, "502 5.5.2 Command not recognized").await?;
+        }
+    }
+}
+
+async fn read_line<S>(stream: &mut BufReader<S>) -> io::Result<Option<String>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end_matches(['\r', '\n']).to_owned()))
+}
+
+async fn reply<S>(stream: &mut BufReader<S>, message: &str) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let stream = stream.get_mut();
+    stream
+        .write_all(format!("{}\r\n", message).as_bytes())
+        .await?;
+    stream.flush().await
+}
+
+fn address(line: &str) -> String {
+    line.split_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map(|(address, _)| address.to_owned())
+        .unwrap_or_default()
+}
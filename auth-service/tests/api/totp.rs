@@ -0,0 +1,182 @@
+use auth_service::{
+    domain::TotpSecret, routes::EnrollTotpResponse, utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{current_totp_step, get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "2FACode": "123456" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[api_test]
+async fn should_return_secret_and_otpauth_uri_on_enroll() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw"
+    });
+    app.post_login(&login_body).await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let enrollment = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(TotpSecret::parse_base32(&enrollment.secret).is_ok());
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment
+        .otpauth_uri
+        .contains(&format!("secret={}", enrollment.secret)));
+}
+
+#[api_test]
+async fn should_return_401_if_confirmed_with_incorrect_code() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw"
+    });
+    app.post_login(&login_body).await;
+
+    let enrollment = app
+        .post_totp_enroll()
+        .await
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+    let secret = TotpSecret::parse_base32(&enrollment.secret).unwrap();
+
+    // A code from well outside the allowed clock skew
+    let stale_code = secret.code_at_step(current_totp_step() - 10);
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "2FACode": stale_code.as_ref() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // TOTP is not active until confirmed, so logging in still works with just the password
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[api_test]
+async fn should_require_totp_code_at_login_once_enabled() {
+    let random_email = get_random_email();
+
+    let (secret, confirmed_step, _) = app.enable_totp(&random_email).await;
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    // The confirmation code's step is already used up, so log in with the next one
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": secret.code_at_step(confirmed_step + 1).as_ref()
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[api_test]
+async fn should_return_401_if_totp_code_replayed() {
+    let random_email = get_random_email();
+
+    let (secret, confirmed_step, _) = app.enable_totp(&random_email).await;
+    let code = secret.code_at_step(confirmed_step + 1);
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code.as_ref()
+    });
+
+    assert_eq!(
+        app.post_verify_2fa(&request_body).await.status().as_u16(),
+        200
+    );
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let test_cases = [code, secret.code_at_step(confirmed_step)];
+
+    for code in test_cases {
+        let request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref()
+        });
+
+        let response = app.post_verify_2fa(&request_body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            request_body
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_409_if_totp_already_enabled() {
+    let random_email = get_random_email();
+
+    app.enable_totp(&random_email).await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 409);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "2FA is already enabled".to_owned()
+    );
+}
@@ -1,6 +1,6 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId, TwoFACode},
-    routes::TwoFactorAuthResponse,
+    domain::{Email, LoginAttemptId, TwoFACode, MAX_TWO_FA_ATTEMPTS},
+    routes::{TokensResponse, TwoFactorAuthResponse},
     utils::constants::JWT_COOKIE_NAME,
     ErrorResponse,
 };
@@ -14,7 +14,7 @@ async fn should_return_200_if_correct_code() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -24,7 +24,7 @@ async fn should_return_200_if_correct_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "Sup3r-S3cret-pw"
     });
 
     let response = app.post_login(&login_body).await;
@@ -43,8 +43,6 @@ async fn should_return_200_if_correct_code() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(&random_email.clone()).unwrap())
         .await
         .unwrap();
@@ -127,7 +125,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -139,7 +137,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "Sup3r-S3cret-pw"
     });
 
     let response = app.post_login(&login_body).await;
@@ -158,8 +156,6 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(&random_email.clone()).unwrap())
         .await
         .unwrap();
@@ -223,7 +219,7 @@ async fn should_return_401_if_old_code() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -235,7 +231,7 @@ async fn should_return_401_if_old_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "Sup3r-S3cret-pw"
     });
 
     let response = app.post_login(&login_body).await;
@@ -254,8 +250,6 @@ async fn should_return_401_if_old_code() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(&random_email.clone()).unwrap())
         .await
         .unwrap();
@@ -287,7 +281,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": true
     });
 
@@ -297,7 +291,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "Sup3r-S3cret-pw"
     });
 
     let response = app.post_login(&login_body).await;
@@ -316,8 +310,6 @@ async fn should_return_401_if_same_code_twice() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(&random_email.clone()).unwrap())
         .await
         .unwrap();
@@ -386,4 +378,172 @@ async fn should_return_422_if_malformed_input() {
             test_case
         );
     }
-}
\ No newline at end of file
+}
+#[api_test]
+async fn should_burn_code_after_too_many_incorrect_attempts() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": true
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_attempt_id = app.login_with_2fa(&random_email).await;
+
+    let code_tuple = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+
+    let two_fa_code = code_tuple.1.as_ref();
+    let incorrect_two_fa_code = if two_fa_code == "000000" {
+        "111111"
+    } else {
+        "000000"
+    };
+
+    for _ in 0..MAX_TWO_FA_ATTEMPTS {
+        let request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": incorrect_two_fa_code
+        });
+
+        let response = app.post_verify_2fa(&request_body).await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The right code no longer works; the user has to log in again
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": two_fa_code
+    });
+
+    let response = app.post_verify_2fa(&request_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert!(response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .is_none());
+}
+
+#[api_test]
+async fn should_accept_code_once_when_submitted_concurrently() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw"
+    });
+
+    let response = app.post_login(&login_body).await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+
+    let request_body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code.as_ref()
+    });
+
+    let (first, second) = tokio::join!(
+        app.post_verify_2fa(&request_body),
+        app.post_verify_2fa(&request_body)
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+
+    assert_eq!(statuses, [200, 401]);
+}
+
+#[api_test]
+async fn should_return_tokens_in_body_if_bearer_mode_negotiated() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": true
+    });
+
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw"
+    });
+
+    let response = app.post_login_bearer(&login_body).await;
+
+    // The second step negotiates the mode on its own
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-2fa?response_mode=bearer", &app.address))
+        .json(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref()
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.cookies().count(), 0);
+
+    let tokens = response
+        .json::<TokensResponse>()
+        .await
+        .expect("Could not deserialize response body to TokensResponse");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,171 @@
+use auth_service::{
+    domain::{Email, EmailVerificationToken},
+    ErrorResponse,
+};
+use test_helpers::api_test;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[api_test]
+async fn should_return_401_if_invalid_token() {
+    let test_cases = [
+        EmailVerificationToken::default().as_ref().to_owned(),
+        "invalid_token".to_owned(),
+        "".to_owned(),
+    ];
+
+    for token in test_cases {
+        let response = app
+            .post_verify_email(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for token: {}",
+            token
+        );
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned()
+        );
+    }
+}
+
+#[api_test]
+async fn should_return_401_if_token_used_twice() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    let verify_email_body = serde_json::json!({ "token": token });
+
+    let response = app.post_verify_email(&verify_email_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_verify_email(&verify_email_body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_401_if_email_changed_since_link_was_sent() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    app.user_store
+        .update_email(
+            &Email::parse(&random_email).unwrap(),
+            Email::parse(&get_random_email()).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_422_if_malformed_input() {
+    let test_cases = [serde_json::json!({ "token": true }), serde_json::json!({})];
+
+    for test_case in test_cases {
+        let response = app.post_verify_email(&test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[api_test]
+async fn should_resend_verification_email() {
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "Sup3r-S3cret-pw",
+        "requires2FA": false
+    });
+
+    let response = app.post_signup(&signup_body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let first_token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    let resend_body = serde_json::json!({ "email": random_email });
+
+    let response = app.post_resend_verification_email(&resend_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .get_token_from_email(&random_email, "verify_email_token")
+        .await;
+
+    assert_ne!(token, first_token);
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Asking again straight away is throttled
+    let response = app.post_resend_verification_email(&resend_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().get("Retry-After").is_some());
+}
+
+#[api_test]
+async fn should_not_send_verification_email_to_unknown_address() {
+    let random_email = get_random_email();
+
+    let response = app
+        .post_resend_verification_email(&serde_json::json!({ "email": random_email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.last_email_to(&random_email).await.is_none());
+}
@@ -1,4 +1,7 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{
+    utils::{auth::Claims, constants::JWT_COOKIE_NAME},
+    ErrorResponse,
+};
 use test_helpers::api_test;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -9,7 +12,7 @@ async fn should_return_200_valid_token() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -19,7 +22,7 @@ async fn should_return_200_valid_token() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
     });
 
     let response = app.post_login(&login_body).await;
@@ -74,7 +77,7 @@ async fn should_return_401_if_banned_token() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
         "requires2FA": false
     });
 
@@ -84,7 +87,7 @@ async fn should_return_401_if_banned_token() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "Sup3r-S3cret-pw",
     });
 
     let response = app.post_login(&login_body).await;
@@ -137,4 +140,73 @@ async fn should_return_422_if_malformed_input() {
         let response = app.post_verify_token(&test_case).await;
         assert_eq!(response.status().as_u16(), 422);
     }
-}
\ No newline at end of file
+}
+
+#[api_test]
+async fn should_verify_bearer_token_if_no_body() {
+    let tokens = app.signup_and_login_bearer(&get_random_email()).await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .bearer_auth(&tokens.access_token)
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .bearer_auth("invalid")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[api_test]
+async fn should_return_400_if_no_token_anywhere() {
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+}
+
+#[api_test]
+async fn should_return_claims_if_asked() {
+    let random_email = get_random_email();
+    let tokens = app.signup_and_login_bearer(&random_email).await;
+    let verify_token_body = serde_json::json!({ "token": tokens.access_token });
+
+    let response = app.post_verify_token(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().is_empty());
+
+    let response = app.post_verify_token_with_claims(&verify_token_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let claims = response
+        .json::<Claims>()
+        .await
+        .expect("Could not deserialize response body to Claims");
+    assert_eq!(claims.sub, random_email);
+    assert!(claims.roles.is_empty());
+}